@@ -2,7 +2,7 @@ use convert_case::Casing;
 use proc_macro2::TokenStream;
 use quote::quote as q;
 
-use crate::{CallFnParams, GenProxyParams};
+use crate::{CallFnParams, GenProxyParams, StreamKind};
 
 #[cfg(feature="std")]
 fn borrow_toowned() -> TokenStream {
@@ -17,85 +17,1152 @@ fn borrow_toowned() -> TokenStream {
     panic!("Cannot use borrow::ToOwned without either `std` or `alloc` features of trait-enumizer")
 }
 
+/// Path to the runtime crate, for generated code that needs its helper types.
+pub(crate) fn runtime_crate() -> TokenStream {
+    q!{::trait_enumizer}
+}
+
+/// Arguments for calling `method` from variables named like enum variant's fields
+fn call_args(method: &crate::Method) -> TokenStream {
+    let mut args = TokenStream::new();
+    for arg in &method.args {
+        let argname = &arg.name;
+        if arg.to_owned {
+            args.extend(q! {::core::borrow::Borrow::borrow(&#argname),});
+        } else {
+            args.extend(q! {#argname,});
+        }
+    }
+    args
+}
+
+/// Type of enum variant's field holding `arg`
+fn variant_field_type(arg: &crate::Argument) -> TokenStream {
+    if !arg.to_owned {
+        let ty = &arg.ty;
+        q! {#ty}
+    } else {
+        match &arg.ty {
+            syn::Type::Reference(r) => {
+                let ty = &*r.elem;
+                let toowned = borrow_toowned();
+                q! {<#ty as #toowned>::Owned}
+            }
+            _ => panic!(
+                "Argument marked with `#[enumizer_to_owned]` must be a &reference"
+            ),
+        }
+    }
+}
+
+use super::{InputData, ReceiverStyle};
+impl InputData {
+    /// Variant of the enum for `method`, with a field for each argument and a return channel
+    fn enum_variant(&self, method: &crate::Method) -> TokenStream {
+        let returnval_handler = self.params.returnval.as_ref();
+        let variant_name = method.variant_name();
+        let mut variant_params = TokenStream::new();
+        for arg in &method.args {
+            let argument_name = &arg.name;
+            let argument_type = variant_field_type(arg);
+            let mut custom_attributes = TokenStream::new();
+            for aa in &arg.enum_attr {
+                custom_attributes.extend(q! {# #aa});
+            }
+            variant_params.extend(q! {
+                #custom_attributes #argument_name : #argument_type,
+            });
+        }
+        if self.params.trace {
+            let runtime = runtime_crate();
+            variant_params.extend(q! {
+                trace: #runtime::TraceContext,
+            });
+        }
+        if self.params.sent_at {
+            let runtime = runtime_crate();
+            variant_params.extend(q! {
+                sent_at: #runtime::SentAt,
+            });
+        }
+        if let Some(return_type) = &method.ret {
+            let mut custom_attributes = TokenStream::new();
+            for aa in &method.return_attr {
+                custom_attributes.extend(q! {# #aa});
+            }
+            let returnval_macro = returnval_handler.unwrap();
+            if let Some((_, item_type)) = &method.stream {
+                let wire_item_type = self.wire_return_type(item_type);
+                variant_params.extend(q! {
+                    #custom_attributes ret : #returnval_macro ! (StreamSender<#wire_item_type>),
+                });
+            } else {
+                let wire_type = self.wire_return_type(return_type);
+                variant_params.extend(q! {
+                    #custom_attributes ret : #returnval_macro ! (Sender<#wire_type>),
+                });
+            }
+        } else {
+            if !method.return_attr.is_empty() {
+                panic!("`enumizer_return_attr[]` used in method without a return type. Add `-> ()` to force using the return channel.");
+            }
+        }
+        let mut custom_attributes = TokenStream::new();
+        for aa in &method.enum_attr {
+            custom_attributes.extend(q! {# #aa});
+        }
+
+        q! {
+            #custom_attributes #variant_name { #variant_params },
+        }
+    }
+
+    pub(crate) fn generate_enum(&self, out: &mut TokenStream) {
+        let returnval_handler = self.params.returnval.as_ref();
+        let custom_attrs = &self.params.enum_attr[..];
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let mut variants = TokenStream::new();
+        for method in &self.methods {
+            variants.extend(self.enum_variant(method));
+        }
+        for method in self.methods.iter().filter(|m| m.handle.is_some()) {
+            let runtime = runtime_crate();
+            let handle_variant_name = method.handle_variant_name();
+            let sub_enum = &method.handle.as_ref().unwrap().sub_enum;
+            let doc = format!("Call or release an object returned by `{}`", method.name);
+            variants.extend(q! {
+                #[doc = #doc]
+                #handle_variant_name { id: #runtime::registry::HandleId, msg: #runtime::registry::HandleMsg<#sub_enum> },
+            });
+        }
+        if self.params.control_variants {
+            let returnval_macro = returnval_handler.unwrap();
+            variants.extend(q! {
+                /// Stop handling messages. Handled by call functions, which return `Control::Shutdown`.
+                Shutdown,
+                /// Reply as soon as this message is handled, to check that the receiving side is alive
+                Ping { ret: #returnval_macro ! (Sender<()>) },
+                /// Reply when all messages queued before this one are handled
+                Barrier { ret: #returnval_macro ! (Sender<()>) },
+            });
+        }
+        let mut customattrs = TokenStream::new();
+        for ca in custom_attrs {
+            customattrs.extend(q! {# #ca});
+        }
+        out.extend(q! {
+            #customattrs
+            #pub_or_priv enum #enum_name {
+                #variants
+            }
+        });
+    }
+
+    /// `<Name>Query` or `<Name>Command` enum with variants of just `&self` or `&mut self` methods (`style`),
+    /// convertible to and from the full enum, with its own call function
+    pub(crate) fn generate_split_enum(&self, out: &mut TokenStream, style: ReceiverStyle) {
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let (split_name, doc) = match style {
+            ReceiverStyle::Ref => (quote::format_ident!("{}Query", self.name), format!("`&self` methods of [`{}`]", enum_name)),
+            ReceiverStyle::Mut => (quote::format_ident!("{}Command", self.name), format!("`&mut self` methods of [`{}`]", enum_name)),
+            _ => unreachable!(),
+        };
+        let methods: Vec<&crate::Method> = self.methods.iter().filter(|m| m.receiver_style == style).collect();
+        let mut variants = TokenStream::new();
+        let mut to_full = TokenStream::new();
+        let mut from_full = TokenStream::new();
+        for method in &methods {
+            let variant_name = method.variant_name();
+            let fields = self.variant_field_names(method);
+            variants.extend(self.enum_variant(method));
+            to_full.extend(q! {
+                #split_name::#variant_name { #fields } => #enum_name::#variant_name { #fields },
+            });
+            from_full.extend(q! {
+                #enum_name::#variant_name { #fields } => Ok(#split_name::#variant_name { #fields }),
+            });
+        }
+        let mut customattrs = TokenStream::new();
+        for ca in &self.params.enum_attr {
+            customattrs.extend(q! {# #ca});
+        }
+
+        // Variants of other methods never reach the call function, so `allow_panic` is harmless
+        let cfparams = CallFnParams {
+            level: style,
+            allow_panic: true,
+            extra_arg: None,
+            name: quote::format_ident!("{}", style.call_fn_name(self.params.returnval.is_some())),
+            r#async: methods.iter().any(|m| m.needs_async()),
+            dispatch: false,
+            rw_dispatch: false,
+            catch_unwind: false,
+            poison_on_panic: false,
+            panic_hook: None,
+            interceptor: None,
+            metrics: None,
+            fan_out: false,
+            restriction: None,
+        };
+        let arms = self.call_fn_match_arms(&cfparams, style);
+        let fn_name = &cfparams.name;
+        let input_trait_or_type_name = &self.name;
+        let (maybe_requirement, arg_o_with_type) = match (self.params.inherent_impl_mode, style) {
+            (false, ReceiverStyle::Ref) => (q! {<I: #input_trait_or_type_name + ?Sized>}, q! {o: &I}),
+            (false, _) => (q! {<I: #input_trait_or_type_name + ?Sized>}, q! {o: &mut I}),
+            (true, ReceiverStyle::Ref) => (q! {}, q! {o: &#input_trait_or_type_name}),
+            (true, _) => (q! {}, q! {o: &mut #input_trait_or_type_name}),
+        };
+        let maybe_returntype = match &self.params.returnval {
+            Some(returnval_handler_macro) => q! { -> ::core::result::Result<(), #returnval_handler_macro ! (SendError)>},
+            None => q! {},
+        };
+        let maybe_async = if cfparams.r#async {
+            q! {async}
+        } else {
+            q! {}
+        };
+        out.extend(q! {
+            #[doc = #doc]
+            #customattrs
+            #pub_or_priv enum #split_name {
+                #variants
+            }
+
+            impl #split_name {
+                /// Call the method on `o`
+                #[allow(unused_variables)]
+                #pub_or_priv #maybe_async fn #fn_name #maybe_requirement(self, #arg_o_with_type) #maybe_returntype {
+                    match <#enum_name as ::core::convert::From<Self>>::from(self) {
+                        #arms
+                    }
+                }
+            }
+
+            impl ::core::convert::From<#split_name> for #enum_name {
+                fn from(value: #split_name) -> Self {
+                    match value {
+                        #to_full
+                    }
+                }
+            }
+
+            impl ::core::convert::TryFrom<#enum_name> for #split_name {
+                type Error = #enum_name;
+                /// Gives the value back if it is not a variant of this enum
+                fn try_from(value: #enum_name) -> ::core::result::Result<Self, #enum_name> {
+                    #[allow(unreachable_patterns)]
+                    match value {
+                        #from_full
+                        other => Err(other),
+                    }
+                }
+            }
+        });
+    }
+
+    /// Names of all fields of `method`'s variant, like `a, b, ret,`
+    fn variant_field_names(&self, method: &crate::Method) -> TokenStream {
+        let mut fields = TokenStream::new();
+        for arg in &method.args {
+            let argname = &arg.name;
+            fields.extend(q! {#argname,});
+        }
+        if self.params.trace {
+            fields.extend(q! {trace,});
+        }
+        if self.params.sent_at {
+            fields.extend(q! {sent_at,});
+        }
+        if method.ret.is_some() {
+            fields.extend(q! {ret,});
+        }
+        fields
+    }
+
+    pub(crate) fn generate_method_info_fn(&self, out: &mut TokenStream) {
+        let runtime = runtime_crate();
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let enum_name_literal = proc_macro2::Literal::string(&enum_name.to_string());
+        let mut arms = TokenStream::new();
+        let mut add_arm = |pattern: TokenStream, name: String, has_return_value: bool| {
+            let name = proc_macro2::Literal::string(&name);
+            arms.extend(q! {
+                #pattern => &#runtime::MethodInfo {
+                    name: #name,
+                    enum_name: #enum_name_literal,
+                    has_return_value: #has_return_value,
+                },
+            });
+        };
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            add_arm(q! {#enum_name::#variant_name { .. }}, method.name.to_string(), method.ret.is_some());
+        }
+        for method in self.methods.iter().filter(|m| m.handle.is_some()) {
+            let handle_variant_name = method.handle_variant_name();
+            add_arm(q! {#enum_name::#handle_variant_name { .. }}, format!("{}_handle", method.name), false);
+        }
+        if self.params.control_variants {
+            add_arm(q! {#enum_name::Shutdown}, "shutdown".to_owned(), false);
+            add_arm(q! {#enum_name::Ping { .. }}, "ping".to_owned(), true);
+            add_arm(q! {#enum_name::Barrier { .. }}, "barrier".to_owned(), true);
+        }
+        out.extend(q! {
+            impl #enum_name {
+                /// Static information about the method this value stands for
+                #pub_or_priv fn method_info(&self) -> &'static #runtime::MethodInfo {
+                    match self {
+                        #arms
+                    }
+                }
+            }
+        });
+    }
+
+    pub(crate) fn generate_sent_at_fn(&self, out: &mut TokenStream) {
+        let runtime = runtime_crate();
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let mut arms = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            arms.extend(q! {
+                #enum_name::#variant_name { sent_at, .. } => ::core::option::Option::Some(sent_at),
+            });
+        }
+        for method in self.methods.iter().filter(|m| m.handle.is_some()) {
+            let handle_variant_name = method.handle_variant_name();
+            arms.extend(q! {
+                #enum_name::#handle_variant_name { .. } => ::core::option::Option::None,
+            });
+        }
+        if self.params.control_variants {
+            arms.extend(q! {
+                #enum_name::Shutdown | #enum_name::Ping { .. } | #enum_name::Barrier { .. } => ::core::option::Option::None,
+            });
+        }
+        out.extend(q! {
+            impl #enum_name {
+                /// When the proxy created this value. `None` for control variants and calls to handles.
+                #pub_or_priv fn sent_at(&self) -> ::core::option::Option<&#runtime::SentAt> {
+                    match self {
+                        #arms
+                    }
+                }
+            }
+        });
+    }
+
+    /// Serializable `call_name` enum with arguments of each method, and `Journaled` and `Replay` impls for it
+    pub(crate) fn generate_journal(&self, out: &mut TokenStream, call_name: &syn::Ident) {
+        let runtime = runtime_crate();
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let mut variants = TokenStream::new();
+        let mut to_call_arms = TokenStream::new();
+        let mut replay_arms = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            let method_name = &method.name;
+            let mut fields = TokenStream::new();
+            let mut names = TokenStream::new();
+            let mut cloned = TokenStream::new();
+            for arg in &method.args {
+                let argname = &arg.name;
+                let argtype = variant_field_type(arg);
+                fields.extend(q! {#argname: #argtype,});
+                names.extend(q! {#argname,});
+                cloned.extend(q! {#argname: ::core::clone::Clone::clone(#argname),});
+            }
+            let call_args = call_args(method);
+            variants.extend(q! {
+                #variant_name { #fields },
+            });
+            to_call_arms.extend(q! {
+                #enum_name::#variant_name { #names .. } => ::core::option::Option::Some(#call_name::#variant_name { #cloned }),
+            });
+            // Methods that cannot be replayed are rejected in `enumizer`
+            let call = q! {o.#method_name(#call_args)};
+            let action = if method.ret.is_none() {
+                q! {{ #call; ::core::result::Result::Ok(#runtime::journal::serde_json::Value::Null) }}
+            } else if let Some((StreamKind::Iterator, item_type)) = &method.stream {
+                q! {#runtime::journal::serde_json::to_value(::core::iter::Iterator::collect::<::std::vec::Vec<#item_type>>(#call))}
+            } else {
+                q! {#runtime::journal::serde_json::to_value(#call)}
+            };
+            replay_arms.extend(q! {
+                #call_name::#variant_name { #names } => #action,
+            });
+        }
+        let maybe_control_arm = if self.params.control_variants {
+            q! {#enum_name::Shutdown | #enum_name::Ping { .. } | #enum_name::Barrier { .. } => ::core::option::Option::None,}
+        } else {
+            q! {}
+        };
+        let input_trait_or_type_name = &self.name;
+        let (impl_generics, target) = if self.params.inherent_impl_mode {
+            (q! {}, q! {#input_trait_or_type_name})
+        } else {
+            (q! {<I: #input_trait_or_type_name>}, q! {I})
+        };
+        out.extend(q! {
+            /// Arguments of a method call, as stored in a `trait_enumizer::journal::Journal`
+            #[derive(::core::clone::Clone, #runtime::journal::serde::Serialize, #runtime::journal::serde::Deserialize)]
+            #[serde(crate = "::trait_enumizer::journal::serde")]
+            #pub_or_priv enum #call_name {
+                #variants
+            }
+
+            impl #runtime::journal::Journaled for #enum_name {
+                type Call = #call_name;
+                fn to_call(&self) -> ::core::option::Option<#call_name> {
+                    match self {
+                        #to_call_arms
+                        #maybe_control_arm
+                    }
+                }
+            }
+
+            impl #impl_generics #runtime::journal::Replay<#target> for #call_name {
+                #[allow(unused_variables)]
+                fn replay(self, o: &mut #target) -> ::core::result::Result<#runtime::journal::serde_json::Value, #runtime::journal::serde_json::Error> {
+                    match self {
+                        #replay_arms
+                    }
+                }
+            }
+        });
+    }
+
+    /// `recorder_name` struct implementing the trait (and resultified traits of proxies) by collecting enum values
+    pub(crate) fn generate_recorder(&self, out: &mut TokenStream, recorder_name: &syn::Ident) {
+        let runtime = runtime_crate();
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let trait_name = &self.name;
+        let mut canned_fields = TokenStream::new();
+        let mut canned_inits = TokenStream::new();
+        let mut canned_accessors = TokenStream::new();
+        let mut trait_methods = TokenStream::new();
+        let mut resultified_methods = vec![TokenStream::new(); self.params.proxies.len()];
+        let mut replay_arms = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            let method_name = &method.name;
+            let literal = proc_macro2::Literal::string(&method_name.to_string());
+            let mut args_with_types = TokenStream::new();
+            let mut names = TokenStream::new();
+            for arg in &method.args {
+                let argname = &arg.name;
+                let argtype = &arg.ty;
+                args_with_types.extend(q! {#argname: #argtype,});
+                names.extend(q! {#argname,});
+            }
+            let is_unit = |t: &syn::Type| matches!(t, syn::Type::Tuple(t) if t.elems.is_empty());
+            let (msg, value) = match (&method.ret, self.params.returnval.as_ref()) {
+                (Some(rt), Some(_)) => {
+                    let msg = self.detached_msg(method);
+                    if is_unit(rt) {
+                        (msg, q! {()})
+                    } else {
+                        canned_fields.extend(q! {
+                            #method_name: ::core::cell::RefCell<#runtime::Canned<#enum_name, #rt>>,
+                        });
+                        canned_inits.extend(q! {
+                            #method_name: ::core::default::Default::default(),
+                        });
+                        let accessor = quote::format_ident!("canned_{}", method_name);
+                        let doc = format!("Return values for `{}` calls", method_name);
+                        canned_accessors.extend(q! {
+                            #[doc = #doc]
+                            #pub_or_priv fn #accessor(&self) -> ::core::cell::RefMut<'_, #runtime::Canned<#enum_name, #rt>> {
+                                self.#method_name.borrow_mut()
+                            }
+                        });
+                        (msg, q! {self.#method_name.borrow_mut().next(&msg, #literal)})
+                    }
+                }
+                _ => (self.detached_msg(method), q! {()}),
+            };
+            let record = q! {
+                {
+                    let msg = #msg;
+                    let value = #value;
+                    self.calls.borrow_mut().push(msg);
+                    value
+                }
+            };
+
+            let slf = method.receiver_style.ts();
+            let maybe_async = if method.r#async { q! {async} } else { q! {} };
+            let returntype = match &method.ret {
+                Some(rt) => q! {-> #rt},
+                None => q! {},
+            };
+            trait_methods.extend(q! {
+                #maybe_async fn #method_name(#slf, #args_with_types) #returntype {
+                    #record
+                }
+            });
+
+            for (gpparams, methods) in self.params.proxies.iter().zip(&mut resultified_methods) {
+                if gpparams.traitname.is_none() {
+                    continue;
+                }
+                let rt_method_name = quote::format_ident!("try_{}", method.name);
+                let slf = gpparams.receiver_level().ts();
+                let maybe_async = if gpparams.r#async { q! {async} } else { q! {} };
+                let (ret, reply) = match (&method.ret, self.params.returnval.as_ref()) {
+                    (Some(rt), Some(returnval_handler_macro)) => {
+                        let wire_type = self.wire_return_type(rt);
+                        let reply = if self.wraps_return_values() {
+                            q! {::core::result::Result::Ok(#record)}
+                        } else {
+                            record.clone()
+                        };
+                        if gpparams.callbacks {
+                            let then_method_name = quote::format_ident!("try_{}_then", method.name);
+                            methods.extend(q! {
+                                #maybe_async fn #then_method_name<C: ::core::ops::FnOnce(#wire_type) + ::core::marker::Send + 'static>(#slf, #args_with_types ret: C) -> ::core::result::Result<(), E> {
+                                    ret(#reply);
+                                    ::core::result::Result::Ok(())
+                                }
+                            });
+                        }
+                        (
+                            q! {::core::result::Result<#wire_type, #returnval_handler_macro ! ( RecvError )>},
+                            q! {::core::result::Result::Ok(#reply)},
+                        )
+                    }
+                    _ if gpparams.interceptor.is_some() => (self.proxy_unit_type(gpparams), q! {::core::result::Result::Ok(#record)}),
+                    _ => (q! {()}, record.clone()),
+                };
+                methods.extend(q! {
+                    #maybe_async fn #rt_method_name(#slf, #args_with_types) -> ::core::result::Result<#ret, E> {
+                        ::core::result::Result::Ok(#reply)
+                    }
+                });
+            }
+
+            let call_args = call_args(method);
+            replay_arms.extend(q! {
+                #enum_name::#variant_name { #names .. } => { let _ = o.#method_name(#call_args); }
+            });
+        }
+        if self.params.control_variants {
+            replay_arms.extend(q! {
+                #enum_name::Shutdown | #enum_name::Ping { .. } | #enum_name::Barrier { .. } => (),
+            });
+        }
+        // Async methods and smart pointer receivers cannot be called on `&mut I`, so no `replay_into` for such traits
+        let replayable = self.methods.iter().all(|m| !m.needs_async() && ReceiverStyle::Mut.can_call(m.receiver_style));
+        let replay_into = if replayable {
+            q! {
+                /// Call recorded methods on `o` in order, removing them. Return values are dropped.
+                #[allow(unused_variables)]
+                #pub_or_priv fn replay_into<I: #trait_name>(&mut self, o: &mut I) {
+                    for msg in self.calls.get_mut().drain(..) {
+                        match msg {
+                            #replay_arms
+                        }
+                    }
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+        let doc = format!("Implements `{}` by collecting `{}` values, replying with canned return values", trait_name, enum_name);
+        out.extend(q! {
+            #[doc = #doc]
+            #pub_or_priv struct #recorder_name {
+                calls: ::core::cell::RefCell<::std::vec::Vec<#enum_name>>,
+                #canned_fields
+            }
+
+            impl ::core::default::Default for #recorder_name {
+                fn default() -> Self {
+                    #recorder_name {
+                        calls: ::core::default::Default::default(),
+                        #canned_inits
+                    }
+                }
+            }
+
+            impl #recorder_name {
+                /// Empty recorder without canned return values
+                #pub_or_priv fn new() -> Self {
+                    ::core::default::Default::default()
+                }
+
+                /// Take all recorded values, leaving the recorder empty
+                #pub_or_priv fn take(&mut self) -> ::std::vec::Vec<#enum_name> {
+                    ::core::mem::take(self.calls.get_mut())
+                }
+
+                /// Remove recorded values one by one
+                #pub_or_priv fn drain(&mut self) -> ::std::vec::Drain<'_, #enum_name> {
+                    self.calls.get_mut().drain(..)
+                }
+
+                /// Number of recorded values
+                #pub_or_priv fn len(&self) -> usize {
+                    self.calls.borrow().len()
+                }
+
+                /// Whether nothing is recorded
+                #pub_or_priv fn is_empty(&self) -> bool {
+                    self.calls.borrow().is_empty()
+                }
+
+                #replay_into
+
+                #canned_accessors
+            }
+
+            impl #trait_name for #recorder_name {
+                #trait_methods
+            }
+        });
+        for (gpparams, methods) in self.params.proxies.iter().zip(resultified_methods) {
+            if let Some(resultified_trait_name) = &gpparams.traitname {
+                out.extend(q! {
+                    impl<E> #resultified_trait_name<E> for #recorder_name {
+                        #methods
+                    }
+                });
+            }
+        }
+    }
+
+    /// `mock_name` struct implementing the trait by checking each call against a queue of expectations
+    pub(crate) fn generate_mock(&self, out: &mut TokenStream, mock_name: &syn::Ident) {
+        let runtime = runtime_crate();
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let trait_name = &self.name;
+        let mut expect_fns = TokenStream::new();
+        let mut trait_methods = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            let method_name = &method.name;
+            let mut args_with_types = TokenStream::new();
+            let mut expected_args = TokenStream::new();
+            let mut format_args = TokenStream::new();
+            let mut pattern = TokenStream::new();
+            let mut comparisons = vec![];
+            let mut format_string = format!("{}(", method_name);
+            for (i, arg) in method.args.iter().enumerate() {
+                let argname = &arg.name;
+                let argtype = &arg.ty;
+                let fieldtype = variant_field_type(arg);
+                let actual = quote::format_ident!("actual_{}", argname);
+                args_with_types.extend(q! {#argname: #argtype,});
+                expected_args.extend(q! {#argname: #fieldtype,});
+                pattern.extend(q! {#argname: #actual,});
+                comparisons.push(q! {*#actual == #argname});
+                if i > 0 {
+                    format_string.push_str(", ");
+                }
+                // Without `enumizer_mock_eq` arguments may lack `Debug`, so only their types are shown
+                if method.mock_eq {
+                    format_args.extend(q! {#argname,});
+                    format_string.push_str(&format!("{}: {{:?}}", argname));
+                } else {
+                    format_args.extend(q! {::core::any::type_name::<#fieldtype>(),});
+                    format_string.push_str(&format!("{}: <{{}}>", argname));
+                }
+            }
+            format_string.push(')');
+            let comparison = if comparisons.is_empty() { q! {true} } else { q! {#(#comparisons)&&*} };
+            let predicate_description = format!("{}(..) satisfying a predicate", method_name);
+            let reply_type = match (&method.ret, self.params.returnval.as_ref()) {
+                (Some(syn::Type::Tuple(t)), _) if t.elems.is_empty() => None,
+                (Some(rt), Some(_)) => Some(rt),
+                _ => None,
+            };
+            let (ret_param, reply, check) = match reply_type {
+                Some(rt) => (
+                    q! {ret: #rt},
+                    q! {::core::option::Option::Some(::std::boxed::Box::new(ret))},
+                    q! {self.expectations.call_needing_reply::<#rt>(&msg, description)},
+                ),
+                None => (
+                    q! {},
+                    q! {::core::option::Option::None},
+                    q! {{ let _ = self.expectations.call(&msg, description); }},
+                ),
+            };
+
+            let expect_with_fn = quote::format_ident!("expect_{}_with", method_name);
+            let doc_with = format!("Expect a call to `{}` with arguments satisfying `predicate`", method_name);
+            // Methods without arguments need neither `PartialEq` nor `Debug`
+            if method.mock_eq || method.args.is_empty() {
+                let expect_fn = quote::format_ident!("expect_{}", method_name);
+                let doc = format!("Expect a call to `{}` with arguments equal to the given ones", method_name);
+                expect_fns.extend(q! {
+                    #[doc = #doc]
+                    #pub_or_priv fn #expect_fn(&self, #expected_args #ret_param) -> &Self {
+                        let description = ::std::format!(#format_string, #format_args);
+                        self.expectations.push(
+                            description,
+                            move |msg| match msg {
+                                #enum_name::#variant_name { #pattern .. } => #comparison,
+                                #[allow(unreachable_patterns)]
+                                _ => false,
+                            },
+                            #reply,
+                        );
+                        self
+                    }
+                });
+            }
+            expect_fns.extend(q! {
+                #[doc = #doc_with]
+                #pub_or_priv fn #expect_with_fn(
+                    &self,
+                    predicate: impl ::core::ops::Fn(&#enum_name) -> bool + ::core::marker::Send + 'static,
+                    #ret_param
+                ) -> &Self {
+                    self.expectations.push(
+                        ::std::string::String::from(#predicate_description),
+                        move |msg| ::core::matches!(msg, #enum_name::#variant_name { .. }) && predicate(msg),
+                        #reply,
+                    );
+                    self
+                }
+            });
+
+            let msg = self.detached_msg(method);
+            let slf = method.receiver_style.ts();
+            let maybe_async = if method.r#async { q! {async} } else { q! {} };
+            let returntype = match &method.ret {
+                Some(rt) => q! {-> #rt},
+                None => q! {},
+            };
+            trait_methods.extend(q! {
+                #maybe_async fn #method_name(#slf, #args_with_types) #returntype {
+                    let description = ::std::format!(#format_string, #format_args);
+                    let msg = #msg;
+                    #check
+                }
+            });
+        }
+        let literal = proc_macro2::Literal::string(&mock_name.to_string());
+        let doc = format!("Implements `{}` by checking each call against a queue of expectations", trait_name);
+        out.extend(q! {
+            #[doc = #doc]
+            #pub_or_priv struct #mock_name {
+                expectations: #runtime::Expectations<#enum_name>,
+            }
+
+            impl ::core::default::Default for #mock_name {
+                fn default() -> Self {
+                    #mock_name {
+                        expectations: #runtime::Expectations::new(#literal),
+                    }
+                }
+            }
+
+            impl #mock_name {
+                /// Mock without expectations
+                #pub_or_priv fn new() -> Self {
+                    ::core::default::Default::default()
+                }
+
+                /// Panic with a report if there were unexpected calls or some expectations are not met yet.
+                /// Also done when the mock is dropped.
+                #pub_or_priv fn verify(&self) {
+                    self.expectations.verify()
+                }
+
+                #expect_fns
+            }
+
+            impl #trait_name for #mock_name {
+                #trait_methods
+            }
+        });
+    }
+
+    pub(crate) fn generate_priority_fn(&self, out: &mut TokenStream) {
+        let runtime = runtime_crate();
+        let pub_or_priv = self.params.access_mode.code();
+        let enum_name = &self.params.enum_name;
+        let mut arms = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            let priority = method.priority.clone().unwrap_or_else(|| quote::format_ident!("Normal"));
+            arms.extend(q! {
+                #enum_name::#variant_name { .. } => #runtime::Priority::#priority,
+            });
+        }
+        for method in self.methods.iter().filter(|m| m.handle.is_some()) {
+            let handle_variant_name = method.handle_variant_name();
+            arms.extend(q! {
+                #enum_name::#handle_variant_name { .. } => #runtime::Priority::Normal,
+            });
+        }
+        if self.params.control_variants {
+            arms.extend(q! {
+                #enum_name::Ping { .. } => #runtime::Priority::High,
+                #enum_name::Shutdown | #enum_name::Barrier { .. } => #runtime::Priority::Normal,
+            });
+        }
+        out.extend(q! {
+            impl #enum_name {
+                /// Priority of the method, as specified by `#[enumizer_priority(...)]`
+                #pub_or_priv fn priority(&self) -> #runtime::Priority {
+                    match self {
+                        #arms
+                    }
+                }
+            }
+        });
+    }
+
+    /// Needed by actor's mailbox, for any of `dispatch`-like traits
+    pub(crate) fn generate_message_impl(&self, out: &mut TokenStream) {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let maybe_priority = if self.has_priorities() {
+            q! {
+                fn priority(&self) -> #runtime::Priority {
+                    #enum_name::priority(self)
+                }
+            }
+        } else {
+            q! {}
+        };
+        let mut coalesce_arms = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            match &method.coalesce {
+                None => (),
+                Some(None) => coalesce_arms.extend(q! {
+                    (#enum_name::#variant_name { .. }, #enum_name::#variant_name { .. }) => true,
+                }),
+                Some(Some(key)) => coalesce_arms.extend(q! {
+                    (#enum_name::#variant_name { #key: a, .. }, #enum_name::#variant_name { #key: b, .. }) => a == b,
+                }),
+            }
+        }
+        let maybe_coalesce = if coalesce_arms.is_empty() {
+            q! {}
+        } else {
+            q! {
+                const COALESCING: bool = true;
+                fn coalesces_with(&self, queued: &Self) -> bool {
+                    #[allow(unreachable_patterns)]
+                    match (self, queued) {
+                        #coalesce_arms
+                        _ => false,
+                    }
+                }
+            }
+        };
+        out.extend(q! {
+            impl #runtime::actor::Message for #enum_name {
+                #maybe_priority
+                #maybe_coalesce
+            }
+        });
+    }
+
+    pub(crate) fn generate_call_fn(&self, out: &mut TokenStream, cfparams: &CallFnParams) {
+        if cfparams.fan_out {
+            self.generate_fan_out_fn(out, cfparams);
+            return;
+        }
+        let pub_or_priv = self.params.access_mode.code();
+        let returnval_handler = self.params.returnval.as_ref();
+        let extra_arg = cfparams.extra_arg.as_ref();
+        let level = cfparams.level;
+        let enum_name = &self.params.enum_name;
+        let fn_name = &cfparams.name;
+        let variants = self.call_fn_match_arms(cfparams, level);
+
+        let input_trait_or_type_name = &self.name;
+        let target = if self.params.inherent_impl_mode {
+            q! {#input_trait_or_type_name}
+        } else {
+            q! {I}
+        };
+        let target = if cfparams.poison_on_panic {
+            let runtime = runtime_crate();
+            q! {#runtime::Poisonable<#target>}
+        } else {
+            target
+        };
+        let arg_o_with_type = match level {
+            ReceiverStyle::Move => q! {mut o: #target},
+            ReceiverStyle::Mut => q! {o: &mut #target},
+            ReceiverStyle::Ref => q! {o: &#target},
+            ReceiverStyle::Box => q! {mut o: ::std::boxed::Box<#target>},
+            ReceiverStyle::Arc => q! {o: &::std::sync::Arc<#target>},
+            ReceiverStyle::Rc => q! {o: &::std::rc::Rc<#target>},
+            ReceiverStyle::PinMut => q! {mut o: ::core::pin::Pin<&mut #target>},
+        };
+        let maybe_requirement = if !self.params.inherent_impl_mode {
+            // Objects behind pointers may be unsized, e.g. `dyn Trait` objects kept in `Handles`.
+            // `Box`ed ones may be moved out of the box for `self` methods.
+            let maybe_unsized = if matches!(level, ReceiverStyle::Move | ReceiverStyle::Box) {
+                q! {}
+            } else {
+                q! {+ ?Sized}
+            };
+            let handle_bounds = self.handle_bounds();
+            q! {<I: #input_trait_or_type_name #maybe_unsized #handle_bounds>}
+        } else {
+            q! {}
+        };
+
+        let maybe_returntype = match (returnval_handler, self.params.control_variants) {
+            (Some(returnval_handler_macro), true) => {
+                let runtime = runtime_crate();
+                q! { -> ::core::result::Result<#runtime::Control, #returnval_handler_macro ! (SendError)>}
+            }
+            (Some(returnval_handler_macro), false) => q! { -> ::core::result::Result<(), #returnval_handler_macro ! (SendError)>},
+            (None, _) => q! {},
+        };
+        let body = if self.params.control_variants {
+            let control = self.control_of_self();
+            let runtime = runtime_crate();
+            let handled = self.intercepted(cfparams, q! {
+                {
+                    match self {
+                        #variants
+                    }?;
+                    Ok(control)
+                }
+            }, q! {Ok(#runtime::Control::Continue)});
+            q! {
+                #control
+                #handled
+            }
+        } else {
+            self.intercepted(cfparams, q! {
+                match self {
+                    #variants
+                }
+            }, q! {Ok(())})
+        };
+        let maybe_extraarg = if let Some(extr) = extra_arg {
+            q! {, extra_arg : #extr}
+        } else {
+            q! {}
+        };
+        let maybe_async = if cfparams.r#async {
+            q! {async}
+        } else {
+            q! {}
+        };
+        let body = if cfparams.poison_on_panic {
+            let runtime = runtime_crate();
+            let split = if level == ReceiverStyle::Mut { q! {split_mut} } else { q! {split} };
+            q! {
+                let (o, poisoned) = #runtime::Poisonable::#split(o);
+                #body
+            }
+        } else {
+            body
+        };
+        out.extend(q! {
+            impl #enum_name {
+                #pub_or_priv #maybe_async fn #fn_name #maybe_requirement(self, #arg_o_with_type #maybe_extraarg) #maybe_returntype {
+                    #body
+                }
+            }
+        });
+        if cfparams.dispatch {
+            self.generate_dispatch_impl(out, cfparams);
+        }
+        if cfparams.rw_dispatch {
+            self.generate_rw_dispatch_impl(out, cfparams);
+        }
+    }
 
-use super::{InputData, ReceiverStyle};
-impl InputData {
-    pub(crate) fn generate_enum(&self, out: &mut TokenStream) {
-        let returnval_handler = self.params.returnval.as_ref();
-        let custom_attrs = &self.params.enum_attr[..];
+    /// Call function applying the value to each of `targets`, combining return values with `aggregate`
+    fn generate_fan_out_fn(&self, out: &mut TokenStream, cfparams: &CallFnParams) {
+        let runtime = runtime_crate();
         let pub_or_priv = self.params.access_mode.code();
+        let returnval_handler = self.params.returnval.as_ref();
+        let level = cfparams.level;
         let enum_name = &self.params.enum_name;
-        let mut variants = TokenStream::new();
+        let fn_name = &cfparams.name;
+        let input_trait_or_type_name = &self.name;
+        let maybe_extraarg = if cfparams.extra_arg.is_some() {
+            q! {, extra_arg}
+        } else {
+            q! {}
+        };
+        let maybe_mut = if level == ReceiverStyle::Move { q! {mut} } else { q! {} };
+        let mut arms = TokenStream::new();
+        let mut aggregate_bounds = TokenStream::new();
         for method in &self.methods {
             let variant_name = method.variant_name();
-            let mut variant_params = TokenStream::new();
+            let method_name = &method.name;
+            if method.stream.is_some() {
+                panic!("`fan_out` does not support streamed return value of method `{}`", method_name);
+            }
+            let mut pattern = TokenStream::new();
+            let mut call_args = TokenStream::new();
             for arg in &method.args {
-                let argument_name = &arg.name;
-                let argument_type = if !arg.to_owned {
-                    let ty = &arg.ty;
-                    q! {#ty}
+                let argname = &arg.name;
+                pattern.extend(q! {#argname,});
+                if arg.to_owned {
+                    call_args.extend(q! {::core::borrow::Borrow::borrow(&#argname),});
                 } else {
-                    match &arg.ty {
-                        syn::Type::Reference(r) => {
-                            let ty = &*r.elem;
-                            let toowned = borrow_toowned();
-                            q! {<#ty as #toowned>::Owned}
-                        }
-                        _ => panic!(
-                            "Argument marked with `#[enumizer_to_owned]` must be a &reference"
-                        ),
-                    }
-                };
-                let mut custom_attributes = TokenStream::new();
-                for aa in &arg.enum_attr {
-                    custom_attributes.extend(q! {# #aa});
+                    call_args.extend(q! {::core::clone::Clone::clone(&#argname),});
                 }
-                variant_params.extend(q! {
-                    #custom_attributes #argument_name : #argument_type,
-                });
             }
-            if let Some(return_type) = &method.ret {
-                let mut custom_attributes = TokenStream::new();
-                for aa in &method.return_attr {
-                    custom_attributes.extend(q! {# #aa});
-                }
-                let returnval_macro = returnval_handler.unwrap();
-                variant_params.extend(q! {
-                    #custom_attributes ret : #returnval_macro ! (Sender<#return_type>),
-                });
-            } else {
-                if !method.return_attr.is_empty() {
-                    panic!("`enumizer_return_attr[]` used in method without a return type. Add `-> ()` to force using the return channel.");
-                }
+            let can_do_it = level.can_call(method.receiver_style) && !method.needs_async();
+            if self.params.trace {
+                pattern.extend(if can_do_it { q! {trace,} } else { q! {trace: _,} });
             }
-            let mut custom_attributes = TokenStream::new();
-            for aa in &method.enum_attr {
-                custom_attributes.extend(q! {# #aa});
+            if self.params.sent_at {
+                pattern.extend(q! {sent_at: _,});
             }
-
-            variants.extend(q! {
-                #custom_attributes #variant_name { #variant_params },
+            let call = self.traced_call(cfparams, method, self.method_call(level, method, call_args));
+            let reply = match (&method.ret, returnval_handler) {
+                (Some(rt), Some(returnval_handler_macro)) => {
+                    pattern.extend(q! {ret,});
+                    let wire_type = self.wire_return_type(rt);
+                    let wrap = |value: TokenStream| if self.wraps_return_values() { q! {Ok(#value)} } else { value };
+                    // Without targets there is no value to reply with: report it with `call_errors`, otherwise drop the return channel
+                    let no_targets = if self.wraps_return_values() {
+                        q! {Ok(#returnval_handler_macro ! (send::<#wire_type>(ret, Err(#runtime::CallError::NoTargets) #maybe_extraarg))?)}
+                    } else {
+                        q! {Ok(())}
+                    };
+                    if matches!(rt, syn::Type::Tuple(t) if t.elems.is_empty()) {
+                        let value = wrap(q! {()});
+                        Some((
+                            q! {let mut any = false;},
+                            q! { #call; any = true; },
+                            q! {
+                                if !any {
+                                    return #no_targets;
+                                }
+                                Ok(#returnval_handler_macro ! (send::<#wire_type>(ret, #value #maybe_extraarg))?)
+                            },
+                        ))
+                    } else {
+                        aggregate_bounds.extend(q! {A: #runtime::Aggregate<#rt>,});
+                        let value = wrap(q! {acc});
+                        Some((
+                            q! {let mut acc = ::core::option::Option::None;},
+                            q! {
+                                let value = #call;
+                                acc = ::core::option::Option::Some(match acc {
+                                    ::core::option::Option::None => value,
+                                    ::core::option::Option::Some(acc) => #runtime::Aggregate::<#rt>::combine(&mut aggregate, acc, value),
+                                });
+                            },
+                            q! {
+                                match acc {
+                                    ::core::option::Option::Some(acc) => Ok(#returnval_handler_macro ! (send::<#wire_type>(ret, #value #maybe_extraarg))?),
+                                    ::core::option::Option::None => #no_targets,
+                                }
+                            },
+                        ))
+                    }
+                }
+                _ => None,
+            };
+            let (init, each, finish) = match reply {
+                Some(x) => x,
+                None => (q! {}, q! { #call; }, if returnval_handler.is_some() { q! {Ok(())} } else { q! {} }),
+            };
+            let action = if can_do_it {
+                q! {
+                    {
+                        #init
+                        for #maybe_mut o in targets {
+                            #each
+                        }
+                        #finish
+                    }
+                }
+            } else {
+                let literal1 = proc_macro2::Literal::string(&format!("{}::{}", enum_name, fn_name));
+                let literal2 = proc_macro2::Literal::string(&method_name.to_string());
+                q! {panic!("Cannot call `{}` from `{}` due to incompatible `self` access mode or it being async", #literal2, #literal1)}
+            };
+            arms.extend(q! {
+                #enum_name::#variant_name { #pattern .. } => #action,
             });
         }
-        let mut customattrs = TokenStream::new();
-        for ca in custom_attrs {
-            customattrs.extend(q! {# #ca});
-        }
+
+        let mut body = q! {
+            match self {
+                #arms
+            }
+        };
+        let maybe_returntype = match returnval_handler {
+            Some(returnval_handler_macro) if self.params.control_variants => {
+                let control = self.control_of_self();
+                body = q! {
+                    #control
+                    match self {
+                        #arms
+                        #enum_name::Shutdown => Ok(()),
+                        #enum_name::Ping { ret } | #enum_name::Barrier { ret } => Ok(#returnval_handler_macro ! (send::<()>(ret, () #maybe_extraarg))?),
+                    }?;
+                    Ok(control)
+                };
+                q! { -> ::core::result::Result<#runtime::Control, #returnval_handler_macro ! (SendError)>}
+            }
+            Some(returnval_handler_macro) => q! { -> ::core::result::Result<(), #returnval_handler_macro ! (SendError)>},
+            None => q! {},
+        };
+        let (generics, target) = match (self.params.inherent_impl_mode, level) {
+            (true, _) => (q! {}, q! {#input_trait_or_type_name}),
+            (false, ReceiverStyle::Move) => (q! {I: #input_trait_or_type_name,}, q! {I}),
+            (false, _) => (q! {I: #input_trait_or_type_name + ?::core::marker::Sized + 'a,}, q! {I}),
+        };
+        let item = match level {
+            ReceiverStyle::Move => q! {#target},
+            ReceiverStyle::Mut => q! {&'a mut #target},
+            ReceiverStyle::Ref => q! {&'a #target},
+            _ => unreachable!("parsing function should have already rejected this case"),
+        };
+        let maybe_lifetime = if level == ReceiverStyle::Move { q! {} } else { q! {'a,} };
+        let maybe_aggregate = if returnval_handler.is_some() {
+            q! {, mut aggregate: A}
+        } else {
+            q! {}
+        };
+        let maybe_aggregate_generic = if returnval_handler.is_some() { q! {A,} } else { q! {} };
+        let maybe_extraarg = if let Some(extr) = &cfparams.extra_arg {
+            q! {, extra_arg : #extr}
+        } else {
+            q! {}
+        };
         out.extend(q! {
-            #customattrs
-            #pub_or_priv enum #enum_name {
-                #variants
+            impl #enum_name {
+                #[allow(unused_mut, unused_variables)]
+                #pub_or_priv fn #fn_name<#maybe_lifetime #generics #maybe_aggregate_generic>(
+                    self,
+                    targets: impl ::core::iter::IntoIterator<Item = #item>
+                    #maybe_aggregate
+                    #maybe_extraarg
+                ) #maybe_returntype where #aggregate_bounds {
+                    #body
+                }
             }
         });
     }
 
-    pub(crate) fn generate_call_fn(&self, out: &mut TokenStream, cfparams: &CallFnParams) {
-        let pub_or_priv = self.params.access_mode.code();
+    /// `match` arms applying each variant to `o` accessed with the specified `level`
+    fn call_fn_match_arms(&self, cfparams: &CallFnParams, level: ReceiverStyle) -> TokenStream {
         let returnval_handler = self.params.returnval.as_ref();
         let extra_arg = cfparams.extra_arg.as_ref();
-        let level = cfparams.level;
         let enum_name = &self.params.enum_name;
-        let fn_name = &cfparams.name;
+        let runtime = runtime_crate();
         let mut variants = TokenStream::new();
         for method in &self.methods {
             let variant_name = quote::format_ident!(
@@ -106,6 +1173,10 @@ impl InputData {
                     .to_case(convert_case::Case::UpperCamel)
             );
             let method_name = &method.name;
+            if cfparams.restriction.as_ref().is_some_and(|r| !r.allows(method)) {
+                variants.extend(self.not_allowed_arms(cfparams, method));
+                continue;
+            }
             let mut variant_params = TokenStream::new();
             let mut variant_params_with_ret = TokenStream::new();
             for arg in &method.args {
@@ -129,22 +1200,42 @@ impl InputData {
                     ret,
                 });
             }
-            let can_do_it1 = match (level, method.receiver_style) {
-                (ReceiverStyle::Move, _) => true,
-                (ReceiverStyle::Mut, ReceiverStyle::Move) => false,
-                (ReceiverStyle::Mut, _) => true,
-                (ReceiverStyle::Ref, ReceiverStyle::Ref) => true,
-                (ReceiverStyle::Ref, _) => false,
-            };
-            let can_do_it2 = match (cfparams.r#async, method.r#async) {
-                (false, true) => false,
-                _ => true,
-            };
+            let can_do_it1 = level.can_call(method.receiver_style);
+            let can_do_it2 = !matches!((cfparams.r#async, method.needs_async()), (false, true));
             let maybe_await = if method.r#async {
                 q! {.await}
             } else {
                 q! {}
             };
+            let call = match &method.handle {
+                Some(handle) => {
+                    let rt = &handle.ret;
+                    let o_mut = self.o_mut(level);
+                    let call = self.method_call(level, method, variant_params);
+                    q! {
+                        {
+                            let object = #call;
+                            #runtime::registry::HoldsHandles::<#rt>::handles(#o_mut).register(object)
+                        }
+                    }
+                }
+                None => {
+                    let call = self.method_call(level, method, variant_params);
+                    q! {#call #maybe_await}
+                }
+            };
+            let call = self.traced_call(cfparams, method, call);
+            let failed = self.metrics_failed(cfparams);
+            if self.params.trace {
+                if can_do_it1 && can_do_it2 {
+                    variant_params_with_ret.extend(q! {trace,});
+                } else {
+                    variant_params_with_ret.extend(q! {trace: _,});
+                }
+            }
+            if self.params.sent_at {
+                variant_params_with_ret.extend(q! {sent_at: _,});
+            }
             let action = if can_do_it1 && can_do_it2 {
                 if let Some(return_type) = &method.ret {
                     if let Some(returnval_handler_macro) = returnval_handler {
@@ -158,15 +1249,139 @@ impl InputData {
                         } else {
                             q! {}
                         };
-                        q! { Ok(#returnval_handler_macro ! (#send_pseudomethod_name::<#return_type>(ret, o.#method_name(#variant_params) #maybe_await #maybe_extraarg))?)  }
+                        if let Some((stream_kind, item_type)) = &method.stream {
+                            let (send_item, send_end) = if cfparams.r#async {
+                                (q! {send_item_async}, q! {send_end_async})
+                            } else {
+                                (q! {send_item}, q! {send_end})
+                            };
+                            let wire_item_type = self.wire_return_type(item_type);
+                            let item = if self.wraps_return_values() { q! {Ok(item)} } else { q! {item} };
+                            let (items, next) = if *stream_kind == StreamKind::Stream {
+                                (q! {let mut items = ::core::pin::pin!(#call);}, q! {#runtime::next_item(items.as_mut()).await})
+                            } else {
+                                (q! {let mut items = ::core::iter::IntoIterator::into_iter(#call);}, q! {::core::iter::Iterator::next(&mut items)})
+                            };
+                            if cfparams.catch_unwind {
+                                // Items are sent from inside `catch_unwind`, as the iterator (or stream) may panic as well
+                                let send_items = q! {
+                                    {
+                                        #items
+                                        let mut sent = Ok(());
+                                        while let Some(item) = #next {
+                                            sent = #returnval_handler_macro ! (#send_item::<#wire_item_type>(&ret, #item #maybe_extraarg));
+                                            if sent.is_err() {
+                                                break;
+                                            }
+                                        }
+                                        sent
+                                    }
+                                };
+                                let caught = if cfparams.r#async {
+                                    q! {#runtime::catch_unwind_async(async #send_items).await}
+                                } else {
+                                    q! {#runtime::catch_unwind(|| #send_items)}
+                                };
+                                let call_hook = self.panic_hook_call(cfparams, method_name);
+                                let poison = if cfparams.poison_on_panic {
+                                    q! {poisoned.store(true, ::core::sync::atomic::Ordering::Relaxed);}
+                                } else {
+                                    q! {}
+                                };
+                                let stream_error = self.stream_error(cfparams, item_type, q! {#runtime::CallError::Panicked(#runtime::Panicked::new(&*payload))});
+                                q! {
+                                    match #caught {
+                                        Ok(sent) => {
+                                            sent?;
+                                            Ok(#returnval_handler_macro ! (#send_end::<#wire_item_type>(ret #maybe_extraarg))?)
+                                        }
+                                        Err(payload) => {
+                                            #failed
+                                            #call_hook
+                                            #stream_error
+                                            #poison
+                                            Ok(())
+                                        }
+                                    }
+                                }
+                            } else {
+                                q! { {
+                                    #items
+                                    while let Some(item) = #next {
+                                        #returnval_handler_macro ! (#send_item::<#wire_item_type>(&ret, #item #maybe_extraarg))?;
+                                    }
+                                    Ok(#returnval_handler_macro ! (#send_end::<#wire_item_type>(ret #maybe_extraarg))?)
+                                } }
+                            }
+                        } else if cfparams.catch_unwind {
+                            let wire_type = self.wire_return_type(return_type);
+                            let caught = self.catch_unwind_expr(cfparams, call.clone());
+                            let after_panic = if cfparams.poison_on_panic {
+                                q! {
+                                    poisoned.store(true, ::core::sync::atomic::Ordering::Relaxed);
+                                    Ok(())
+                                }
+                            } else {
+                                q! {Ok(())}
+                            };
+                            q! {
+                                match #caught {
+                                    Ok(x) => Ok(#returnval_handler_macro ! (#send_pseudomethod_name::<#wire_type>(ret, Ok(x) #maybe_extraarg))?),
+                                    Err(payload) => {
+                                        #failed
+                                        let _ = #returnval_handler_macro ! (#send_pseudomethod_name::<#wire_type>(ret, Err(#runtime::CallError::Panicked(#runtime::Panicked::new(&*payload))) #maybe_extraarg));
+                                        #after_panic
+                                    }
+                                }
+                            }
+                        } else {
+                            let wire_type = self.wire_return_type(return_type);
+                            let value = if self.wraps_return_values() {
+                                q! {Ok(#call)}
+                            } else {
+                                q! {#call}
+                            };
+                            q! { Ok(#returnval_handler_macro ! (#send_pseudomethod_name::<#wire_type>(ret, #value #maybe_extraarg))?)  }
+                        }
                     } else {
                         unreachable!("parsing function should have already rejected this case");
                     }
+                } else if cfparams.catch_unwind {
+                    let caught = self.catch_unwind_expr(cfparams, call.clone());
+                    let ok = if returnval_handler.is_some() {
+                        q! {Ok(x)}
+                    } else {
+                        q! {x}
+                    };
+                    let done = if returnval_handler.is_some() {
+                        q! {Ok(())}
+                    } else {
+                        q! {}
+                    };
+                    let after_panic = if cfparams.poison_on_panic {
+                        q! {
+                            poisoned.store(true, ::core::sync::atomic::Ordering::Relaxed);
+                            #done
+                        }
+                    } else {
+                        done
+                    };
+                    let call_hook = self.panic_hook_call(cfparams, method_name);
+                    q! {
+                        match #caught {
+                            Ok(x) => #ok,
+                            Err(payload) => {
+                                #failed
+                                #call_hook
+                                #after_panic
+                            }
+                        }
+                    }
                 } else {
                     if returnval_handler.is_none() {
-                        q! {o.#method_name(#variant_params) #maybe_await}
+                        q! {#call}
                     } else {
-                        q! {Ok(o.#method_name(#variant_params) #maybe_await)}
+                        q! {Ok(#call)}
                     }
                 }
             } else {
@@ -188,103 +1403,761 @@ impl InputData {
                     unreachable!()
                 }
             };
-            variants.extend(q! {
-                #enum_name::#variant_name { #variant_params_with_ret } => #action,
-            });
+            let action = if cfparams.poison_on_panic && can_do_it1 && can_do_it2 {
+                let refusal = self.poisoned_refusal(cfparams, method);
+                q! {
+                    if poisoned.load(::core::sync::atomic::Ordering::Relaxed) {
+                        #refusal
+                    } else {
+                        #action
+                    }
+                }
+            } else {
+                action
+            };
+            variants.extend(q! {
+                #enum_name::#variant_name { #variant_params_with_ret } => #action,
+            });
+        }
+        for method in self.methods.iter().filter(|m| m.handle.is_some()) {
+            if cfparams.restriction.as_ref().is_some_and(|r| !r.allows(method)) {
+                continue;
+            }
+            let handle_variant_name = method.handle_variant_name();
+            let rt = &method.handle.as_ref().unwrap().ret;
+            let o_mut = self.o_mut(level);
+            // Failures to deliver return values of handle's methods are ignored, like in `Dispatch`
+            variants.extend(q! {
+                #enum_name::#handle_variant_name { id, msg } => {
+                    let handles = #runtime::registry::HoldsHandles::<#rt>::handles(#o_mut);
+                    match msg {
+                        #runtime::registry::HandleMsg::Call(msg) => {
+                            let _ = handles.dispatch(id, msg);
+                        }
+                        #runtime::registry::HandleMsg::Release => {
+                            handles.release(id);
+                        }
+                    }
+                    Ok(())
+                }
+            });
+        }
+        if self.params.control_variants {
+            let returnval_handler_macro = returnval_handler.unwrap();
+            let send_pseudomethod_name = if cfparams.r#async {
+                q! {send_async}
+            } else {
+                q! {send}
+            };
+            let maybe_extraarg = if extra_arg.is_some() {
+                q! {, extra_arg}
+            } else {
+                q! {}
+            };
+            // Messages are handled in order, so everything before `Barrier` is already handled by now
+            variants.extend(q! {
+                #enum_name::Shutdown => Ok(()),
+                #enum_name::Ping { ret } | #enum_name::Barrier { ret } => Ok(#returnval_handler_macro ! (#send_pseudomethod_name::<()>(ret, () #maybe_extraarg))?),
+            });
+        }
+        variants
+    }
+
+    /// `match` arms replying `CallError::NotAllowed` to `method`'s variant (and its handle variant), which is outside of call function's `restriction`.
+    /// Values without return channels are just dropped.
+    fn not_allowed_arms(&self, cfparams: &CallFnParams, method: &crate::Method) -> TokenStream {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let variant_name = method.variant_name();
+        let literal = proc_macro2::Literal::string(&method.name.to_string());
+        let error = q! {#runtime::CallError::NotAllowed(#runtime::NotAllowed::new(#literal))};
+        let failed = self.metrics_failed(cfparams);
+        let Some(returnval_handler_macro) = &self.params.returnval else {
+            return q! {
+                #enum_name::#variant_name { .. } => { #failed },
+            };
+        };
+        let mut arms = match (&method.ret, &method.stream) {
+            (Some(return_type), None) => {
+                let wire_type = self.wire_return_type(return_type);
+                let send = if cfparams.r#async { q! {send_async} } else { q! {send} };
+                let maybe_extraarg = if cfparams.extra_arg.is_some() { q! {, extra_arg} } else { q! {} };
+                q! {
+                    #enum_name::#variant_name { ret, .. } => {
+                        #failed
+                        let _ = #returnval_handler_macro ! (#send::<#wire_type>(ret, Err(#error) #maybe_extraarg));
+                        Ok(())
+                    }
+                }
+            }
+            _ => q! {
+                #enum_name::#variant_name { .. } => {
+                    #failed
+                    Ok(())
+                }
+            },
+        };
+        if method.handle.is_some() {
+            let handle_variant_name = method.handle_variant_name();
+            arms.extend(q! {
+                #enum_name::#handle_variant_name { msg, .. } => {
+                    #failed
+                    if let #runtime::registry::HandleMsg::Call(msg) = msg {
+                        #runtime::registry::Refuse::refuse(msg, #error);
+                    }
+                    Ok(())
+                }
+            });
+        }
+        arms
+    }
+
+    /// Handling of `method`'s call to a poisoned object by `poison_on_panic` call function. Uses bindings of `method`'s variant.
+    fn poisoned_refusal(&self, cfparams: &CallFnParams, method: &crate::Method) -> TokenStream {
+        let runtime = runtime_crate();
+        let failed = self.metrics_failed(cfparams);
+        let Some(returnval_handler_macro) = &self.params.returnval else {
+            return q! { { #failed } };
+        };
+        match (&method.ret, &method.stream) {
+            (Some(return_type), None) => {
+                let wire_type = self.wire_return_type(return_type);
+                let send = if cfparams.r#async { q! {send_async} } else { q! {send} };
+                let maybe_extraarg = if cfparams.extra_arg.is_some() { q! {, extra_arg} } else { q! {} };
+                q! {
+                    {
+                        #failed
+                        let _ = #returnval_handler_macro ! (#send::<#wire_type>(ret, Err(#runtime::CallError::Poisoned) #maybe_extraarg));
+                        Ok(())
+                    }
+                }
+            }
+            (Some(_), Some((_, item_type))) => {
+                let stream_error = self.stream_error(cfparams, item_type, q! {#runtime::CallError::Poisoned});
+                q! {
+                    {
+                        #failed
+                        #stream_error
+                        Ok(())
+                    }
+                }
+            }
+            _ => q! {
+                {
+                    #failed
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Statements sending `error` as the only item of streamed return value `ret` and ending the stream. Failures to send are ignored.
+    fn stream_error(&self, cfparams: &CallFnParams, item_type: &syn::Type, error: TokenStream) -> TokenStream {
+        let returnval_handler_macro = self.params.returnval.as_ref().unwrap();
+        let wire_item_type = self.wire_return_type(item_type);
+        let (send_item, send_end) = if cfparams.r#async {
+            (q! {send_item_async}, q! {send_end_async})
+        } else {
+            (q! {send_item}, q! {send_end})
+        };
+        let maybe_extraarg = if cfparams.extra_arg.is_some() { q! {, extra_arg} } else { q! {} };
+        q! {
+            let _ = #returnval_handler_macro ! (#send_item::<#wire_item_type>(&ret, Err(#error) #maybe_extraarg));
+            let _ = #returnval_handler_macro ! (#send_end::<#wire_item_type>(ret #maybe_extraarg));
+        }
+    }
+
+    /// Statement calling `cfparams`'s panic hook (if any) with `method_name` and caught `payload`
+    fn panic_hook_call(&self, cfparams: &CallFnParams, method_name: &syn::Ident) -> TokenStream {
+        let Some(hook) = &cfparams.panic_hook else {
+            return q! {};
+        };
+        let runtime = runtime_crate();
+        let literal = proc_macro2::Literal::string(&method_name.to_string());
+        q! {(#hook)(#literal, &#runtime::Panicked::new(&*payload));}
+    }
+
+    /// Expression reborrowing call function's `o` as `&mut`, for `Move`, `Mut` or `Box` `level`
+    fn o_mut(&self, level: ReceiverStyle) -> TokenStream {
+        match level {
+            ReceiverStyle::Move => q! {&mut o},
+            ReceiverStyle::Mut | ReceiverStyle::Box => q! {&mut *o},
+            _ => panic!("`enumizer_handle` methods require `move`, `ref_mut` or `boxed` call functions"),
+        }
+    }
+
+    /// Call of `method` on call function's `o` accessed with `level`
+    fn method_call(&self, level: ReceiverStyle, method: &crate::Method, args: TokenStream) -> TokenStream {
+        let method_name = &method.name;
+        match level.receiver_expr(method.receiver_style) {
+            Some(receiver) => {
+                let name = &self.name;
+                let path = if self.params.inherent_impl_mode {
+                    q! {#name}
+                } else {
+                    q! {<I as #name>}
+                };
+                q! {#path::#method_name(#receiver, #args)}
+            }
+            None => q! {o.#method_name(#args)},
+        }
+    }
+
+    /// `, arg = ?arg` span fields for arguments marked with `#[enumizer_trace]`
+    fn traced_fields(&self, method: &crate::Method) -> TokenStream {
+        let mut fields = TokenStream::new();
+        for arg in method.args.iter().filter(|a| a.traced) {
+            let argname = &arg.name;
+            fields.extend(q! {, #argname = ?#argname});
+        }
+        fields
+    }
+
+    /// In `trace` mode, wrap `call` of the method into a span continuing `trace` context of the enum value
+    fn traced_call(&self, cfparams: &CallFnParams, method: &crate::Method, call: TokenStream) -> TokenStream {
+        if !self.params.trace {
+            return call;
+        }
+        let runtime = runtime_crate();
+        let name = proc_macro2::Literal::string(&method.name.to_string());
+        let fields = self.traced_fields(method);
+        let run = if cfparams.r#async {
+            q! {#runtime::tracing::Instrument::instrument(async move { #call }, ::core::clone::Clone::clone(&span)).await}
+        } else {
+            q! {span.in_scope(|| #call)}
+        };
+        q! {
+            {
+                let span = #runtime::tracing::debug_span!(
+                    parent: trace.span(),
+                    #name,
+                    returned = #runtime::tracing::field::Empty,
+                    elapsed_us = #runtime::tracing::field::Empty
+                    #fields
+                );
+                let started = ::std::time::Instant::now();
+                let value = #run;
+                span.record("returned", true);
+                span.record("elapsed_us", started.elapsed().as_micros() as u64);
+                value
+            }
+        }
+    }
+
+    /// Expression evaluating `call` with panics caught into `Result<T, Box<dyn Any + Send>>`
+    fn catch_unwind_expr(&self, cfparams: &CallFnParams, call: TokenStream) -> TokenStream {
+        let runtime = runtime_crate();
+        if cfparams.r#async {
+            q! {#runtime::catch_unwind_async(async move { #call }).await}
+        } else {
+            q! {#runtime::catch_unwind(move || #call)}
+        }
+    }
+
+    /// Wrap `handled` expression (handling `self`) into calls of `cfparams`'s interceptor and metrics, if any.
+    /// `rejected` is the value of the whole expression if interceptor rejects the call (ignored without `returnval`).
+    fn intercepted(&self, cfparams: &CallFnParams, handled: TokenStream, rejected: TokenStream) -> TokenStream {
+        let Some(interceptor) = &cfparams.interceptor else {
+            return self.metered(cfparams, handled);
+        };
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let interceptor = q! {<#interceptor as #runtime::Interceptor<#enum_name>>};
+        let (send, maybe_extraarg) = (
+            if cfparams.r#async { q! {send_async} } else { q! {send} },
+            if cfparams.extra_arg.is_some() { q! {, extra_arg} } else { q! {} },
+        );
+        let handle = if self.params.returnval.is_some() {
+            q! {
+                let result = #handled;
+                #interceptor::after(info, started.elapsed());
+                result
+            }
+        } else {
+            q! {
+                #handled;
+                #interceptor::after(info, started.elapsed());
+            }
+        };
+        let rejected = if self.params.returnval.is_some() {
+            rejected
+        } else {
+            q! {{}}
+        };
+        let failed = self.metrics_failed(cfparams);
+        let reject_arm = self.reject_arm(q! {self}, send, maybe_extraarg, q! {{ #failed #rejected }});
+        self.metered(cfparams, q! {
+            {
+                let info = #enum_name::method_info(&self);
+                match #interceptor::before(info, &self) {
+                    Ok(()) => {
+                        let started = ::std::time::Instant::now();
+                        #handle
+                    }
+                    #reject_arm
+                }
+            }
+        })
+    }
+
+    /// Wrap `handled` expression (handling `self`) into reports to `cfparams`'s metrics, if any.
+    /// Makes `info` available to `metrics_failed` statements inside `handled`.
+    fn metered(&self, cfparams: &CallFnParams, handled: TokenStream) -> TokenStream {
+        let Some(metrics) = &cfparams.metrics else {
+            return handled;
+        };
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let metrics = q! {<#metrics as #runtime::Metrics>};
+        let queued = if self.params.sent_at {
+            q! {
+                if let ::core::option::Option::Some(sent_at) = #enum_name::sent_at(&self) {
+                    #metrics::queued(info, sent_at.elapsed());
+                }
+            }
+        } else {
+            q! {}
+        };
+        let handle = if self.params.returnval.is_some() {
+            q! {
+                let result = #handled;
+                #metrics::handled(info, started.elapsed());
+                if result.is_err() {
+                    #metrics::failed(info);
+                }
+                result
+            }
+        } else {
+            q! {
+                #handled;
+                #metrics::handled(info, started.elapsed());
+            }
+        };
+        q! {
+            {
+                let info = #enum_name::method_info(&self);
+                #queued
+                let started = ::std::time::Instant::now();
+                #handle
+            }
+        }
+    }
+
+    /// Statement reporting failed call to `cfparams`'s metrics, if any. Only valid inside `metered` expression.
+    fn metrics_failed(&self, cfparams: &CallFnParams) -> TokenStream {
+        let Some(metrics) = &cfparams.metrics else {
+            return q! {};
+        };
+        let runtime = runtime_crate();
+        q! {<#metrics as #runtime::Metrics>::failed(info);}
+    }
+
+    /// `match` arms replying `Err(error)` to return channels of variants with non-streamed return values
+    fn reply_error_arms(&self, returnval_handler_macro: &syn::Ident, send: TokenStream, maybe_extraarg: TokenStream, error: TokenStream) -> TokenStream {
+        let enum_name = &self.params.enum_name;
+        let mut arms = TokenStream::new();
+        for method in &self.methods {
+            if let (Some(return_type), None) = (&method.ret, &method.stream) {
+                let variant_name = method.variant_name();
+                let wire_type = self.wire_return_type(return_type);
+                arms.extend(q! {
+                    #enum_name::#variant_name { ret, .. } => {
+                        let _ = #returnval_handler_macro ! (#send::<#wire_type>(ret, Err(#error) #maybe_extraarg));
+                    }
+                });
+            }
+        }
+        arms
+    }
+
+    /// `impl Refuse`, replying `error` to return channel of the value (if it has one) instead of calling the method
+    pub(crate) fn generate_refuse_impl(&self, out: &mut TokenStream) {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let body = match &self.params.returnval {
+            Some(returnval_handler_macro) => {
+                let arms = self.reply_error_arms(returnval_handler_macro, q! {send}, q! {}, q! {error});
+                q! {
+                    #[allow(unreachable_patterns)]
+                    match self {
+                        #arms
+                        _ => (),
+                    }
+                }
+            }
+            None => q! {},
+        };
+        out.extend(q! {
+            impl #runtime::registry::Refuse for #enum_name {
+                #[allow(unused_variables)]
+                fn refuse(self, error: #runtime::CallError) {
+                    #body
+                }
+            }
+        });
+    }
+
+    /// `Err(rejected) => ...` match arm replying `CallError::Rejected` to `msg`'s return channel (if it has one) and evaluating to `value`
+    fn reject_arm(&self, msg: TokenStream, send: TokenStream, maybe_extraarg: TokenStream, value: TokenStream) -> TokenStream {
+        let Some(returnval_handler_macro) = &self.params.returnval else {
+            return q! {Err(_) => #value};
+        };
+        let runtime = runtime_crate();
+        let arms = self.reply_error_arms(returnval_handler_macro, send, maybe_extraarg, q! {#runtime::CallError::Rejected(rejected)});
+        if arms.is_empty() {
+            return q! {Err(_) => #value};
+        }
+        // Streams and control variants just get their return channels dropped
+        q! {
+            Err(rejected) => {
+                #[allow(unreachable_patterns)]
+                match #msg {
+                    #arms
+                    _ => (),
+                }
+                #value
+            }
+        }
+    }
+
+    /// `let control = ...;` statement determining `Control` value for `self`
+    fn control_of_self(&self) -> TokenStream {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        q! {
+            let control = if let #enum_name::Shutdown = self {
+                #runtime::Control::Shutdown
+            } else {
+                #runtime::Control::Continue
+            };
         }
+    }
 
+    fn generate_dispatch_impl(&self, out: &mut TokenStream, cfparams: &CallFnParams) {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let fn_name = &cfparams.name;
         let input_trait_or_type_name = &self.name;
-        let arg_o_with_type = match (self.params.inherent_impl_mode, level) {
-            (false, ReceiverStyle::Move) => q! {mut o: I},
-            (false, ReceiverStyle::Mut) => q! {o: &mut I},
-            (false, ReceiverStyle::Ref) => q! {o: &I},
-            (true, ReceiverStyle::Move) => q! {mut o: #input_trait_or_type_name},
-            (true, ReceiverStyle::Mut) => q! {o: &mut #input_trait_or_type_name},
-            (true, ReceiverStyle::Ref) => q! {o: & #input_trait_or_type_name},
+        if matches!(cfparams.level, ReceiverStyle::Move | ReceiverStyle::Box) {
+            panic!("`dispatch` requires `ref`, `ref_mut`, `arc`, `rc` or `pin_mut` call function");
+        }
+        if cfparams.extra_arg.is_some() {
+            panic!("`dispatch` is incompatible with `extra_arg_type`");
+        }
+        let (impl_generics, object) = if self.params.inherent_impl_mode {
+            (q! {}, q! {#input_trait_or_type_name})
+        } else {
+            let handle_bounds = self.handle_bounds();
+            (q! {<I: #input_trait_or_type_name + ::core::marker::Send + ?Sized #handle_bounds>}, q! {I})
         };
-        let maybe_requirement = if !self.params.inherent_impl_mode {
-            q! {<I: #input_trait_or_type_name>}
+        // Actor state holding the object the way call function needs it
+        let (target, o) = match cfparams.level {
+            ReceiverStyle::Arc => (q! {::std::sync::Arc<#object>}, q! {o}),
+            ReceiverStyle::Rc => (q! {::std::rc::Rc<#object>}, q! {o}),
+            ReceiverStyle::PinMut => (q! {::core::pin::Pin<::std::boxed::Box<#object>>}, q! {o.as_mut()}),
+            _ if cfparams.poison_on_panic => (q! {#runtime::Poisonable<#object>}, q! {o}),
+            _ => (object, q! {o}),
+        };
+        let impl_generics = if cfparams.r#async && cfparams.level == ReceiverStyle::Arc && !self.params.inherent_impl_mode {
+            // Future keeps `&Arc<I>`, which is only `Send` for `Sync` objects
+            let handle_bounds = self.handle_bounds();
+            q! {<I: #input_trait_or_type_name + ::core::marker::Send + ::core::marker::Sync + ?Sized #handle_bounds>}
         } else {
-            q! {}
+            impl_generics
         };
-
-        let maybe_returntype = if let Some(returnval_handler_macro) = returnval_handler {
-            q! { -> ::core::result::Result<(), #returnval_handler_macro ! (SendError)>}
+        // Failed sends of return values just mean that caller is no longer interested
+        let handle_result = |call: TokenStream| match (self.params.returnval.is_some(), self.params.control_variants) {
+            (true, true) => q! {#call.unwrap_or(#runtime::Control::Continue)},
+            (true, false) => q! {let _ = #call; #runtime::Control::Continue},
+            (false, _) => q! {#call; #runtime::Control::Continue},
+        };
+        if cfparams.r#async {
+            if !self.params.inherent_impl_mode && self.methods.iter().any(|m| m.r#async) {
+                panic!("`dispatch` for async call functions is not supported for traits with `async fn` methods, as their futures are not known to be `Send`. Use non-async `dispatch` or `inherent_impl` mode.");
+            }
+            if cfparams.level == ReceiverStyle::Rc {
+                panic!("`dispatch` for async call functions does not support `rc`, as `Rc` is not `Send`");
+            }
+            let body = handle_result(q! {self.#fn_name(#o).await});
+            out.extend(q! {
+                impl #impl_generics #runtime::actor::AsyncDispatch<#target> for #enum_name {
+                    fn dispatch_async(self, o: &mut #target) -> impl ::core::future::Future<Output = #runtime::Control> + ::core::marker::Send {
+                        async move {
+                            #body
+                        }
+                    }
+                }
+            });
         } else {
-            q! {}
+            let body = handle_result(q! {self.#fn_name(#o)});
+            out.extend(q! {
+                impl #impl_generics #runtime::actor::Dispatch<#target> for #enum_name {
+                    fn dispatch(self, o: &mut #target) -> #runtime::Control {
+                        #body
+                    }
+                }
+            });
+        }
+    }
+    fn generate_rw_dispatch_impl(&self, out: &mut TokenStream, cfparams: &CallFnParams) {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let fn_name = &cfparams.name;
+        let input_trait_or_type_name = &self.name;
+        if cfparams.level != ReceiverStyle::Move {
+            panic!("`rw_dispatch` requires `once` call function");
+        }
+        if cfparams.extra_arg.is_some() {
+            panic!("`rw_dispatch` is incompatible with `extra_arg_type`");
+        }
+        if cfparams.r#async {
+            panic!("`rw_dispatch` is not supported for async call functions");
+        }
+        let (impl_generics, target) = if self.params.inherent_impl_mode {
+            (q! {}, q! {#input_trait_or_type_name})
+        } else {
+            (q! {<I: #input_trait_or_type_name>}, q! {I})
         };
-        let maybe_extraarg = if let Some(extr) = extra_arg {
-            q! {, extra_arg : #extr}
+        let mut access_arms = TokenStream::new();
+        for method in &self.methods {
+            let variant_name = method.variant_name();
+            let access = match method.receiver_style {
+                ReceiverStyle::Ref => q! {Read},
+                ReceiverStyle::Mut => q! {Write},
+                // Typed receivers need the object itself to be put behind a pointer
+                ReceiverStyle::Move | ReceiverStyle::Box | ReceiverStyle::Arc | ReceiverStyle::Rc | ReceiverStyle::PinMut => q! {Consume},
+            };
+            access_arms.extend(q! {
+                #enum_name::#variant_name { .. } => #runtime::actor::Access::#access,
+            });
+        }
+        if self.params.control_variants {
+            access_arms.extend(q! {
+                #enum_name::Shutdown | #enum_name::Ping { .. } | #enum_name::Barrier { .. } => #runtime::actor::Access::Control,
+            });
+        }
+        let ref_arms = self.call_fn_match_arms(cfparams, ReceiverStyle::Ref);
+        let mut_arms = self.call_fn_match_arms(cfparams, ReceiverStyle::Mut);
+        let control = if self.params.control_variants {
+            self.control_of_self()
         } else {
-            q! {}
+            q! {let control = #runtime::Control::Continue;}
         };
-        let maybe_async = if cfparams.r#async {
-            q! {async}
+        // Failed sends of return values just mean that caller is no longer interested
+        let (ref_body, mut_body, once_body) = if let Some(returnval_handler_macro) = &self.params.returnval {
+            let ref_handled = self.intercepted(cfparams, q! {match self { #ref_arms }}, q! {Ok(())});
+            let mut_handled = self.intercepted(cfparams, q! {
+                {
+                    match self { #mut_arms }?;
+                    Ok(control)
+                }
+            }, q! {Ok(#runtime::Control::Continue)});
+            (
+                q! {let _ = (move || -> ::core::result::Result<(), #returnval_handler_macro ! (SendError)> { #ref_handled })();},
+                q! {
+                    (move || -> ::core::result::Result<#runtime::Control, #returnval_handler_macro ! (SendError)> {
+                        #control
+                        #mut_handled
+                    })().unwrap_or(#runtime::Control::Continue)
+                },
+                q! {let _ = self.#fn_name(o);},
+            )
         } else {
-            q! {}
+            let ref_handled = self.intercepted(cfparams, q! {match self { #ref_arms }}, q! {});
+            let mut_handled = self.intercepted(cfparams, q! {match self { #mut_arms }}, q! {});
+            (
+                ref_handled,
+                q! {
+                    #mut_handled
+                    #control
+                    control
+                },
+                q! {self.#fn_name(o);},
+            )
         };
         out.extend(q! {
-            impl #enum_name {
-                #pub_or_priv #maybe_async fn #fn_name #maybe_requirement(self, #arg_o_with_type #maybe_extraarg) #maybe_returntype {
+            impl #impl_generics #runtime::actor::RwDispatch<#target> for #enum_name {
+                fn access(&self) -> #runtime::actor::Access {
                     match self {
-                        #variants
+                        #access_arms
                     }
                 }
+                #[allow(unused_variables)]
+                fn dispatch_ref(self, o: &#target) {
+                    #ref_body
+                }
+                #[allow(unused_variables)]
+                fn dispatch_mut(self, o: &mut #target) -> #runtime::Control {
+                    #mut_body
+                }
+                fn dispatch_once(self, o: #target) {
+                    #once_body
+                }
             }
         });
     }
-    pub(crate) fn generate_resultified_trait(
-        &self,
-        out: &mut TokenStream,
-        gpparams: &GenProxyParams,
-    ) {
-        let pub_or_priv = self.params.access_mode.code();
-        let level = gpparams.level;
+
+    /// Signatures of resultified trait's methods, along with calls of the same methods with the same arguments
+    fn resultified_signatures(&self, gpparams: &GenProxyParams) -> Vec<(TokenStream, TokenStream)> {
+        let level = gpparams.receiver_level();
         let returnval_handler = self.params.returnval.as_ref();
-        let resultified_trait_name = gpparams.traitname.as_ref().unwrap();
-        //let name = &self.name;
-        let mut methods = TokenStream::new();
+        let mut signatures = vec![];
+        let maybe_async = if gpparams.r#async {
+            q! {async}
+        } else {
+            q! {}
+        };
         for method in &self.methods {
-            if method.r#async {
-                panic!("Resultified traits are not compatible with async methods");
-            }
             let rt_method_name = quote::format_ident!("try_{}", method.name,);
             // let method_name = &method.name;
             let mut args = TokenStream::new();
+            let mut names = TokenStream::new();
             for arg in &method.args {
                 let argname = &arg.name;
                 let argtype = &arg.ty;
                 args.extend(q! {
                     #argname : #argtype,
                 });
+                names.extend(q! {#argname,});
             }
             let slf = level.ts();
             let ret = if let Some(return_type) = &method.ret {
                 if let Some(returnval_handler_macro) = returnval_handler {
-                    q! {::core::result::Result<#return_type, #returnval_handler_macro ! ( RecvError )>}
+                    if let Some((_, item_type)) = &method.stream {
+                        let wire_item_type = self.wire_return_type(item_type);
+                        q! {#returnval_handler_macro ! ( StreamReceiver<#wire_item_type> )}
+                    } else {
+                        let wire_type = self.wire_return_type(return_type);
+                        q! {::core::result::Result<#wire_type, #returnval_handler_macro ! ( RecvError )>}
+                    }
                 } else {
                     unreachable!("Should had been rejected earlier")
                 }
             } else {
-                q! {()}
+                self.proxy_unit_type(gpparams)
             };
+            signatures.push((
+                q! {#maybe_async fn #rt_method_name(#slf, #args ) -> ::core::result::Result<#ret, E>},
+                q! {#rt_method_name(#names)},
+            ));
+            if gpparams.callbacks && method.stream.is_none() {
+                if let Some(return_type) = &method.ret {
+                    let then_method_name = quote::format_ident!("try_{}_then", method.name,);
+                    let return_type = self.wire_return_type(return_type);
+                    signatures.push((
+                        q! {#maybe_async fn #then_method_name<C: ::core::ops::FnOnce(#return_type) + ::core::marker::Send + 'static>(#slf, #args ret: C) -> ::core::result::Result<(), E>},
+                        q! {#then_method_name(#names ret)},
+                    ));
+                }
+            }
+        }
+        signatures
+    }
+
+    pub(crate) fn generate_resultified_trait(
+        &self,
+        out: &mut TokenStream,
+        gpparams: &GenProxyParams,
+    ) {
+        let pub_or_priv = self.params.access_mode.code();
+        let resultified_trait_name = gpparams.traitname.as_ref().unwrap();
+        let mut methods = TokenStream::new();
+        for (signature, _) in self.resultified_signatures(gpparams) {
             methods.extend(q! {
-                fn #rt_method_name(#slf, #args ) -> ::core::result::Result<#ret, E>;
+                #signature;
             });
         }
 
+        // Proxies are generic anyway, so there is little point in `Send` bounds on returned futures
+        let maybe_allow = if gpparams.r#async {
+            q! {#[allow(async_fn_in_trait)]}
+        } else {
+            q! {}
+        };
         out.extend(q! {
+            #maybe_allow
             #pub_or_priv trait #resultified_trait_name<E> {
                 #methods
             }
         });
     }
 
+    /// Impls of the trait and resultified trait for `MuxProxy<M, F>`, delegating to a temporary `gpparams` proxy
+    /// that converts enum values into `M`
+    pub(crate) fn generate_mux_impls(&self, out: &mut TokenStream, gpparams: &GenProxyParams) {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let proxy_name = &gpparams.name;
+        let fn_trait = gpparams.level.fn_trait();
+        let genparams = if gpparams.r#async {
+            q! {F: #fn_trait(M) -> Fu, Fu: ::core::future::Future<Output = ::core::result::Result<(), E>>}
+        } else {
+            q! {F: #fn_trait(M) -> ::core::result::Result<(), E>}
+        };
+        let proxy = q! {
+            #proxy_name(move |msg: #enum_name| f(::core::convert::From::from(msg)))
+        };
+        if let Some(resultified_trait_name) = &gpparams.traitname {
+            let maybe_await = if gpparams.r#async { q! {.await} } else { q! {} };
+            let mut methods = TokenStream::new();
+            for (signature, call) in self.resultified_signatures(gpparams) {
+                methods.extend(q! {
+                    #signature {
+                        let f = &self.0;
+                        #proxy.#call #maybe_await
+                    }
+                });
+            }
+            out.extend(q! {
+                impl<M: ::core::convert::From<#enum_name>, E, #genparams> #resultified_trait_name<E> for #runtime::MuxProxy<M, F> {
+                    #methods
+                }
+            });
+        }
+        if gpparams.some_impl_requested() {
+            let name = &self.name;
+            let mut methods = TokenStream::new();
+            for method in &self.methods {
+                let method_name = &method.name;
+                let mut args = TokenStream::new();
+                let mut names = TokenStream::new();
+                for arg in &method.args {
+                    let argname = &arg.name;
+                    let argtype = &arg.ty;
+                    args.extend(q! {#argname: #argtype,});
+                    names.extend(q! {#argname,});
+                }
+                let slf = method.receiver_style.ts();
+                let (maybe_async, maybe_await) = if method.r#async { (q! {async}, q! {.await}) } else { (q! {}, q! {}) };
+                let returntype = match &method.ret {
+                    Some(rt) => q! {-> #rt},
+                    None => q! {},
+                };
+                // Temporary proxy is put behind the pointer that typed receivers need
+                let call = match ReceiverStyle::Move.receiver_expr(method.receiver_style) {
+                    Some(receiver) => q! {{ let o = #proxy; #name::#method_name(#receiver, #names) }},
+                    None => q! {#proxy.#method_name(#names)},
+                };
+                methods.extend(q! {
+                    #maybe_async fn #method_name(#slf, #args) #returntype {
+                        let f = &self.0;
+                        #call #maybe_await
+                    }
+                });
+            }
+            out.extend(q! {
+                impl<M: ::core::convert::From<#enum_name>, E: ::core::fmt::Debug, #genparams> #name for #runtime::MuxProxy<M, F> {
+                    #methods
+                }
+            });
+        }
+    }
+
     pub(crate) fn generate_proxy(&self, out: &mut TokenStream, gpparams: &GenProxyParams) {
         let pub_or_priv = self.params.access_mode.code();
         let returnval_handler = self.params.returnval.as_ref();
         let extra_arg = gpparams.extra_arg.as_ref();
-        let level = gpparams.level;
+        let level = gpparams.receiver_level();
         let enum_name = &self.params.enum_name;
         let resultified_trait_name = gpparams.traitname.as_ref();
         let proxy_name = &gpparams.name;
@@ -295,64 +2168,155 @@ impl InputData {
         };
         //let name = &self.name;
         let mut methods = TokenStream::new();
+        let mut deferred_methods = TokenStream::new();
         for method in &self.methods {
+            if gpparams.restriction.as_ref().is_some_and(|r| !r.allows(method)) {
+                continue;
+            }
             let rt_method_name = quote::format_ident!("try_{}", method.name,);
             //let method_name = &method.name;
             let variant_name = method.variant_name();
             let mut args_with_types_for_signature = TokenStream::new();
-            let mut enum_variant_fields = TokenStream::new();
             for arg in &method.args {
                 let argname = &arg.name;
                 let argtype = &arg.ty;
                 args_with_types_for_signature.extend(q! {
                     #argname : #argtype,
                 });
-                if !arg.to_owned {
-                    enum_variant_fields.extend(q! {
-                        #argname,
-                    });
-                } else {
-                    let toowned = borrow_toowned();
-                    enum_variant_fields.extend(q! {
-                        #argname: #toowned::to_owned(#argname),
-                    });
-                }
             }
+            let enum_variant_fields = self.variant_fields_from_args(method);
             let slf = level.ts();
-            let (maybe_async, maybe_await, recv_pseudomethod_name) = if gpparams.r#async {
-                (q!{async}, q!{.await}, q!{recv_async})
+            let (maybe_async, recv_pseudomethod_name) = if gpparams.r#async {
+                (q!{async}, q!{recv_async})
             } else {
-                (q!{}, q!{}, q!{recv})
+                (q!{}, q!{recv})
             };
-            if let Some(rt) = &method.ret {
+            if let (Some((_, item_type)), Some(returnval_handler_macro)) = (&method.stream, returnval_handler) {
+                let (maybe_extraarg_with_comma, maybe_extraarg) = if let Some(_eat) = extra_arg {
+                    (q! {, self.1}, q! {self.1})
+                } else {
+                    (q! {}, q! {})
+                };
+                let sent = self.proxy_send(gpparams, Some(method), q! {#enum_name::#variant_name { #enum_variant_fields ret: tx }});
+                let item_type = self.wire_return_type(item_type);
+                methods.extend(q! {
+                    #pub_or_priv2 #maybe_async fn #rt_method_name(#slf, #args_with_types_for_signature ) -> ::core::result::Result<#returnval_handler_macro ! (StreamReceiver<#item_type>), E> {
+                        let (tx, rx) = #returnval_handler_macro !(create_stream::<#item_type>(#maybe_extraarg));
+                        #sent ?;
+                        Ok(#returnval_handler_macro ! (recv_stream::<#item_type>(rx #maybe_extraarg_with_comma) ) )
+                    }
+                });
+            } else if let Some(rt) = &method.ret {
+                let rt = self.wire_return_type(rt);
                 let returnval_handler_macro = returnval_handler.unwrap();
                 let (maybe_extraarg_with_comma, maybe_extraarg) = if let Some(_eat) = extra_arg {
                     (q! {, self.1}, q! {self.1})
                 } else {
                     (q! {}, q! {})
                 };
+                let sent = self.proxy_send(gpparams, Some(method), q! {#enum_name::#variant_name { #enum_variant_fields ret: tx }});
                 methods.extend(q! {
                     #pub_or_priv2 #maybe_async fn #rt_method_name(#slf, #args_with_types_for_signature ) -> ::core::result::Result<::core::result::Result<#rt, #returnval_handler_macro ! (RecvError)>, E> {
                         let (tx, rx) = #returnval_handler_macro !(create::<#rt>(#maybe_extraarg));
-                        self.0(#enum_name::#variant_name { #enum_variant_fields ret: tx }) #maybe_await ?;
+                        #sent ?;
                         Ok(#returnval_handler_macro ! (#recv_pseudomethod_name::<#rt>(rx #maybe_extraarg_with_comma) ) )
                     }
                 });
+                if gpparams.deferred {
+                    let deferred_method_name = quote::format_ident!("try_{}_deferred", method.name,);
+                    let runtime = runtime_crate();
+                    let (maybe_extraarg_clone, maybe_extraarg_for_recv) = if let Some(_eat) = extra_arg {
+                        (q! {let extra = ::core::clone::Clone::clone(&self.1);}, q! {, extra})
+                    } else {
+                        (q! {}, q! {})
+                    };
+                    let (pending_type, pending_value) = if gpparams.r#async {
+                        (
+                            q! {impl ::core::future::Future<Output=::core::result::Result<#rt, #returnval_handler_macro ! (RecvError)>>},
+                            q! {async move { #returnval_handler_macro ! (recv_async::<#rt>(rx #maybe_extraarg_for_recv)) }},
+                        )
+                    } else {
+                        (
+                            q! {impl ::core::ops::FnOnce() -> ::core::result::Result<#rt, #returnval_handler_macro ! (RecvError)>},
+                            q! {move || #returnval_handler_macro ! (recv::<#rt>(rx #maybe_extraarg_for_recv))},
+                        )
+                    };
+                    let sent = self.proxy_send(gpparams, Some(method), q! {#enum_name::#variant_name { #enum_variant_fields ret: tx }});
+                    deferred_methods.extend(q! {
+                        #pub_or_priv #maybe_async fn #deferred_method_name(#slf, #args_with_types_for_signature ) -> ::core::result::Result<#runtime::PendingReply<#pending_type>, E> {
+                            let (tx, rx) = #returnval_handler_macro !(create::<#rt>(#maybe_extraarg));
+                            #maybe_extraarg_clone
+                            #sent ?;
+                            Ok(#runtime::PendingReply::new(#pending_value))
+                        }
+                    });
+                }
+                if gpparams.callbacks {
+                    let then_method_name = quote::format_ident!("try_{}_then", method.name,);
+                    let sent = self.proxy_send(gpparams, Some(method), q! {#enum_name::#variant_name { #enum_variant_fields ret: tx }});
+                    methods.extend(q! {
+                        #pub_or_priv2 #maybe_async fn #then_method_name<C: ::core::ops::FnOnce(#rt) + ::core::marker::Send + 'static>(#slf, #args_with_types_for_signature ret: C) -> ::core::result::Result<(), E> {
+                            let tx = #returnval_handler_macro !(callback::<#rt>(ret #maybe_extraarg_with_comma));
+                            #sent
+                        }
+                    });
+                }
             } else {
+                let sent = self.proxy_send(gpparams, Some(method), q! {#enum_name::#variant_name{ #enum_variant_fields }});
+                let unit = self.proxy_unit_type(gpparams);
                 methods.extend(q! {
-                    #pub_or_priv2 #maybe_async fn #rt_method_name(#slf, #args_with_types_for_signature ) -> ::core::result::Result<(), E> {
-                        self.0(#enum_name::#variant_name{ #enum_variant_fields }) #maybe_await
+                    #pub_or_priv2 #maybe_async fn #rt_method_name(#slf, #args_with_types_for_signature ) -> ::core::result::Result<#unit, E> {
+                        #sent
                     }
                 });
             };
         }
-        let fn_trait = level.fn_trait();
-
-        let maybe_extraarg = if let Some(eat) = extra_arg {
+        let mut control_methods = TokenStream::new();
+        if self.params.control_variants {
+            let returnval_handler_macro = returnval_handler.unwrap();
+            let slf = level.ts();
+            let (maybe_async, recv_pseudomethod_name) = if gpparams.r#async {
+                (q!{async}, q!{recv_async})
+            } else {
+                (q!{}, q!{recv})
+            };
+            let (maybe_extraarg_with_comma, maybe_extraarg) = if let Some(_eat) = extra_arg {
+                (q! {, self.1}, q! {self.1})
+            } else {
+                (q! {}, q! {})
+            };
+            let sent = self.proxy_send(gpparams, None, q! {#enum_name::Shutdown});
+            control_methods.extend(q! {
+                /// Ask the receiving side to stop handling messages
+                #pub_or_priv #maybe_async fn try_shutdown(#slf) -> ::core::result::Result<(), E> {
+                    #sent
+                }
+            });
+            for (method_name, variant_name, doc) in [
+                ("try_ping", "Ping", "Check that the receiving side is alive"),
+                ("try_barrier", "Barrier", "Wait until all previously sent messages are handled"),
+            ] {
+                let method_name = quote::format_ident!("{}", method_name);
+                let variant_name = quote::format_ident!("{}", variant_name);
+                let sent = self.proxy_send(gpparams, None, q! {#enum_name::#variant_name { ret: tx }});
+                control_methods.extend(q! {
+                    #[doc = #doc]
+                    #pub_or_priv #maybe_async fn #method_name(#slf) -> ::core::result::Result<::core::result::Result<(), #returnval_handler_macro ! (RecvError)>, E> {
+                        let (tx, rx) = #returnval_handler_macro !(create::<()>(#maybe_extraarg));
+                        #sent ?;
+                        Ok(#returnval_handler_macro ! (#recv_pseudomethod_name::<()>(rx #maybe_extraarg_with_comma) ) )
+                    }
+                });
+            }
+        }
+        let mut maybe_extraarg = if let Some(eat) = extra_arg {
             q! {, pub #eat}
         } else {
             q! {}
         };
+        if let Some(id_type) = &gpparams.object_id {
+            maybe_extraarg.extend(q! {, pub #id_type});
+        }
 
         let maybe_trait_for_impl = if let Some(rtn) = resultified_trait_name {
             q! {#rtn<E> for}
@@ -360,27 +2324,42 @@ impl InputData {
             q! {}
         };
 
-        #[allow(non_snake_case)]
-        let F_and_maybe_Fu_genparams = if gpparams.r#async {
-            q! { F: #fn_trait(#enum_name) -> Fu, Fu: ::core::future::Future<Output = ::core::result::Result<(), E>>  }
-        } else {
-            q! { F: #fn_trait(#enum_name) -> ::core::result::Result<(), E>  }
+        let (closure_field, maybe_interior_doc) = match gpparams.interior {
+            None => (q! {F}, q! {}),
+            Some(crate::Interior::RefCell) => (
+                q! {::core::cell::RefCell<F>},
+                q! {#[doc = "Closure is kept in a `RefCell` and borrowed for each call, so the proxy is not `Sync`. Calling the proxy again from inside the closure panics."]},
+            ),
+            Some(crate::Interior::Mutex) => (
+                q! {::std::sync::Mutex<F>},
+                q! {#[doc = "Closure is kept in a `Mutex` and locked for each call, so concurrent calls through the proxy are serialized. Calling the proxy again from inside the closure deadlocks."]},
+            ),
         };
-
         #[allow(non_snake_case)]
-        let maybe_Fu = if gpparams.r#async {
-            q! {, Fu}
-        } else {
-            q! {}
-        };
+        let (F_and_maybe_Fu_genparams, maybe_Fu) = self.closure_genparams(gpparams, &q! {E});
 
         out.extend(q! {
-            #pub_or_priv struct #proxy_name<E, #F_and_maybe_Fu_genparams > (pub F #maybe_extraarg);
+            #maybe_interior_doc
+            #pub_or_priv struct #proxy_name<E, #F_and_maybe_Fu_genparams > (pub #closure_field #maybe_extraarg);
 
             impl<E, #F_and_maybe_Fu_genparams> #maybe_trait_for_impl #proxy_name<E, F #maybe_Fu> {
                 #methods
             }
         });
+        if gpparams.deferred {
+            out.extend(q! {
+                impl<E, #F_and_maybe_Fu_genparams> #proxy_name<E, F #maybe_Fu> {
+                    #deferred_methods
+                }
+            });
+        }
+        if self.params.control_variants {
+            out.extend(q! {
+                impl<E, #F_and_maybe_Fu_genparams> #proxy_name<E, F #maybe_Fu> {
+                    #control_methods
+                }
+            });
+        }
     }
     pub(crate) fn generate_infallible_impl(
         &self,
@@ -391,9 +2370,14 @@ impl InputData {
         let name = &self.name;
         let mut methods = TokenStream::new();
         for method in &self.methods {
-            if method.r#async {
-                panic!("Generating trait impls for async methods is not supported");
+            if method.r#async != gpparams.r#async {
+                panic!("Infallible trait impl requires all methods to be async for async proxies and non-async for non-async proxies. `{}` does not match.", method.name);
             }
+            let (maybe_async, maybe_await) = if method.r#async {
+                (q! {async}, q! {.await})
+            } else {
+                (q! {}, q! {})
+            };
             let rt_method_name = quote::format_ident!("try_{}", method.name,);
             let method_name = &method.name;
             let mut args_for_signature = TokenStream::new();
@@ -409,29 +2393,38 @@ impl InputData {
                 });
             }
             let slf = method.receiver_style.ts();
+            let slf2 = method.receiver_style.proxy_self_expr(gpparams.receiver_level()).unwrap_or(q! {self});
             let methodcall = if resultified_trait_name.is_some() {
                 q! { R::#rt_method_name }
             } else {
                 q! { Self::#rt_method_name }
             };
+            let maybe_second_unwrap = if gpparams.interceptor.is_some() { q! {.unwrap()} } else { q! {} };
             methods.extend(q! {
-                fn #method_name(#slf, #args_for_signature ) {
-                    #methodcall(self, #args_for_calling).unwrap()
+                #maybe_async fn #method_name(#slf, #args_for_signature ) {
+                    #methodcall(#slf2, #args_for_calling) #maybe_await .unwrap() #maybe_second_unwrap
                 }
             });
         }
+        let maybe_unpin = self.needs_unpin(gpparams.receiver_level());
         if let Some(rtn) = resultified_trait_name {
+            let maybe_unpin = if maybe_unpin { q! {+ ::core::marker::Unpin} } else { q! {} };
             out.extend(q! {
-                impl<R:#rtn<::core::convert::Infallible>> #name for R {
+                impl<R:#rtn<::core::convert::Infallible> #maybe_unpin> #name for R {
                     #methods
                 }
             });
         } else {
             let proxy_name = &gpparams.name;
-            let fn_trait = gpparams.level.fn_trait();
-            let enum_name = &self.params.enum_name;
+            #[allow(non_snake_case)]
+            let (F_and_maybe_Fu_genparams, maybe_Fu) = self.closure_genparams(gpparams, &q! {::core::convert::Infallible});
+            let maybe_where_clause = if maybe_unpin {
+                q! {where #proxy_name<::core::convert::Infallible, F #maybe_Fu>: ::core::marker::Unpin}
+            } else {
+                q! {}
+            };
             out.extend(q! {
-                impl<F: #fn_trait(#enum_name) -> ::core::result::Result<(), ::core::convert::Infallible>> #name for #proxy_name<::core::convert::Infallible, F> {
+                impl<#F_and_maybe_Fu_genparams> #name for #proxy_name<::core::convert::Infallible, F #maybe_Fu> #maybe_where_clause {
                     #methods
                 }
             });
@@ -444,17 +2437,22 @@ impl InputData {
         gpparams: &GenProxyParams,
     ) {
         let returnval_handler = self.params.returnval.as_ref();
-        let level = gpparams.level;
+        let level = gpparams.receiver_level();
         let resultified_trait_name = gpparams.traitname.as_ref();
         let proxy_name = &gpparams.name;
-        let fn_trait = level.fn_trait();
-        let enum_name = &self.params.enum_name;
         let name = &self.name;
         let mut methods = TokenStream::new();
         for method in &self.methods {
-            if method.r#async {
-                panic!("Generating trait impls for async methods is not supported");
+            let async_matches = method.r#async == gpparams.r#async
+                && !matches!(method.stream, Some((StreamKind::Stream, _)));
+            if !async_matches && !gpparams.gen_unwrapping_and_panicking {
+                panic!("Cannot implement `{}` for {} proxy: only async methods can be implemented by async proxies and only non-async methods by non-async proxies. Methods returning `impl Stream` are not supported. Use `unwrapping_and_panicking_impl` to force generation and retain only some methods", method.name, if gpparams.r#async { "async" } else { "non-async" });
             }
+            let (maybe_async, maybe_await) = if method.r#async {
+                (q! {async}, q! {.await})
+            } else {
+                (q! {}, q! {})
+            };
             let rt_method_name = quote::format_ident!("try_{}", method.name,);
             let method_name = &method.name;
             let mut args_with_types = TokenStream::new();
@@ -470,58 +2468,313 @@ impl InputData {
                 });
             }
             let slf = method.receiver_style.ts();
-            let can_call = match (method.receiver_style, level) {
-                (ReceiverStyle::Move, _) => true,
-                (ReceiverStyle::Mut, ReceiverStyle::Move) => false,
-                (ReceiverStyle::Mut, ReceiverStyle::Mut) => true,
-                (ReceiverStyle::Mut, ReceiverStyle::Ref) => true,
-                (ReceiverStyle::Ref, ReceiverStyle::Move) => false,
-                (ReceiverStyle::Ref, ReceiverStyle::Mut) => false,
-                (ReceiverStyle::Ref, ReceiverStyle::Ref) => true,
-            };
-            let mut maybe_second_unwrap = q! {};
+            let slf2 = method.receiver_style.proxy_self_expr(level);
+            // Rejections by proxy-side interceptor are propagated to the caller
+            let mut maybe_second_unwrap = if gpparams.interceptor.is_some() { q! {.unwrap()} } else { q! {} };
             let returntype = if let Some(rt) = &method.ret {
-                maybe_second_unwrap = q! {.unwrap()};
-                q! {-> #rt}
+                maybe_second_unwrap = match method.stream {
+                    // Caught panics are propagated to the caller
+                    None if self.wraps_return_values() => q! {.unwrap().unwrap()},
+                    None => q! {.unwrap()},
+                    Some((StreamKind::Iterator, _)) if self.wraps_return_values() => q! {.map(|x| x.unwrap().unwrap())},
+                    Some((StreamKind::Collection, _)) if self.wraps_return_values() => q! {.map(|x| x.unwrap().unwrap()).collect()},
+                    Some((StreamKind::Iterator, _)) => q! {.map(::core::result::Result::unwrap)},
+                    Some((StreamKind::Collection, _)) => q! {.map(::core::result::Result::unwrap).collect()},
+                    Some((StreamKind::Stream, _)) => q! {},
+                };
+                match &method.handle {
+                    Some(handle) => {
+                        let rt = &handle.ret;
+                        q! {-> #rt}
+                    }
+                    None => q! {-> #rt},
+                }
             } else {
                 q! {}
             };
-            if can_call {
-                let slf2 = match (method.receiver_style, level) {
-                    (ReceiverStyle::Move, ReceiverStyle::Ref) => q! {&self},
-                    (ReceiverStyle::Move, ReceiverStyle::Mut) => q! {&mut self},
-                    _ => q! {self},
-                };
+            let can_call = slf2.is_some();
+            if let (Some(slf2), true) = (slf2, async_matches) {
                 let methodcall = if let Some(rtn) = resultified_trait_name {
                     q! { #rtn::#rt_method_name }
                 } else {
                     q! { Self::#rt_method_name }
                 };
+                let body = match &method.handle {
+                    Some(handle) => {
+                        let sub_proxy = self.sub_proxy(method, handle);
+                        q! {
+                            let id = #methodcall(#slf2, #args_without_types) .unwrap() #maybe_second_unwrap;
+                            #sub_proxy
+                        }
+                    }
+                    None => q! {#methodcall(#slf2, #args_without_types) #maybe_await .unwrap() #maybe_second_unwrap},
+                };
                 methods.extend(q! {
-                    fn #method_name(#slf, #args_with_types ) #returntype {
-                        #methodcall(#slf2, #args_without_types).unwrap() #maybe_second_unwrap
+                    #maybe_async fn #method_name(#slf, #args_with_types ) #returntype {
+                        #body
                     }
                 });
             } else {
                 let literal1 = proc_macro2::Literal::string(&self.name.to_string());
                 let literal2 = proc_macro2::Literal::string(&method.name.to_string());
                 let literal3 = proc_macro2::Literal::string(&proxy_name.to_string());
+                let reason = if can_call {
+                    q! {"mismatching asyncness"}
+                } else {
+                    q! {"accepting too weak `self`"}
+                };
                 methods.extend(q! {
-                    fn #method_name(#slf, #args_with_types ) #returntype {
-                        panic!("Cannot call {}::{} accepting too weak `self` on {}", #literal1, #literal2, #literal3)
+                    #maybe_async fn #method_name(#slf, #args_with_types ) #returntype {
+                        panic!("Cannot call {}::{} {} on {}", #literal1, #literal2, #reason, #literal3)
                     }
                 });
             }
         }
-        let maybe_additional_where_clause = if let Some(returval_macro) = returnval_handler {
+        let mut maybe_additional_where_clause = if let Some(returval_macro) = returnval_handler {
             q! {,#returval_macro ! (RecvError) : ::core::fmt::Debug}
         } else {
             q! {}
         };
+        if self.has_handles() {
+            // Sub-proxies get their own clones of the closure
+            maybe_additional_where_clause.extend(q! {, F: ::core::clone::Clone + ::core::marker::Send + ::core::marker::Sync + 'static, E: 'static});
+        }
+        #[allow(non_snake_case)]
+        let (F_and_maybe_Fu_genparams, maybe_Fu) = self.closure_genparams(gpparams, &q! {E});
+        if self.needs_unpin(level) {
+            // `self: Pin<&mut Self>` methods get `&mut Self` from the pin
+            maybe_additional_where_clause.extend(q! {, #proxy_name<E,F #maybe_Fu>: ::core::marker::Unpin});
+        }
         out.extend(q! {
-            impl<E, F: #fn_trait(#enum_name) -> ::core::result::Result<(), E>>  #name for #proxy_name<E,F> where E : ::core::fmt::Debug #maybe_additional_where_clause {
+            impl<E, #F_and_maybe_Fu_genparams>  #name for #proxy_name<E,F #maybe_Fu> where E : ::core::fmt::Debug #maybe_additional_where_clause {
                 #methods
             }
         });
     }
+
+    /// Expression boxing `handle`'s sub-proxy bound to `id`, sending its calls through proxy's closure in `method`'s handle variant.
+    /// Dropping the sub-proxy sends `HandleMsg::Release`.
+    fn sub_proxy(&self, method: &crate::Method, handle: &crate::Handle) -> TokenStream {
+        let runtime = runtime_crate();
+        let enum_name = &self.params.enum_name;
+        let handle_variant_name = method.handle_variant_name();
+        let sub_enum = &handle.sub_enum;
+        let sub_proxy = &handle.sub_proxy;
+        q! {
+            let f = ::core::clone::Clone::clone(&self.0);
+            let guard = #runtime::registry::ReleaseGuard::new({
+                let f = ::core::clone::Clone::clone(&f);
+                move || {
+                    let _ = f(#enum_name::#handle_variant_name { id, msg: #runtime::registry::HandleMsg::Release });
+                }
+            });
+            ::std::boxed::Box::new(#sub_proxy(
+                move |(id, msg): (#runtime::registry::HandleId, #sub_enum)| {
+                    let _guard = &guard;
+                    f(#enum_name::#handle_variant_name { id, msg: #runtime::registry::HandleMsg::Call(msg) })
+                },
+                id,
+            ))
+        }
+    }
+
+    /// Fields (except `ret`) of `method`'s variant, constructed from method's arguments
+    fn variant_fields_from_args(&self, method: &crate::Method) -> TokenStream {
+        let runtime = runtime_crate();
+        let mut fields = TokenStream::new();
+        for arg in &method.args {
+            let argname = &arg.name;
+            if !arg.to_owned {
+                fields.extend(q! {
+                    #argname,
+                });
+            } else {
+                let toowned = borrow_toowned();
+                fields.extend(q! {
+                    #argname: #toowned::to_owned(#argname),
+                });
+            }
+        }
+        if self.params.trace {
+            fields.extend(q! {
+                trace: #runtime::TraceContext::current(),
+            });
+        }
+        if self.params.sent_at {
+            fields.extend(q! {
+                sent_at: #runtime::SentAt::now(),
+            });
+        }
+        fields
+    }
+
+    /// Expression constructing `method`'s variant from method's arguments, with return channel's receiving end dropped
+    fn detached_msg(&self, method: &crate::Method) -> TokenStream {
+        let enum_name = &self.params.enum_name;
+        let variant_name = method.variant_name();
+        let fields = self.variant_fields_from_args(method);
+        match (&method.ret, self.params.returnval.as_ref()) {
+            (Some(rt), Some(returnval_handler_macro)) => {
+                let wire_type = self.wire_return_type(rt);
+                q! {
+                    {
+                        let (tx, _rx) = #returnval_handler_macro !(create::<#wire_type>());
+                        #enum_name::#variant_name { #fields ret: tx }
+                    }
+                }
+            }
+            _ => q! {#enum_name::#variant_name { #fields }},
+        }
+    }
+
+    /// Expression passing `msg` to proxy's closure, through `gpparams`'s interceptor and metrics, if any.
+    /// Rejected messages are not passed to the closure: rejection is replied to `msg`'s return channel instead.
+    fn proxy_send(&self, gpparams: &GenProxyParams, method: Option<&crate::Method>, msg: TokenStream) -> TokenStream {
+        let runtime = runtime_crate();
+        let maybe_await = if gpparams.r#async {
+            q! {.await}
+        } else {
+            q! {}
+        };
+        let call = self.closure_call(gpparams, q! {msg});
+        // In `trace` mode, message is created inside the span, so that it carries it as `TraceContext`
+        let (open_span, msg, send) = match method {
+            Some(method) if self.params.trace => {
+                let name = proc_macro2::Literal::string(&method.name.to_string());
+                let fields = self.traced_fields(method);
+                let send = if gpparams.r#async {
+                    q! {#runtime::tracing::Instrument::instrument(#call, ::core::clone::Clone::clone(&span)).await}
+                } else {
+                    q! {span.in_scope(|| #call)}
+                };
+                // Like spans of call functions, but `returned` tells whether the closure accepted the message
+                let send = q! {
+                    {
+                        let started = ::std::time::Instant::now();
+                        let result = #send;
+                        span.record("returned", result.is_ok());
+                        span.record("elapsed_us", started.elapsed().as_micros() as u64);
+                        result
+                    }
+                };
+                let open_span = q! {
+                    let span = #runtime::tracing::debug_span!(
+                        #name,
+                        returned = #runtime::tracing::field::Empty,
+                        elapsed_us = #runtime::tracing::field::Empty
+                        #fields
+                    );
+                };
+                (open_span, q! {span.in_scope(|| #msg)}, send)
+            }
+            _ => (q! {}, msg, q! {#call #maybe_await}),
+        };
+        let enum_name = &self.params.enum_name;
+        let (get_info, send) = if let Some(metrics) = &gpparams.metrics {
+            (
+                q! {let info = #enum_name::method_info(&msg);},
+                q! {
+                    {
+                        let result = #send;
+                        <#metrics as #runtime::Metrics>::sent(info, result.is_ok());
+                        result
+                    }
+                },
+            )
+        } else {
+            (q! {}, send)
+        };
+        let Some(interceptor) = &gpparams.interceptor else {
+            if open_span.is_empty() && get_info.is_empty() {
+                let call = self.closure_call(gpparams, msg);
+                return q! {#call #maybe_await};
+            }
+            return q! {
+                {
+                    #open_span
+                    let msg = #msg;
+                    #get_info
+                    #send
+                }
+            };
+        };
+        let interceptor = q! {<#interceptor as #runtime::Interceptor<#enum_name>>};
+        let maybe_extraarg = if gpparams.extra_arg.is_some() {
+            q! {, self.1}
+        } else {
+            q! {}
+        };
+        // Rejections of calls without return values are returned by `try_*` methods, see `proxy_unit_type`
+        let (result, reject_arm) = if method.is_some_and(|m| m.ret.is_none()) {
+            (
+                q! {result.map(Ok)},
+                q! {Err(rejected) => Ok(Err(#runtime::CallError::Rejected(rejected)))},
+            )
+        } else {
+            (q! {result}, self.reject_arm(q! {msg}, q! {send}, maybe_extraarg, q! {Ok(())}))
+        };
+        q! {
+            {
+                #open_span
+                let msg = #msg;
+                let info = #enum_name::method_info(&msg);
+                match #interceptor::before(info, &msg) {
+                    Ok(()) => {
+                        let started = ::std::time::Instant::now();
+                        let result = #send;
+                        #interceptor::after(info, started.elapsed());
+                        #result
+                    }
+                    #reject_arm
+                }
+            }
+        }
+    }
+
+    /// Expression calling proxy's closure with `msg` (and a clone of target object's id in `object_id` mode).
+    /// `interior` proxies borrow or lock the closure first.
+    fn closure_call(&self, gpparams: &GenProxyParams, msg: TokenStream) -> TokenStream {
+        let arg = match gpparams.object_id {
+            None => msg,
+            Some(_) => {
+                let id_field = syn::Index::from(if gpparams.extra_arg.is_some() { 2 } else { 1 });
+                q! {(::core::clone::Clone::clone(&self.#id_field), #msg)}
+            }
+        };
+        // Lock is released before awaiting the future in `async` mode
+        let closure = match gpparams.interior {
+            None => return q! {self.0(#arg)},
+            Some(crate::Interior::RefCell) => q! {self.0.borrow_mut()},
+            Some(crate::Interior::Mutex) => q! {self.0.lock().unwrap_or_else(::std::sync::PoisonError::into_inner)},
+        };
+        q! {
+            {
+                let mut f = #closure;
+                let result = f(#arg);
+                result
+            }
+        }
+    }
+
+    /// Generic parameters for proxy's closure (and its future in `async` mode) with specified error type.
+    /// Also returns `, Fu` to be appended to proxy's generic arguments in `async` mode.
+    fn closure_genparams(&self, gpparams: &GenProxyParams, error_type: &TokenStream) -> (TokenStream, TokenStream) {
+        let fn_trait = gpparams.level.fn_trait();
+        let enum_name = &self.params.enum_name;
+        let enum_name = match &gpparams.object_id {
+            Some(id_type) => q! {(#id_type, #enum_name)},
+            None => q! {#enum_name},
+        };
+        if gpparams.r#async {
+            (
+                q! { F: #fn_trait(#enum_name) -> Fu, Fu: ::core::future::Future<Output = ::core::result::Result<(), #error_type>>  },
+                q! {, Fu},
+            )
+        } else {
+            (
+                q! { F: #fn_trait(#enum_name) -> ::core::result::Result<(), #error_type>  },
+                q! {},
+            )
+        }
+    }
 }
@@ -7,6 +7,8 @@ struct Argument {
     ty: syn::Type,
     enum_attr: Vec<proc_macro2::Group>,
     to_owned: bool,
+    /// `#[enumizer_trace]`: record as a span field
+    traced: bool,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -14,6 +16,25 @@ enum ReceiverStyle {
     Move,
     Mut,
     Ref,
+    /// `self: Box<Self>`. As a call function level, `o: Box<I>`.
+    Box,
+    /// `self: Arc<Self>`. As a call function level, `o: &Arc<I>`.
+    Arc,
+    /// `self: Rc<Self>`. As a call function level, `o: &Rc<I>`.
+    Rc,
+    /// `self: Pin<&mut Self>`. As a call function level, `o: Pin<&mut I>`.
+    PinMut,
+}
+
+/// How return value is delivered if it is streamed item by item instead of being sent once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StreamKind {
+    /// `-> impl Iterator<Item=T>`
+    Iterator,
+    /// `#[enumizer_stream] -> Vec<T>` or other collection
+    Collection,
+    /// `-> impl Stream<Item=T>`. Can only be handled in async call functions.
+    Stream,
 }
 
 struct Method {
@@ -21,12 +42,41 @@ struct Method {
     receiver_style: ReceiverStyle,
     args: Vec<Argument>,
     ret: Option<syn::Type>,
+    /// Kind and item type for streamed return values. `ret` is still the original return type.
+    stream: Option<(StreamKind, syn::Type)>,
     enum_attr: Vec<proc_macro2::Group>,
     return_attr: Vec<proc_macro2::Group>,
     r#async: bool,
+    /// `Priority` variant name from `#[enumizer_priority(...)]`
+    priority: Option<Ident>,
+    /// `#[enumizer_coalesce]`, with optional `key = ` argument name
+    coalesce: Option<Option<Ident>>,
+    /// `#[enumizer_handle(...)]`. `ret` is then `HandleId`, original return type is kept here.
+    handle: Option<Handle>,
+    /// `#[enumizer_mock_eq]`: mock compares arguments with `PartialEq` and reports them with `Debug`
+    mock_eq: bool,
+}
+
+/// Returned object stays with the callee, registered in its `Handles`. Caller gets a sub-proxy bound to object's id.
+struct Handle {
+    /// Original return type, like `Box<dyn Qqq>`
+    ret: syn::Type,
+    /// Enum of the returned object's trait
+    sub_enum: Ident,
+    /// Proxy of the returned object's trait, generated with `object_id(HandleId)`
+    sub_proxy: Ident,
 }
 
 impl Method {
+    fn needs_async(&self) -> bool {
+        self.r#async || matches!(self.stream, Some((StreamKind::Stream, _)))
+    }
+
+    /// Name of the variant carrying calls to objects returned by this `#[enumizer_handle]` method
+    fn handle_variant_name(&self) -> proc_macro2::Ident {
+        quote::format_ident!("{}Handle", self.variant_name())
+    }
+
     fn variant_name(&self) -> proc_macro2::Ident {
         quote::format_ident!(
             "{}",
@@ -69,11 +119,34 @@ struct GenProxyParams {
     name: Ident,
     traitname: Option<Ident>,
     r#async: bool,
+    callbacks: bool,
+    deferred: bool,
+    /// Type implementing `Interceptor`, called before sending each message
+    interceptor: Option<Ident>,
+    /// Type implementing `Metrics`, notified about each sent message
+    metrics: Option<Ident>,
+    /// Also implement the traits for `MuxProxy` with a wrapper enum from `multiplex!`
+    multiplexed: bool,
+    /// Type of target object's id, stored in the proxy and passed to the closure along with each enum value
+    object_id: Option<proc_macro2::TokenStream>,
+    /// Generate `try_*` methods only for some of trait's methods
+    restriction: Option<Restriction>,
+    /// Keep `FnMut` closure in a `RefCell` or `Mutex`, so that proxy's methods take `&self`
+    interior: Option<Interior>,
 }
 impl GenProxyParams {
     fn some_impl_requested(&self) -> bool {
         self.gen_infallible || self.gen_unwrapping || self.gen_unwrapping_and_panicking
     }
+
+    /// Access to the proxy that its methods require. Follows the closure, except for `interior` proxies.
+    fn receiver_level(&self) -> ReceiverStyle {
+        if self.interior.is_some() {
+            ReceiverStyle::Ref
+        } else {
+            self.level
+        }
+    }
 }
 
 struct CallFnParams {
@@ -82,6 +155,34 @@ struct CallFnParams {
     extra_arg: Option<proc_macro2::TokenStream>,
     name: Ident,
     r#async: bool,
+    dispatch: bool,
+    rw_dispatch: bool,
+    catch_unwind: bool,
+    poison_on_panic: bool,
+    /// Function called with method name and `Panicked` for caught panics in methods without return values
+    panic_hook: Option<proc_macro2::TokenStream>,
+    /// Type implementing `Interceptor`, called around each method call
+    interceptor: Option<Ident>,
+    /// Type implementing `Metrics`, notified about each handled message
+    metrics: Option<Ident>,
+    /// Apply the value to each of many targets, combining return values with an `Aggregate`
+    fan_out: bool,
+    /// Reply `CallError::NotAllowed` instead of calling methods outside of it
+    restriction: Option<Restriction>,
+}
+
+/// Wrapper of `FnMut` closure in `interior=` proxies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interior {
+    RefCell,
+    Mutex,
+}
+
+/// `only(...)` or `except(...)` subparameter, listing method names
+#[derive(Debug)]
+enum Restriction {
+    Only(Vec<Ident>),
+    Except(Vec<Ident>),
 }
 
 
@@ -101,6 +202,22 @@ struct Params {
     enum_attr: Vec<proc_macro2::Group>,
     enum_name: Ident,
     inherent_impl_mode : bool,
+    /// Add `Shutdown`, `Ping` and `Barrier` variants handled by call functions themselves
+    control_variants: bool,
+    /// Open tracing spans in call functions and proxies, carrying `TraceContext` in each method's variant
+    trace: bool,
+    /// Stamp each method's variant with `SentAt`, so that call functions can report queue time
+    sent_at: bool,
+    /// Name of serializable enum with just arguments of each method, for `trait_enumizer::journal`
+    journal: Option<Ident>,
+    /// Name of generated struct implementing the trait by collecting enum values
+    recorder: Option<Ident>,
+    /// Name of generated struct implementing the trait by checking calls against expectations
+    mock: Option<Ident>,
+    /// Also generate `<Name>Query` and `<Name>Command` enums with just `&self` or `&mut self` methods
+    split_enums: bool,
+    /// Send return values as `Result<T, CallError>`, so that call functions and proxies can report failures instead of them
+    call_errors: bool,
 }
 
 #[proc_macro_attribute]
@@ -126,58 +243,207 @@ pub fn enumizer(
         input_data
     };
     let params = &input_data.params;
-   
+
+    if params.control_variants {
+        if params.returnval.is_none() {
+            panic!("`control_variants` requires `returnval`");
+        }
+        for m in &input_data.methods {
+            let v = m.variant_name().to_string();
+            if v == "Shutdown" || v == "Ping" || v == "Barrier" {
+                panic!("Method `{}` clashes with a variant added by `control_variants`", m.name);
+            }
+        }
+    }
+    if params.trace {
+        if !cfg!(feature = "tracing") {
+            panic!("`trace` parameter requires `tracing` feature of trait-enumizer");
+        }
+        for m in &input_data.methods {
+            if m.args.iter().any(|a| a.name == "trace") {
+                panic!("In `trace` mode, method's arguments cannot be named literally `trace`. Rename it away in `{}`.", m.name);
+            }
+        }
+    } else if input_data.methods.iter().any(|m| m.args.iter().any(|a| a.traced)) {
+        panic!("`enumizer_trace` requires `trace` parameter");
+    }
+    if params.journal.is_some() {
+        if !cfg!(feature = "journal") {
+            panic!("`journal` parameter requires `journal` feature of trait-enumizer");
+        }
+        // Replay calls methods on `&mut I`, without a runtime
+        for m in &input_data.methods {
+            if m.needs_async() {
+                panic!("`journal` cannot replay async method `{}`", m.name);
+            }
+            if !ReceiverStyle::Mut.can_call(m.receiver_style) {
+                panic!("`journal` cannot replay method `{}`, as it consumes `self` or takes it behind a smart pointer", m.name);
+            }
+        }
+    }
+    if params.recorder.is_some() {
+        if params.inherent_impl_mode {
+            panic!("`recorder` is incompatible with `inherent_impl` mode");
+        }
+        for m in &input_data.methods {
+            if m.receiver_style == ReceiverStyle::Move {
+                panic!("`recorder` cannot implement method `{}` that takes `self`", m.name);
+            }
+            if m.stream.is_some() {
+                panic!("`recorder` does not support streamed return value of method `{}`", m.name);
+            }
+        }
+    }
+    if params.mock.is_some() {
+        if params.inherent_impl_mode {
+            panic!("`mock` is incompatible with `inherent_impl` mode");
+        }
+        for m in &input_data.methods {
+            if m.stream.is_some() {
+                panic!("`mock` does not support streamed return value of method `{}`", m.name);
+            }
+        }
+    }
+    if input_data.has_handles() {
+        if params.inherent_impl_mode {
+            panic!("`enumizer_handle` is incompatible with `inherent_impl` mode");
+        }
+        if params.journal.is_some() || params.recorder.is_some() || params.mock.is_some() {
+            panic!("`enumizer_handle` is incompatible with `journal`, `recorder` and `mock`");
+        }
+        for m in input_data.methods.iter().filter(|m| m.handle.is_some()) {
+            let v = m.handle_variant_name();
+            if input_data.methods.iter().any(|m2| m2.variant_name() == v) {
+                panic!("Method `{}` clashes with a variant added for `enumizer_handle` method `{}`", m.name, v);
+            }
+        }
+        for c in &params.call_fns {
+            if !matches!(c.level, ReceiverStyle::Move | ReceiverStyle::Mut | ReceiverStyle::Box) || c.fan_out || c.rw_dispatch {
+                panic!("`enumizer_handle` methods require `move`, `ref_mut` or `boxed` call functions without `fan_out` or `rw_dispatch`, as returned objects are registered in the callee");
+            }
+        }
+        for p in &params.proxies {
+            if p.multiplexed {
+                panic!("`enumizer_handle` is incompatible with `multiplexed`");
+            }
+            if p.some_impl_requested() && (p.level != ReceiverStyle::Ref || p.r#async || p.extra_arg.is_some() || p.object_id.is_some()) {
+                panic!("Trait impls for traits with `enumizer_handle` methods require non-async `Fn` proxy without `extra_field_type` or `object_id`, as sub-proxies share the closure");
+            }
+        }
+    }
+    if params.sent_at {
+        for m in &input_data.methods {
+            if m.args.iter().any(|a| a.name == "sent_at") {
+                panic!("In `sent_at` mode, method's arguments cannot be named literally `sent_at`. Rename it away in `{}`.", m.name);
+            }
+        }
+    }
+    if params.split_enums && input_data.has_handles() {
+        panic!("`split_enums` is incompatible with `enumizer_handle`");
+    }
+    if !params.call_errors {
+        for c in &params.call_fns {
+            let option = if c.catch_unwind {
+                "catch_unwind"
+            } else if c.interceptor.is_some() {
+                "interceptor"
+            } else if c.restriction.is_some() {
+                "only` or `except"
+            } else {
+                continue;
+            };
+            panic!("`{}` of call function `{}` requires `call_errors` parameter, as it replies with `trait_enumizer::CallError`", option, c.name);
+        }
+        for p in &params.proxies {
+            let option = if p.interceptor.is_some() {
+                "interceptor"
+            } else if p.object_id.is_some() {
+                "object_id"
+            } else {
+                continue;
+            };
+            panic!("`{}` of proxy `{}` requires `call_errors` parameter, as it leads to replies with `trait_enumizer::CallError`", option, p.name);
+        }
+    }
     //dbg!(thetrait);
     input_data.generate_enum(&mut ret);
+    if params.split_enums {
+        input_data.generate_split_enum(&mut ret, ReceiverStyle::Ref);
+        input_data.generate_split_enum(&mut ret, ReceiverStyle::Mut);
+    }
+    if input_data.has_method_info() {
+        input_data.generate_method_info_fn(&mut ret);
+    }
+    if params.sent_at {
+        input_data.generate_sent_at_fn(&mut ret);
+    }
+    if let Some(journal) = &params.journal {
+        input_data.generate_journal(&mut ret, journal);
+    }
+    if let Some(recorder) = &params.recorder {
+        input_data.generate_recorder(&mut ret, recorder);
+    }
+    if let Some(mock) = &params.mock {
+        input_data.generate_mock(&mut ret, mock);
+    }
+    if input_data.has_priorities() {
+        input_data.generate_priority_fn(&mut ret);
+    }
+    // Coalescing has no effect without the `Message` impl, so emit it even if actor dispatch is implemented by hand
+    if params.call_fns.iter().any(|c| c.dispatch || c.rw_dispatch) || input_data.methods.iter().any(|m| m.coalesce.is_some()) {
+        input_data.generate_message_impl(&mut ret);
+    }
+    if params.proxies.iter().any(|p| p.object_id.is_some()) {
+        input_data.generate_refuse_impl(&mut ret);
+    }
 
-    let caller_inconv = input_data.receiver_style_that_is_the_most_inconvenient_for_caller();
+    for restriction in params.call_fns.iter().filter_map(|c| c.restriction.as_ref()).chain(params.proxies.iter().filter_map(|p| p.restriction.as_ref())) {
+        for name in restriction.names() {
+            if !input_data.methods.iter().any(|m| m.name == *name) {
+                panic!("`only(...)` or `except(...)` mentions `{}`, which is not a method", name);
+            }
+        }
+    }
 
     for g in &params.call_fns {
-        match g.level {
-            ReceiverStyle::Move => (),
-            ReceiverStyle::Mut => {
-                if caller_inconv == ReceiverStyle::Move && !g.allow_panic {
-                    panic!("Cannot generate `call_fn(ref_mut)` function because of trait have `self` methods. Use `call_fn(... ,allow_panic)` to override.");
-                }
-            }
-            ReceiverStyle::Ref => {
-                if caller_inconv != ReceiverStyle::Ref && !g.allow_panic {
-                    panic!("Cannot generate `call_fn(ref)` function because of trait have non-`&self` methods. Use `call_fn(... ,allow_panic)` to override.");
-                }
+        if let (Some(m), false) = (input_data.method_uncallable_from(g.level, g.restriction.as_ref()), g.allow_panic) {
+            match g.level {
+                ReceiverStyle::Mut => panic!("Cannot generate `call_fn(ref_mut)` function because of trait have `self` methods or typed ones like `self: Box<Self>`. Use `call_fn(... ,allow_panic)` to override."),
+                ReceiverStyle::Ref => panic!("Cannot generate `call_fn(ref)` function because of trait have non-`&self` methods. Use `call_fn(... ,allow_panic)` to override."),
+                _ => panic!("Cannot generate `{}` call function because `self` of method `{}` cannot be obtained from its `o`. Use `call_fn(... ,allow_panic)` to override.", g.name, m.name),
             }
         }
         input_data.generate_call_fn(&mut ret, g);
     }
 
-    let callee_inconv = input_data.receiver_style_that_is_the_most_inconvenient_for_callee();
-
     for g in &params.proxies {
         if params.inherent_impl_mode && g.some_impl_requested() {
             panic!("Generating trait impls is incompatible with inherent_impl mode");
         }
 
-        if g.gen_infallible {
-            if params.returnval.is_some() {
-                panic!("infallible_impl and returnval are incompatible");
-            }
+        if g.restriction.is_some() && (g.some_impl_requested() || g.traitname.is_some()) {
+            panic!("Proxies with `only(...)` or `except(...)` cannot implement the trait or `resultified_trait`, as some methods are missing");
         }
-        match g.level {
-            ReceiverStyle::Move => {
-                if g.gen_infallible || g.gen_unwrapping {
-                    if callee_inconv != ReceiverStyle::Move {
-                        panic!("The trait contains `&self` or `&mut self` methods. The FnOnce proxy cannot implement it - only for traits with solely `self` methods. Use `unwrapping_and_panicking_impl` to force generation and retain only some methods");
-                    }
-                }
-            }
-            ReceiverStyle::Mut => {
-                if g.gen_infallible || g.gen_unwrapping {
-                    if callee_inconv == ReceiverStyle::Ref {
-                        panic!("The trait contains &self methods. The FnMut proxy cannot implement it. Use `unwrapping_and_panicking_impl` to force generation and retain only some methods");
-                    }
-                }
+        if g.callbacks && params.returnval.is_none() {
+            panic!("`callbacks` proxy subparameter requires `returnval`");
+        }
+        if let (true, Some(class)) = (g.callbacks, &params.returnval) {
+            const WITHOUT_CALLBACK: &[&str] = &["flume_class", "crossbeam_class", "tokio_oneshot_class", "catty_class", "futures_oneshot_class", "stdmpsc_class", "pooled_class"];
+            if WITHOUT_CALLBACK.iter().any(|c| class == c) {
+                panic!("`callbacks` proxy subparameter requires `callback` pseudomethod in the channel class, which `{}` does not have. Use `callback_class` or a custom channel class.", class);
             }
-            ReceiverStyle::Ref => {
-               
+        }
+        if g.deferred && params.returnval.is_none() {
+            panic!("`deferred` proxy subparameter requires `returnval`");
+        }
+        if g.gen_infallible && params.returnval.is_some() {
+            panic!("infallible_impl and returnval are incompatible");
+        }
+        if let (Some(m), true) = (input_data.method_unimplementable_by(g.receiver_level()), g.gen_infallible || g.gen_unwrapping) {
+            match g.receiver_level() {
+                ReceiverStyle::Move => panic!("The trait contains `{}` method that takes neither `self` nor `self: Box<Self>`. The FnOnce proxy cannot implement it - only for traits with solely such methods. Use `unwrapping_and_panicking_impl` to force generation and retain only some methods", m.name),
+                ReceiverStyle::Mut => panic!("The trait contains `{}` method that takes `&self`, `self: Arc<Self>` or `self: Rc<Self>`. The FnMut proxy cannot implement it. Use `interior=RefCell` or `interior=Mutex` to make its methods take `&self`, or `unwrapping_and_panicking_impl` to force generation and retain only some methods", m.name),
+                _ => unreachable!(),
             }
         }
 
@@ -191,6 +457,15 @@ pub fn enumizer(
         if g.gen_unwrapping || g.gen_unwrapping_and_panicking {
             input_data.generate_unwrapping_impl(&mut ret, g);
         }
+        if g.multiplexed {
+            if g.level != ReceiverStyle::Ref || g.extra_arg.is_some() || g.object_id.is_some() {
+                panic!("`multiplexed` requires `Fn` proxy without `extra_field_type` or `object_id`");
+            }
+            if !g.some_impl_requested() && g.traitname.is_none() {
+                panic!("`multiplexed` requires `resultified_trait` or some trait impl of the proxy");
+            }
+            input_data.generate_mux_impls(&mut ret, g);
+        }
     }
 
     ret.into()
@@ -4,6 +4,7 @@ use proc_macro2::TokenTree;
 use crate::AccessMode;
 use crate::CallFnParams;
 use crate::ReceiverStyle;
+use crate::Restriction;
 
 use super::GenProxyParams;
 
@@ -19,6 +20,9 @@ enum ParserState<I, G> {
 enum RootLevelIdentAssignmentTargets {
     Returnval,
     Name,
+    Journal,
+    Recorder,
+    Mock,
 }
 #[derive(Debug, Clone, Copy)]
 enum RootLevelGroupAssignmentTargets {
@@ -35,6 +39,14 @@ pub(crate) fn parse_args(input: TokenStream) -> Params {
     let mut enum_attr = vec![];
     let mut enum_name = None;
     let mut inherent_impl_mode = false;
+    let mut control_variants = false;
+    let mut trace = false;
+    let mut sent_at = false;
+    let mut journal = None;
+    let mut recorder = None;
+    let mut mock = None;
+    let mut split_enums = false;
+    let mut call_errors = false;
 
     let mut state = ParserState::<RootLevelIdentAssignmentTargets,RootLevelGroupAssignmentTargets>::ExpectingNewParam;
 
@@ -55,6 +67,14 @@ pub(crate) fn parse_args(input: TokenStream) -> Params {
                     "enum_attr" => state = ExpectingGroup(CustomAttr),
                     "name" => state = ExpectingEqsign(Name),
                     "inherent_impl" => inherent_impl_mode = true,
+                    "control_variants" => control_variants = true,
+                    "trace" => trace = true,
+                    "sent_at" => sent_at = true,
+                    "split_enums" => split_enums = true,
+                    "call_errors" => call_errors = true,
+                    "journal" => state = ExpectingEqsign(Journal),
+                    "recorder" => state = ExpectingEqsign(Recorder),
+                    "mock" => state = ExpectingEqsign(Mock),
                     z => panic!("Unknown parameter {}", z),
                 },
                 TokenTree::Group(_) => panic!("No group is expected here"),
@@ -67,6 +87,9 @@ pub(crate) fn parse_args(input: TokenStream) -> Params {
                     TokenTree::Ident(y) => match t {
                         Returnval => returnval = Some(y),
                         Name => enum_name = Some(y),
+                        Journal => journal = Some(y),
+                        Recorder => recorder = Some(y),
+                        Mock => mock = Some(y),
                     },
                     _ => panic!(
                         "Single identifier is expected in {:?} state after `=` sign",
@@ -103,16 +126,29 @@ pub(crate) fn parse_args(input: TokenStream) -> Params {
         enum_attr,
         enum_name,
         inherent_impl_mode,
+        control_variants,
+        trace,
+        sent_at,
+        journal,
+        recorder,
+        mock,
+        split_enums,
+        call_errors,
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 enum CallFnIdentAssignmentTargets {
     Name,
+    Interceptor,
+    Metrics,
 }
 #[derive(Debug, Clone, Copy)]
 enum CallFnGroupAssignmentTargets {
     ExtraArgType,
+    PanicHook,
+    Only,
+    Except,
 }
 
 fn parse_call_fn(input: TokenStream) -> CallFnParams {
@@ -121,6 +157,15 @@ fn parse_call_fn(input: TokenStream) -> CallFnParams {
     let mut extra_arg = None;
     let mut name = None;
     let mut r#async = false;
+    let mut dispatch = false;
+    let mut rw_dispatch = false;
+    let mut catch_unwind = false;
+    let mut poison_on_panic = false;
+    let mut panic_hook = None;
+    let mut interceptor = None;
+    let mut metrics = None;
+    let mut fan_out = false;
+    let mut restriction = None;
 
     let mut state = ParserState::<CallFnIdentAssignmentTargets,CallFnGroupAssignmentTargets>::ExpectingNewParam;
 
@@ -136,10 +181,24 @@ fn parse_call_fn(input: TokenStream) -> CallFnParams {
                     "deny_panic" => allow_panic = false,
                     "async" => r#async = true,
                     "no_async" => r#async = false,
+                    "dispatch" => dispatch = true,
+                    "rw_dispatch" => rw_dispatch = true,
+                    "catch_unwind" => catch_unwind = true,
+                    "poison_on_panic" => poison_on_panic = true,
+                    "fan_out" => fan_out = true,
+                    "panic_hook" => state = ExpectingGroup(PanicHook),
+                    "interceptor" => state = ExpectingEqsign(Interceptor),
+                    "metrics" => state = ExpectingEqsign(Metrics),
                     "extra_arg_type" => state = ExpectingGroup(ExtraArgType),
+                    "only" => state = ExpectingGroup(Only),
+                    "except" => state = ExpectingGroup(Except),
                     "ref" => level = Some(ReceiverStyle::Ref),
                     "ref_mut" | "mut_ref" => level = Some(ReceiverStyle::Mut),
                     "once" | "move" => level = Some(ReceiverStyle::Move),
+                    "boxed" => level = Some(ReceiverStyle::Box),
+                    "arc" => level = Some(ReceiverStyle::Arc),
+                    "rc" => level = Some(ReceiverStyle::Rc),
+                    "pin_mut" => level = Some(ReceiverStyle::PinMut),
                     "Fn" | "FnMut" | "FnOnce" => {
                         panic!("Use ref/ref_mut/once for call_fn, not Fn*")
                     }
@@ -153,6 +212,8 @@ fn parse_call_fn(input: TokenStream) -> CallFnParams {
                 match x {
                     TokenTree::Ident(y) => match t {
                         Name => name = Some(y),
+                        Interceptor => interceptor = Some(y),
+                        Metrics => metrics = Some(y),
                     },
                     _ => panic!(
                         "Single identifier is expected in {:?} state after `=` sign",
@@ -169,6 +230,9 @@ fn parse_call_fn(input: TokenStream) -> CallFnParams {
                 match x {
                     TokenTree::Group(y) => match t {
                         ExtraArgType => extra_arg = Some(y.stream()),
+                        PanicHook => panic_hook = Some(y.stream()),
+                        Only => set_restriction(&mut restriction, Restriction::Only(parse_method_list(y.stream()))),
+                        Except => set_restriction(&mut restriction, Restriction::Except(parse_method_list(y.stream()))),
                     },
                     _ => panic!("Expected a group after parameter for {:?}", t),
                 }
@@ -177,8 +241,20 @@ fn parse_call_fn(input: TokenStream) -> CallFnParams {
         }
     }
 
-    let level = level.expect("Set one of `ref`, `ref_mut` or `once` subparameters");
+    let level = level.expect("Set one of `ref`, `ref_mut`, `once`, `boxed`, `arc`, `rc` or `pin_mut` subparameters");
     let name = name.expect("`name` subparameter is required.");
+    if (poison_on_panic || panic_hook.is_some()) && !catch_unwind {
+        panic!("`poison_on_panic` and `panic_hook` require `catch_unwind` subparameter");
+    }
+    if poison_on_panic && !matches!(level, ReceiverStyle::Mut | ReceiverStyle::Ref) {
+        panic!("`poison_on_panic` requires `ref` or `ref_mut` call function, as it takes `&Poisonable<I>` or `&mut Poisonable<I>`");
+    }
+    if fan_out && (r#async || dispatch || rw_dispatch || catch_unwind || interceptor.is_some() || metrics.is_some() || restriction.is_some()) {
+        panic!("`fan_out` is incompatible with `async`, `dispatch`, `rw_dispatch`, `catch_unwind`, `interceptor`, `metrics`, `only` and `except` subparameters");
+    }
+    if fan_out && !matches!(level, ReceiverStyle::Move | ReceiverStyle::Mut | ReceiverStyle::Ref) {
+        panic!("`fan_out` requires `ref`, `ref_mut` or `once` call function");
+    }
 
     CallFnParams {
         level,
@@ -186,17 +262,55 @@ fn parse_call_fn(input: TokenStream) -> CallFnParams {
         allow_panic,
         extra_arg,
         r#async,
+        dispatch,
+        rw_dispatch,
+        catch_unwind,
+        poison_on_panic,
+        panic_hook,
+        interceptor,
+        metrics,
+        fan_out,
+        restriction,
+    }
+}
+
+/// Comma-separated method names inside `only(...)` or `except(...)`
+fn parse_method_list(input: TokenStream) -> Vec<proc_macro2::Ident> {
+    let mut names = vec![];
+    for x in input {
+        match x {
+            TokenTree::Ident(y) => names.push(y),
+            TokenTree::Punct(y) if y.as_char() == ',' => (),
+            _ => panic!("Expected method names in `only(...)` or `except(...)`, got {:?}", x),
+        }
+    }
+    if names.is_empty() {
+        panic!("`only(...)` and `except(...)` require at least one method name");
+    }
+    names
+}
+
+fn set_restriction(restriction: &mut Option<Restriction>, value: Restriction) {
+    if restriction.is_some() {
+        panic!("Use at most one of `only(...)` and `except(...)`");
     }
+    *restriction = Some(value);
 }
 
 #[derive(Debug, Clone, Copy)]
 enum ProxyIdentAssignmentTargets {
     Name,
     TraitName,
+    Interceptor,
+    Metrics,
+    Interior,
 }
 #[derive(Debug, Clone, Copy)]
 enum ProxyGroupAssignmentTargets {
     ExtraFieldType,
+    ObjectId,
+    Only,
+    Except,
 }
 
 fn parse_proxy(input: TokenStream) -> GenProxyParams {
@@ -208,6 +322,14 @@ fn parse_proxy(input: TokenStream) -> GenProxyParams {
     let mut traitname = None;
     let mut level = None;
     let mut r#async = false;
+    let mut callbacks = false;
+    let mut deferred = false;
+    let mut multiplexed = false;
+    let mut object_id = None;
+    let mut interceptor = None;
+    let mut metrics = None;
+    let mut restriction = None;
+    let mut interior = None;
 
     let mut state =
         ParserState::<ProxyIdentAssignmentTargets, ProxyGroupAssignmentTargets>::ExpectingNewParam;
@@ -230,10 +352,19 @@ fn parse_proxy(input: TokenStream) -> GenProxyParams {
                         "Maybe you meant Fn/FnMut/FnOnce for proxy subparam, not ref/once/move/mut"
                     ),
                     "extra_field_type" => state = ExpectingGroup(ExtraFieldType),
+                    "object_id" => state = ExpectingGroup(ObjectId),
+                    "only" => state = ExpectingGroup(Only),
+                    "except" => state = ExpectingGroup(Except),
                     "name" => state = ExpectingEqsign(Name),
                     "resultified_trait" => state = ExpectingEqsign(TraitName),
                     "async" => r#async = true,
                     "no_async" => r#async = false,
+                    "callbacks" => callbacks = true,
+                    "deferred" => deferred = true,
+                    "multiplexed" => multiplexed = true,
+                    "interceptor" => state = ExpectingEqsign(Interceptor),
+                    "metrics" => state = ExpectingEqsign(Metrics),
+                    "interior" => state = ExpectingEqsign(Interior),
                     z => panic!("Unknown subparameter {}", z),
                 },
                 TokenTree::Punct(y) if y.as_char() == ',' => (),
@@ -244,6 +375,15 @@ fn parse_proxy(input: TokenStream) -> GenProxyParams {
                     TokenTree::Ident(y) => match t {
                         Name => name = Some(y),
                         TraitName => traitname = Some(y),
+                        Interceptor => interceptor = Some(y),
+                        Metrics => metrics = Some(y),
+                        Interior => {
+                            interior = Some(match y.to_string().as_str() {
+                                "RefCell" => crate::Interior::RefCell,
+                                "Mutex" => crate::Interior::Mutex,
+                                z => panic!("Unknown `interior` wrapper {}. Use `RefCell` or `Mutex`.", z),
+                            })
+                        }
                     },
                     _ => panic!(
                         "Single identifier is expected in {:?} state after `=` sign",
@@ -260,6 +400,9 @@ fn parse_proxy(input: TokenStream) -> GenProxyParams {
                 match x {
                     TokenTree::Group(y) => match t {
                         ExtraFieldType => extra_arg = Some(y.stream()),
+                        ObjectId => object_id = Some(y.stream()),
+                        Only => set_restriction(&mut restriction, Restriction::Only(parse_method_list(y.stream()))),
+                        Except => set_restriction(&mut restriction, Restriction::Except(parse_method_list(y.stream()))),
                     },
                     _ => panic!("Expected a group after parameter for {:?}", t),
                 }
@@ -281,12 +424,12 @@ fn parse_proxy(input: TokenStream) -> GenProxyParams {
     if ctr > 1 {
         panic!("Choose only one of infallible or unwrapping impl")
     }
-    if r#async && ctr > 0 {
-        panic!("async is incompatible with any impls");
-    }
 
     let name = name.expect("`name` subparameter is required.");
     let level = level.expect("Set one of `Fn`, `FnMut` or `FnOnce` subparameters");
+    if interior.is_some() && level != ReceiverStyle::Mut {
+        panic!("`interior` requires `FnMut` proxy");
+    }
 
     GenProxyParams {
         level,
@@ -297,6 +440,14 @@ fn parse_proxy(input: TokenStream) -> GenProxyParams {
         name,
         traitname,
         r#async,
+        callbacks,
+        deferred,
+        interceptor,
+        metrics,
+        multiplexed,
+        object_id,
+        restriction,
+        interior,
     }
 }
 
@@ -308,6 +459,14 @@ fn test_parser1() {
     assert!(attrs.proxies.is_empty());
     assert!(attrs.enum_attr.is_empty());
     assert!(attrs.returnval.is_none());
+    assert!(!attrs.control_variants);
+    assert!(!attrs.trace);
+    assert!(!attrs.sent_at);
+    assert!(attrs.journal.is_none());
+    assert!(attrs.recorder.is_none());
+    assert!(attrs.mock.is_none());
+    assert!(!attrs.split_enums);
+    assert!(!attrs.call_errors);
 }
 
 #[test]
@@ -315,12 +474,23 @@ fn test_parser2() {
     let attrs = parse_args(quote::quote! {
         returnval=my_rpc_class,
         name=MyEnum,
+        control_variants,
+        trace,
+        sent_at,
+        journal=MyCall,
+        recorder=MyRecorder,
+        mock=MyMock,
+        split_enums,
+        call_errors,
         call_fn(ref,name=call,extra_arg_type(i32)),
-        call_fn(ref_mut,name=call_mut,extra_arg_type(&flume::Sender<String>)),
-        call_fn(once,name=call_once,allow_panic),
-        proxy(Fn,name=MyProxy,unwrapping_impl,extra_field_type(MyRpcClient)),
-        proxy(FnMut,name=MyMutProxy,infallible_impl),
-        proxy(FnOnce,name=MyOnceProxy,resultified_trait=Qqq,unwrapping_and_panicking_impl),
+        call_fn(ref_mut,name=call_mut,extra_arg_type(&flume::Sender<String>),fan_out),
+        call_fn(once,name=call_once,allow_panic,catch_unwind,panic_hook(log_panic),interceptor=Auth,metrics=Stats),
+        call_fn(ref,name=call_some,except(delete)),
+        call_fn(arc,name=call_arc,dispatch),
+        proxy(Fn,name=MyProxy,unwrapping_impl,extra_field_type(MyRpcClient),callbacks,interceptor=Log,metrics=Stats),
+        proxy(FnMut,name=MyMutProxy,infallible_impl,multiplexed,interior=Mutex),
+        proxy(FnOnce,name=MyOnceProxy,resultified_trait=Qqq,unwrapping_and_panicking_impl,object_id(u64)),
+        proxy(Fn,name=MyReadProxy,only(get, list)),
         enum_attr[derive(serde_derive::Serialize,serde_derive::Deserialize)],
         enum_attr[222]
     });
@@ -333,13 +503,42 @@ fn test_parser2() {
     assert!(attrs.call_fns[1].extra_arg.is_some());
     assert!(attrs.call_fns[2].extra_arg.is_none());
 
+    assert!(!attrs.call_fns[0].catch_unwind);
+    assert!(attrs.call_fns[2].catch_unwind);
+    assert!(!attrs.call_fns[2].poison_on_panic);
+    assert!(attrs.call_fns[2].panic_hook.is_some());
+    assert!(attrs.call_fns[1].interceptor.is_none());
+    assert_eq!(attrs.call_fns[2].interceptor.as_ref().unwrap().to_string(), "Auth");
+    assert!(attrs.call_fns[0].metrics.is_none());
+    assert_eq!(attrs.call_fns[2].metrics.as_ref().unwrap().to_string(), "Stats");
+    assert!(!attrs.call_fns[0].fan_out);
+    assert!(attrs.call_fns[1].fan_out);
+    assert!(attrs.call_fns[0].restriction.is_none());
+    assert!(matches!(&attrs.call_fns[3].restriction, Some(Restriction::Except(x)) if x.len() == 1 && x[0] == "delete"));
+
     assert_eq!(attrs.call_fns[0].level, ReceiverStyle::Ref);
     assert_eq!(attrs.call_fns[1].level, ReceiverStyle::Mut);
     assert_eq!(attrs.call_fns[2].level, ReceiverStyle::Move);
+    assert_eq!(attrs.call_fns[4].level, ReceiverStyle::Arc);
+    assert!(attrs.call_fns[4].dispatch);
 
     assert_eq!(attrs.proxies[0].gen_unwrapping, true);
     assert_eq!(attrs.proxies[0].gen_infallible, false);
     assert_eq!(attrs.proxies[0].gen_unwrapping_and_panicking, false);
+    assert!(attrs.proxies[0].callbacks);
+    assert!(!attrs.proxies[1].callbacks);
+    assert_eq!(attrs.proxies[0].interceptor.as_ref().unwrap().to_string(), "Log");
+    assert!(attrs.proxies[1].interceptor.is_none());
+    assert_eq!(attrs.proxies[0].metrics.as_ref().unwrap().to_string(), "Stats");
+    assert!(attrs.proxies[1].metrics.is_none());
+    assert!(!attrs.proxies[0].multiplexed);
+    assert!(attrs.proxies[1].multiplexed);
+    assert!(attrs.proxies[1].object_id.is_none());
+    assert!(attrs.proxies[2].object_id.is_some());
+    assert!(attrs.proxies[2].restriction.is_none());
+    assert!(attrs.proxies[0].interior.is_none());
+    assert_eq!(attrs.proxies[1].interior, Some(crate::Interior::Mutex));
+    assert!(matches!(&attrs.proxies[3].restriction, Some(Restriction::Only(x)) if x.len() == 2 && x[1] == "list"));
 
     assert_eq!(attrs.proxies[1].gen_unwrapping, false);
     assert_eq!(attrs.proxies[1].gen_infallible, true);
@@ -355,4 +554,12 @@ fn test_parser2() {
 
     assert_eq!(attrs.enum_attr.len(), 2);
     assert_eq!(attrs.returnval.unwrap().to_string(), "my_rpc_class");
+    assert!(attrs.control_variants);
+    assert!(attrs.trace);
+    assert!(attrs.sent_at);
+    assert_eq!(attrs.journal.unwrap().to_string(), "MyCall");
+    assert_eq!(attrs.recorder.unwrap().to_string(), "MyRecorder");
+    assert_eq!(attrs.mock.unwrap().to_string(), "MyMock");
+    assert!(attrs.split_enums);
+    assert!(attrs.call_errors);
 }
@@ -1,6 +1,6 @@
 use proc_macro2::TokenTree;
 
-use crate::{Argument, Method, Params};
+use crate::{Argument, Handle, Method, Params, StreamKind};
 
 use super::{InputData, ReceiverStyle};
 impl InputData {
@@ -123,10 +123,88 @@ fn parse_method(
     if method_signature.variadic.is_some() {
         panic!("Trait-enumizer does not support variadics")
     }
-    if !returnval_mode && !matches!(method_signature.output, syn::ReturnType::Default) {
-        panic!("Specify `returnval` parameter to handle methods with return types.")
-    }
+    let mut wait = None;
+    let mut stream_attr = false;
+    let mut priority = None;
+    let mut coalesce = None;
+    let mut handle = None;
+    let mut mock_eq = false;
     attrs.retain(|a| match a.path.get_ident() {
+        Some(x) if x == "enumizer_handle" => {
+            if handle.is_some() {
+                panic!("Use at most one `enumizer_handle` per method");
+            }
+            let usage = "`enumizer_handle` should be used like `#[enumizer_handle(QqqEnum, QqqProxy)]`";
+            handle = Some(match a.tokens.clone().into_iter().next() {
+                Some(TokenTree::Group(g)) => {
+                    let t: Vec<TokenTree> = g.stream().into_iter().collect();
+                    match &t[..] {
+                        [TokenTree::Ident(e), TokenTree::Punct(comma), TokenTree::Ident(p)] if comma.as_char() == ',' => (e.clone(), p.clone()),
+                        _ => panic!("{}", usage),
+                    }
+                }
+                _ => panic!("{}", usage),
+            });
+            false
+        }
+        Some(x) if x == "enumizer_coalesce" => {
+            if coalesce.is_some() {
+                panic!("Use at most one `enumizer_coalesce` per method");
+            }
+            coalesce = Some(match a.tokens.clone().into_iter().next() {
+                None => None,
+                Some(TokenTree::Group(g)) => {
+                    let t: Vec<TokenTree> = g.stream().into_iter().collect();
+                    match &t[..] {
+                        [TokenTree::Ident(k), TokenTree::Punct(eq), TokenTree::Ident(arg)] if k == "key" && eq.as_char() == '=' => Some(arg.clone()),
+                        _ => panic!("`enumizer_coalesce` should be used like `#[enumizer_coalesce]` or `#[enumizer_coalesce(key = argument_name)]`"),
+                    }
+                }
+                _ => panic!("`enumizer_coalesce` should be used like `#[enumizer_coalesce]` or `#[enumizer_coalesce(key = argument_name)]`"),
+            });
+            false
+        }
+        Some(x) if x == "enumizer_priority" => {
+            let level = match a.tokens.clone().into_iter().next() {
+                Some(TokenTree::Group(g)) => g.stream().to_string(),
+                _ => panic!("`enumizer_priority` should be used like `#[enumizer_priority(high)]`"),
+            };
+            let level = match &level[..] {
+                "low" => "Low",
+                "normal" => "Normal",
+                "high" => "High",
+                _ => panic!("Unknown priority `{}`. Use `low`, `normal` or `high`.", level),
+            };
+            if priority.is_some() {
+                panic!("Use at most one `enumizer_priority` per method");
+            }
+            priority = Some(quote::format_ident!("{}", level));
+            false
+        }
+        Some(x) if x == "enumizer_mock_eq" => {
+            if !a.tokens.is_empty() {
+                panic!("`enumizer_mock_eq` does not accept any additional arguments");
+            }
+            mock_eq = true;
+            false
+        }
+        Some(x) if x == "enumizer_stream" => {
+            if !a.tokens.is_empty() {
+                panic!("`enumizer_stream` does not accept any additional arguments");
+            }
+            stream_attr = true;
+            false
+        }
+        Some(x) if x == "enumizer_wait" || x == "enumizer_no_wait" => {
+            if !a.tokens.is_empty() {
+                panic!("`enumizer_wait` and `enumizer_no_wait` do not accept any additional arguments");
+            }
+            if wait.is_some() {
+                panic!("Use at most one of `enumizer_wait` or `enumizer_no_wait` per method");
+            }
+            wait = Some(x == "enumizer_wait");
+            false
+        }
         Some(x) if x == "enumizer_enum_attr" || x == "enumizer_return_attr" => {
             let g = match a.tokens.clone().into_iter().next() {
                 Some(TokenTree::Group(g)) => g,
@@ -145,10 +223,23 @@ fn parse_method(
     });
     let mut args = Vec::with_capacity(method_signature.inputs.len());
     let mut receiver_style = None;
-    let ret = match &method_signature.output {
-        syn::ReturnType::Default => None,
-        syn::ReturnType::Type(_, t) => Some(*t.clone()),
+    let ret = match (&method_signature.output, wait) {
+        (syn::ReturnType::Default, Some(true)) => Some(syn::parse_quote! {()}),
+        (syn::ReturnType::Default, _) => None,
+        (syn::ReturnType::Type(_, t), Some(false)) => match &**t {
+            syn::Type::Tuple(tt) if tt.elems.is_empty() => None,
+            _ => panic!("`enumizer_no_wait` can only be used on methods returning nothing or `()`"),
+        },
+        (syn::ReturnType::Type(_, t), _) => Some(*t.clone()),
     };
+    let stream = match &ret {
+        Some(t) => stream_item(t, stream_attr),
+        None if stream_attr => panic!("`enumizer_stream` requires a return type"),
+        None => None,
+    };
+    if !returnval_mode && ret.is_some() {
+        panic!("Specify `returnval` parameter to handle methods with return types (or `enumizer_wait` methods).")
+    }
     for input_args in &mut method_signature.inputs {
         match input_args {
             syn::FnArg::Receiver(r) => {
@@ -165,9 +256,13 @@ fn parse_method(
                     Some(ReceiverStyle::Move)
                 }
             }
+            syn::FnArg::Typed(arg) if matches!(&*arg.pat, syn::Pat::Ident(pi) if pi.ident == "self") => {
+                receiver_style = Some(typed_receiver_style(&arg.ty));
+            }
             syn::FnArg::Typed(arg) => {
                 let mut enum_attr = vec![];
                 let mut to_owned = false;
+                let mut traced = false;
                 arg.attrs.retain(|a| match a.path.get_ident() {
                     Some(x) if x == "enumizer_enum_attr" => {
                         match a.tokens.clone().into_iter().next() {
@@ -187,6 +282,13 @@ fn parse_method(
                         to_owned = true;
                         false
                     }
+                    Some(x) if x == "enumizer_trace" => {
+                        if !a.tokens.is_empty() {
+                            panic!("`enumizer_trace` does not accept any additional arguments");
+                        }
+                        traced = true;
+                        false
+                    }
                     _ => true,
                 });
                 match &*arg.pat {
@@ -199,7 +301,7 @@ fn parse_method(
                                 panic!("In `returnval` mode, method's arguments cannot be named literally `ret`. Rename it away in `{}`.", method_signature.ident);
                             }
                         }
-                        args.push(Argument { name: pi.ident.clone(), ty: *arg.ty.clone(), enum_attr, to_owned });
+                        args.push(Argument { name: pi.ident.clone(), ty: *arg.ty.clone(), enum_attr, to_owned, traced });
                     }
                     _ => panic!("Trait-enumizer does not support method arguments that are patterns, not just simple identifiers"),
                 }
@@ -209,14 +311,119 @@ fn parse_method(
     if receiver_style.is_none() {
         panic!("Trait-enumizer does not support methods that do not accept `self`")
     }
+    if let Some(coalesce) = &coalesce {
+        if ret.is_some() {
+            panic!("`enumizer_coalesce` cannot be used on methods with return values, as callers of replaced messages would never get a reply");
+        }
+        if let Some(key) = coalesce {
+            if !args.iter().any(|a| a.name == *key) {
+                panic!("`enumizer_coalesce` key `{}` is not an argument of `{}`", key, method_signature.ident);
+            }
+        }
+    }
+    let mut ret = ret;
+    let handle = handle.map(|(sub_enum, sub_proxy)| {
+        if !returnval_mode {
+            panic!("`enumizer_handle` requires `returnval`");
+        }
+        if stream.is_some() || r#async {
+            panic!("`enumizer_handle` does not support async methods or streamed return values");
+        }
+        if !matches!(receiver_style, Some(ReceiverStyle::Ref | ReceiverStyle::Mut)) {
+            panic!("`enumizer_handle` method `{}` should take `&self` or `&mut self`", method_signature.ident);
+        }
+        let Some(rt) = ret.take() else {
+            panic!("`enumizer_handle` requires method `{}` to return the object, like `Box<dyn Qqq>`", method_signature.ident);
+        };
+        let runtime = crate::generate::runtime_crate();
+        ret = Some(syn::parse_quote! {#runtime::registry::HandleId});
+        Handle { ret: rt, sub_enum, sub_proxy }
+    });
     let method = Method {
         args,
         name: method_signature.ident.clone(),
         receiver_style: receiver_style.unwrap(),
         ret,
+        stream,
         enum_attr,
         return_attr,
         r#async,
+        priority,
+        coalesce,
+        handle,
+        mock_eq,
     };
     methods.push(method);
 }
+
+/// Detect streamed return types: `impl Iterator<Item=T>`, `impl Stream<Item=T>` or `#[enumizer_stream]`-marked `Vec<T>`-like types.
+fn stream_item(ty: &syn::Type, stream_attr: bool) -> Option<(StreamKind, syn::Type)> {
+    match ty {
+        syn::Type::ImplTrait(it) => {
+            for bound in &it.bounds {
+                let tb = match bound {
+                    syn::TypeParamBound::Trait(tb) => tb,
+                    _ => continue,
+                };
+                let last = tb.path.segments.last().unwrap();
+                let kind = if last.ident == "Iterator" {
+                    StreamKind::Iterator
+                } else if last.ident == "Stream" {
+                    StreamKind::Stream
+                } else {
+                    continue;
+                };
+                if let syn::PathArguments::AngleBracketed(ab) = &last.arguments {
+                    for arg in &ab.args {
+                        if let syn::GenericArgument::Binding(b) = arg {
+                            if b.ident == "Item" {
+                                return Some((kind, b.ty.clone()));
+                            }
+                        }
+                    }
+                }
+                panic!("Cannot find `Item=` in returned `impl Iterator` or `impl Stream`");
+            }
+            panic!("Trait-enumizer supports only `impl Iterator<Item=...>` or `impl Stream<Item=...>` as `impl` return types")
+        }
+        syn::Type::Path(p) if stream_attr => {
+            let last = p.path.segments.last().unwrap();
+            if let syn::PathArguments::AngleBracketed(ab) = &last.arguments {
+                if let (1, Some(syn::GenericArgument::Type(t))) = (ab.args.len(), ab.args.first()) {
+                    return Some((StreamKind::Collection, t.clone()));
+                }
+            }
+            panic!("`enumizer_stream` requires return type like `Vec<T>` with a single type parameter being the item type")
+        }
+        _ if stream_attr => panic!("`enumizer_stream` requires return type like `Vec<T>`"),
+        _ => None,
+    }
+}
+
+/// Receiver style of `self: Type` argument: `Self`, `&Self`, `&mut Self`, `Box<Self>`, `Arc<Self>`, `Rc<Self>` or `Pin<&mut Self>`.
+fn typed_receiver_style(ty: &syn::Type) -> ReceiverStyle {
+    let is_self = |t: &syn::Type| matches!(t, syn::Type::Path(p) if p.qself.is_none() && p.path.is_ident("Self"));
+    let is_ref_self = |r: &syn::TypeReference| r.lifetime.is_none() && is_self(&r.elem);
+    match ty {
+        t if is_self(t) => return ReceiverStyle::Move,
+        syn::Type::Reference(r) if is_ref_self(r) => {
+            return if r.mutability.is_some() { ReceiverStyle::Mut } else { ReceiverStyle::Ref };
+        }
+        syn::Type::Path(p) if p.qself.is_none() => {
+            let last = p.path.segments.last().unwrap();
+            if let syn::PathArguments::AngleBracketed(ab) = &last.arguments {
+                if let (1, Some(syn::GenericArgument::Type(t))) = (ab.args.len(), ab.args.first()) {
+                    match t {
+                        t if is_self(t) && last.ident == "Box" => return ReceiverStyle::Box,
+                        t if is_self(t) && last.ident == "Arc" => return ReceiverStyle::Arc,
+                        t if is_self(t) && last.ident == "Rc" => return ReceiverStyle::Rc,
+                        syn::Type::Reference(r) if is_ref_self(r) && r.mutability.is_some() && last.ident == "Pin" => return ReceiverStyle::PinMut,
+                        _ => (),
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+    panic!("Trait-enumizer supports only `self`, `&self`, `&mut self`, `self: Box<Self>`, `self: Arc<Self>`, `self: Rc<Self>` and `self: Pin<&mut Self>` receivers, without explicit lifetimes")
+}
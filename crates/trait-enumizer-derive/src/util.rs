@@ -1,6 +1,6 @@
 use proc_macro2::{TokenStream};
 
-use crate::{Argument, AccessMode};
+use crate::{Argument, AccessMode, GenProxyParams, Restriction};
 
 use super::{InputData, ReceiverStyle};
 
@@ -17,33 +17,75 @@ impl std::fmt::Debug for InputData {
 
 
 impl InputData {
-    pub(crate) fn receiver_style_that_is_the_most_inconvenient_for_caller(&self) -> ReceiverStyle {
-        use ReceiverStyle::{Move, Mut, Ref};
-        let mut style = Ref;
-        for method in &self.methods {
-            match (style, method.receiver_style) {
-                (_, Move) => style = ReceiverStyle::Move,
-                (_, Ref) => (),
-                (Move, Mut) => (),
-                (Ref, Mut) => style = ReceiverStyle::Mut,
-                (Mut, Mut) => (),
-            }
+    /// First method (allowed by `restriction`, if any) that cannot be called from `o` accessed with `level`
+    pub(crate) fn method_uncallable_from(&self, level: ReceiverStyle, restriction: Option<&Restriction>) -> Option<&crate::Method> {
+        self.methods
+            .iter()
+            .filter(|m| restriction.is_none_or(|r| r.allows(m)))
+            .find(|m| !level.can_call(m.receiver_style))
+    }
+
+    /// First method that a trait impl of a proxy, whose `try_*` methods take `level`, cannot implement
+    pub(crate) fn method_unimplementable_by(&self, level: ReceiverStyle) -> Option<&crate::Method> {
+        self.methods.iter().find(|m| m.receiver_style.proxy_self_expr(level).is_none())
+    }
+
+    /// Whether trait impls of a proxy with `level` need the proxy to be `Unpin`, for `self: Pin<&mut Self>` methods
+    pub(crate) fn needs_unpin(&self, level: ReceiverStyle) -> bool {
+        level == ReceiverStyle::Mut && self.methods.iter().any(|m| m.receiver_style == ReceiverStyle::PinMut)
+    }
+
+    /// Whether return values are wrapped in `Result<T, CallError>`, as requested by `call_errors`
+    pub(crate) fn wraps_return_values(&self) -> bool {
+        self.params.call_errors
+    }
+
+    /// Whether some methods are marked `#[enumizer_handle]`
+    pub(crate) fn has_handles(&self) -> bool {
+        self.methods.iter().any(|m| m.handle.is_some())
+    }
+
+    /// `+ HoldsHandles<T>` bounds for call functions' target, for each `#[enumizer_handle]` method's return type
+    pub(crate) fn handle_bounds(&self) -> TokenStream {
+        let runtime = crate::generate::runtime_crate();
+        let mut bounds = TokenStream::new();
+        for handle in self.methods.iter().filter_map(|m| m.handle.as_ref()) {
+            let rt = &handle.ret;
+            bounds.extend(q! {+ #runtime::registry::HoldsHandles<#rt>});
         }
-        style
+        bounds
     }
-    pub(crate) fn receiver_style_that_is_the_most_inconvenient_for_callee(&self) -> ReceiverStyle {
-        use ReceiverStyle::{Move, Mut, Ref};
-        let mut style = Move;
-        for method in &self.methods {
-            match (style, method.receiver_style) {
-                (_, Ref) => style = ReceiverStyle::Ref,
-                (_, Move) => (),
-                (Ref, Mut) => (),
-                (Move, Mut) => style = ReceiverStyle::Mut,
-                (Mut, Mut) => (),
-            }
+
+    /// Whether enum has `method_info` function
+    pub(crate) fn has_method_info(&self) -> bool {
+        self.params.call_fns.iter().any(|c| c.interceptor.is_some() || c.metrics.is_some())
+            || self.params.proxies.iter().any(|p| p.interceptor.is_some() || p.metrics.is_some())
+    }
+
+    /// Type sent through return channel for a non-streamed return value of type `rt`
+    pub(crate) fn wire_return_type(&self, rt: &syn::Type) -> TokenStream {
+        if self.wraps_return_values() {
+            let runtime = crate::generate::runtime_crate();
+            q! {::core::result::Result<#rt, #runtime::CallError>}
+        } else {
+            q! {#rt}
         }
-        style
+    }
+
+    /// Type that `try_*` methods of a proxy return (inside `Result<_, E>`) for methods without return values.
+    /// There is no return channel, so proxy-side interceptors report rejections there.
+    pub(crate) fn proxy_unit_type(&self, gpparams: &GenProxyParams) -> TokenStream {
+        if gpparams.interceptor.is_some() {
+            let runtime = crate::generate::runtime_crate();
+            q! {::core::result::Result<(), #runtime::CallError>}
+        } else {
+            q! {()}
+        }
+    }
+
+    /// Whether enum has `priority` function. Control variants always have `Ping` prioritized.
+    pub(crate) fn has_priorities(&self) -> bool {
+        self.params.control_variants || self.methods.iter().any(|m| m.priority.is_some())
     }
 
     #[allow(dead_code)]
@@ -78,6 +120,10 @@ impl ReceiverStyle {
             ReceiverStyle::Move => q! {self},
             ReceiverStyle::Ref => q! {&self},
             ReceiverStyle::Mut => q! {&mut self},
+            ReceiverStyle::Box => q! {self: ::std::boxed::Box<Self>},
+            ReceiverStyle::Arc => q! {self: ::std::sync::Arc<Self>},
+            ReceiverStyle::Rc => q! {self: ::std::rc::Rc<Self>},
+            ReceiverStyle::PinMut => q! {self: ::core::pin::Pin<&mut Self>},
         }
     }
 
@@ -86,6 +132,7 @@ impl ReceiverStyle {
             ReceiverStyle::Move => q! {FnOnce},
             ReceiverStyle::Mut => q! {FnMut},
             ReceiverStyle::Ref => q! {Fn},
+            _ => unreachable!("proxies are only `Fn`, `FnMut` or `FnOnce`"),
         }
     }
     pub(crate) fn call_fn_name(self, returnval: bool) -> &'static str {
@@ -93,11 +140,87 @@ impl ReceiverStyle {
            (ReceiverStyle::Move, false) => "call_once",
            (ReceiverStyle::Mut, false) => "call_mut",
            (ReceiverStyle::Ref, false) => "call",
+           (ReceiverStyle::Box, false) => "call_boxed",
+           (ReceiverStyle::Arc, false) => "call_arc",
+           (ReceiverStyle::Rc, false) => "call_rc",
+           (ReceiverStyle::PinMut, false) => "call_pin",
            (ReceiverStyle::Move, true) => "try_call_once",
            (ReceiverStyle::Mut, true) => "try_call_mut",
            (ReceiverStyle::Ref, true) => "try_call",
+           (ReceiverStyle::Box, true) => "try_call_boxed",
+           (ReceiverStyle::Arc, true) => "try_call_arc",
+           (ReceiverStyle::Rc, true) => "try_call_rc",
+           (ReceiverStyle::PinMut, true) => "try_call_pin",
        }
    }
+
+    /// Whether call function's `o` accessed with this level can be turned into `receiver` of a method
+    pub(crate) fn can_call(self, receiver: ReceiverStyle) -> bool {
+        use ReceiverStyle::*;
+        matches!(
+            (self, receiver),
+            (Move | Box, _) | (Mut, Mut) | (Arc, Arc) | (Rc, Rc) | (PinMut, PinMut) | (_, Ref)
+        )
+    }
+
+    /// Expression turning call function's `o` into `receiver`, for methods that cannot be called as just `o.method()`.
+    /// Such methods are called as `<I as Trait>::method(expr, ...)`, avoiding surprises of auto-deref.
+    pub(crate) fn receiver_expr(self, receiver: ReceiverStyle) -> Option<TokenStream> {
+        use ReceiverStyle::*;
+        Some(match (self, receiver) {
+            (Move | Mut | Ref, Move | Mut | Ref) => return None,
+            (Move, Box) => q! {::std::boxed::Box::new(o)},
+            (Move, Arc) => q! {::std::sync::Arc::new(o)},
+            (Move, Rc) => q! {::std::rc::Rc::new(o)},
+            (Move, PinMut) => q! {::core::pin::pin!(o)},
+            (Box, Move) => q! {*o},
+            (Box, Mut) => q! {&mut *o},
+            (Box, Ref) => q! {&*o},
+            (Box, Box) => q! {o},
+            (Box, Arc) => q! {::std::sync::Arc::from(o)},
+            (Box, Rc) => q! {::std::rc::Rc::from(o)},
+            (Box, PinMut) => q! {::std::boxed::Box::into_pin(o).as_mut()},
+            (Arc | Rc, Ref) => q! {&**o},
+            (Arc, Arc) => q! {::std::sync::Arc::clone(o)},
+            (Rc, Rc) => q! {::std::rc::Rc::clone(o)},
+            (PinMut, Ref) => q! {&*o},
+            (PinMut, PinMut) => q! {o.as_mut()},
+            _ => return None,
+        })
+    }
+
+    /// Expression turning `self` of a trait impl's method with `self` receiver style into
+    /// what `try_*` methods of a proxy with `level` take. `None` if it cannot be done.
+    pub(crate) fn proxy_self_expr(self, level: ReceiverStyle) -> Option<TokenStream> {
+        use ReceiverStyle::*;
+        Some(match (self, level) {
+            (Move, Move) | (Mut, Mut) | (Mut, Ref) | (Ref, Ref) => q! {self},
+            (Move, Ref) => q! {&self},
+            (Move, Mut) => q! {&mut self},
+            (Box, Move) => q! {*self},
+            // Moved into a block, as `self` binding is not `mut`
+            (Box, Mut) => q! {&mut *{ self }},
+            (Box | Arc | Rc | PinMut, Ref) => q! {&*self},
+            // Requires the proxy to be `Unpin`, see `needs_unpin`
+            (PinMut, Mut) => q! {::core::pin::Pin::get_mut(self)},
+            _ => return None,
+        })
+    }
+}
+
+impl Restriction {
+    pub(crate) fn allows(&self, method: &crate::Method) -> bool {
+        match self {
+            Restriction::Only(names) => names.contains(&method.name),
+            Restriction::Except(names) => !names.contains(&method.name),
+        }
+    }
+
+    pub(crate) fn names(&self) -> &[proc_macro2::Ident] {
+        match self {
+            Restriction::Only(names) | Restriction::Except(names) => names,
+        }
+    }
 }
 
 impl AccessMode {
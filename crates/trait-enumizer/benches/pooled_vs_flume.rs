@@ -0,0 +1,157 @@
+//! Chatty interface benchmark: many tiny `get_*` calls to an actor thread.
+//! Compares allocation-per-call `flume_class` with recycling `pooled_class`.
+//!
+//! Run with `cargo bench --features flume --bench pooled_vs_flume`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counts allocations to show allocator pressure along with timings.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const CALLS: u64 = 200_000;
+
+macro_rules! chatty_iface {
+    ($class:ident, $enum_name:ident, $proxy_name:ident) => {
+        #[trait_enumizer::enumizer(
+            name=$enum_name,
+            pub,
+            returnval=$class,
+            call_fn(name=try_call_mut, ref_mut),
+            proxy(Fn, name=$proxy_name, unwrapping_impl),
+        )]
+        pub trait Telemetry {
+            fn record(&mut self, x: u64);
+            fn get_total(&self) -> u64;
+            fn get_count(&self) -> u64;
+        }
+    };
+}
+
+mod with_flume {
+    use trait_enumizer::flume_class;
+    chatty_iface!(flume_class, Enum, Proxy);
+    pub use Telemetry as Iface;
+}
+
+mod with_pool {
+    use trait_enumizer::pooled_class;
+    chatty_iface!(pooled_class, Enum, Proxy);
+    pub use Telemetry as Iface;
+}
+
+#[derive(Default)]
+struct Counters {
+    total: u64,
+    count: u64,
+}
+
+impl with_flume::Iface for Counters {
+    fn record(&mut self, x: u64) {
+        self.total += x;
+        self.count += 1;
+    }
+    fn get_total(&self) -> u64 {
+        self.total
+    }
+    fn get_count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl with_pool::Iface for Counters {
+    fn record(&mut self, x: u64) {
+        self.total += x;
+        self.count += 1;
+    }
+    fn get_total(&self) -> u64 {
+        self.total
+    }
+    fn get_count(&self) -> u64 {
+        self.count
+    }
+}
+
+fn bench_flume() -> (Duration, u64) {
+    use with_flume::Iface;
+    let (tx, rx) = flume::bounded::<with_flume::Enum>(16);
+    let actor = std::thread::spawn(move || {
+        let mut o = Counters::default();
+        for msg in rx {
+            msg.try_call_mut(&mut o).unwrap();
+        }
+    });
+    let mut p = with_flume::Proxy::<_, _>(move |c| tx.send(c));
+    let allocs_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..CALLS {
+        p.record(i);
+        assert!(p.get_count() > 0);
+        assert!(p.get_total() >= i);
+    }
+    let elapsed = start.elapsed();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - allocs_before;
+    drop(p);
+    actor.join().unwrap();
+    (elapsed, allocs)
+}
+
+fn bench_pooled() -> (Duration, u64) {
+    use with_pool::Iface;
+    let (tx, rx) = flume::bounded::<with_pool::Enum>(16);
+    let actor = std::thread::spawn(move || {
+        let mut o = Counters::default();
+        for msg in rx {
+            msg.try_call_mut(&mut o).unwrap();
+        }
+    });
+    let mut p = with_pool::Proxy::<_, _>(move |c| tx.send(c));
+    let allocs_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..CALLS {
+        p.record(i);
+        assert!(p.get_count() > 0);
+        assert!(p.get_total() >= i);
+    }
+    let elapsed = start.elapsed();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - allocs_before;
+    drop(p);
+    actor.join().unwrap();
+    (elapsed, allocs)
+}
+
+fn report(name: &str, (d, allocs): (Duration, u64)) {
+    let calls = CALLS * 2;
+    println!(
+        "{:>8}: {:>10.3?} total, {:>6} ns and {:.2} allocations per returning call",
+        name,
+        d,
+        d.as_nanos() / calls as u128,
+        allocs as f64 / calls as f64,
+    );
+}
+
+fn main() {
+    // warm up
+    bench_flume();
+    bench_pooled();
+
+    report("flume", bench_flume());
+    report("pooled", bench_pooled());
+}
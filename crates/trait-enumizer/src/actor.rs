@@ -0,0 +1,488 @@
+//! Minimal actor runtime: run an object on a separate thread (or Tokio task) and talk to it using enum values.
+//!
+//! Use `dispatch` subparameter of `call_fn` to make the generated enum implement [`Dispatch`] (or [`AsyncDispatch`] for `async` call functions),
+//! then spawn the object and use [`ActorHandle::sink`] as proxy's closure:
+//!
+//! ```rust,ignore
+//! #[trait_enumizer::enumizer(name=QqqEnum, returnval=stdmpsc_class, call_fn(name=try_call_mut,ref_mut,dispatch), proxy(Fn,name=QqqProxy,unwrapping_impl))]
+//! trait Qqq { ... }
+//!
+//! let (handle, join) = trait_enumizer::actor::spawn_thread::<QqqEnum, _>(obj, Mailbox::Unbounded);
+//! let p = QqqProxy(handle.sink());
+//! drop(handle);
+//! ...
+//! drop(p);
+//! let obj = join.join().unwrap();
+//! ```
+//!
+//! Actor stops after all handles and sinks are dropped and all queued messages are processed
+//! (or after receiving `Shutdown` variant added by `control_variants`). Then it returns the object back.
+//!
+//! [`spawn_rw_thread`] runs `&self` methods in parallel on a pool of worker threads. It needs `call_fn(..., once, rw_dispatch)`.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc, Condvar, Mutex, PoisonError, RwLock};
+use std::thread::JoinHandle;
+use std::vec::Vec;
+
+use crate::{Control, Priority};
+
+mod queue;
+use queue::{QueueReceiver, QueueSender};
+
+/// Properties of a message used by actor's mailbox. Implemented by enums with `dispatch` or `rw_dispatch` call functions.
+pub trait Message {
+    /// Priority for [`Mailbox::PriorityBounded`] and [`Mailbox::PriorityUnbounded`], as specified by `#[enumizer_priority(...)]`
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    /// Whether [`coalesces_with`](Self::coalesces_with) may ever return `true`
+    const COALESCING: bool = false;
+
+    /// Whether this message should replace `queued` message in priority mailboxes, as specified by `#[enumizer_coalesce]`
+    fn coalesces_with(&self, queued: &Self) -> bool {
+        let _ = queued;
+        false
+    }
+}
+
+/// Enum value that can be applied to an actor's object. Implemented by `call_fn(..., dispatch)`.
+pub trait Dispatch<I: ?Sized>: Message {
+    /// Call the method specified by this enum value. Failures to deliver return values are ignored.
+    ///
+    /// Returns [`Control::Shutdown`] if actor should stop.
+    fn dispatch(self, o: &mut I) -> Control;
+}
+
+/// Async version of [`Dispatch`]. Implemented by `call_fn(..., async, dispatch)`.
+pub trait AsyncDispatch<I: ?Sized>: Message {
+    /// Call the method specified by this enum value. Failures to deliver return values are ignored.
+    fn dispatch_async(self, o: &mut I) -> impl core::future::Future<Output = Control> + Send;
+}
+
+/// Actor's incoming message queue configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mailbox {
+    /// Senders wait when there are that many messages queued.
+    ///
+    /// `Bounded(0)` is a rendezvous channel for [`spawn_thread`]: senders wait until the actor takes the message.
+    /// Tokio actors need capacity of at least 1.
+    Bounded(usize),
+    /// Senders never wait
+    Unbounded,
+    /// Like `Bounded`, but messages with higher [`Priority`] are received first. FIFO order is kept within a priority level.
+    ///
+    /// Also replaces queued messages with coalescing ones (see [`Message::coalesces_with`]).
+    /// Capacity must be at least 1, as coalescing needs a queue: `PriorityBounded(0)` makes actor spawning functions panic.
+    PriorityBounded(usize),
+    /// Like `Unbounded`, but messages with higher [`Priority`] are received first. FIFO order is kept within a priority level.
+    ///
+    /// Also replaces queued messages with coalescing ones (see [`Message::coalesces_with`]).
+    PriorityUnbounded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Error returned when sending a message to an actor that is no longer running.
+pub struct ActorStopped;
+impl core::fmt::Display for ActorStopped {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "trait-enumizer: actor is no longer running")
+    }
+}
+impl std::error::Error for ActorStopped {}
+
+enum Tx<M> {
+    StdBounded(mpsc::SyncSender<M>),
+    StdUnbounded(mpsc::Sender<M>),
+    Queue(QueueSender<M>),
+    #[cfg(feature = "tokio")]
+    TokioBounded(tokio::sync::mpsc::Sender<M>),
+    #[cfg(feature = "tokio")]
+    TokioUnbounded(tokio::sync::mpsc::UnboundedSender<M>),
+}
+
+impl<M> Clone for Tx<M> {
+    fn clone(&self) -> Self {
+        match self {
+            Tx::StdBounded(x) => Tx::StdBounded(x.clone()),
+            Tx::StdUnbounded(x) => Tx::StdUnbounded(x.clone()),
+            Tx::Queue(x) => Tx::Queue(x.clone()),
+            #[cfg(feature = "tokio")]
+            Tx::TokioBounded(x) => Tx::TokioBounded(x.clone()),
+            #[cfg(feature = "tokio")]
+            Tx::TokioUnbounded(x) => Tx::TokioUnbounded(x.clone()),
+        }
+    }
+}
+
+/// Sending side of actor's mailbox. Actor keeps running while at least one handle (or sink) exists.
+pub struct ActorHandle<M> {
+    tx: Tx<M>,
+}
+
+impl<M> Clone for ActorHandle<M> {
+    fn clone(&self) -> Self {
+        ActorHandle { tx: self.tx.clone() }
+    }
+}
+
+impl<M> core::fmt::Debug for ActorHandle<M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ActorHandle").finish_non_exhaustive()
+    }
+}
+
+impl<M: Message + Send + 'static> ActorHandle<M> {
+    /// Queue a message, waiting for space in bounded mailbox.
+    ///
+    /// For actors with bounded mailbox this must not be called from async context, use [`send_async`](Self::send_async) there.
+    pub fn send(&self, msg: M) -> Result<(), ActorStopped> {
+        match &self.tx {
+            Tx::StdBounded(x) => x.send(msg).map_err(|_| ActorStopped),
+            Tx::StdUnbounded(x) => x.send(msg).map_err(|_| ActorStopped),
+            Tx::Queue(x) => x.send(msg).map_err(|_| ActorStopped),
+            #[cfg(feature = "tokio")]
+            Tx::TokioBounded(x) => x.blocking_send(msg).map_err(|_| ActorStopped),
+            #[cfg(feature = "tokio")]
+            Tx::TokioUnbounded(x) => x.send(msg).map_err(|_| ActorStopped),
+        }
+    }
+
+    /// Queue a message, asynchronously waiting for space in bounded mailbox.
+    ///
+    /// For actors spawned on a thread with [`Mailbox::Bounded`] this blocks instead.
+    pub async fn send_async(&self, msg: M) -> Result<(), ActorStopped> {
+        match &self.tx {
+            Tx::Queue(x) => x.send_async(msg).await.map_err(|_| ActorStopped),
+            #[cfg(feature = "tokio")]
+            Tx::TokioBounded(x) => x.send(msg).await.map_err(|_| ActorStopped),
+            _ => self.send(msg),
+        }
+    }
+
+    /// Closure suitable as a field of a non-async proxy
+    pub fn sink(&self) -> impl Fn(M) -> Result<(), ActorStopped> + Clone + Send + Sync + 'static {
+        let h = self.clone();
+        move |msg| h.send(msg)
+    }
+
+    /// Closure suitable as a field of an `async` proxy
+    #[allow(clippy::type_complexity)]
+    pub fn async_sink(
+        &self,
+    ) -> impl Fn(M) -> core::pin::Pin<std::boxed::Box<dyn core::future::Future<Output = Result<(), ActorStopped>> + Send>>
+           + Clone
+           + Send
+           + Sync
+           + 'static {
+        let h = self.clone();
+        move |msg| {
+            let h = h.clone();
+            std::boxed::Box::pin(async move { h.send_async(msg).await })
+        }
+    }
+}
+
+enum Rx<M> {
+    Std(mpsc::Receiver<M>),
+    Queue(QueueReceiver<M>),
+}
+
+impl<M> Iterator for Rx<M> {
+    type Item = M;
+
+    fn next(&mut self) -> Option<M> {
+        match self {
+            Rx::Std(x) => x.recv().ok(),
+            Rx::Queue(x) => x.recv(),
+        }
+    }
+}
+
+fn std_mailbox<M: Message>(mailbox: Mailbox) -> (Tx<M>, Rx<M>) {
+    match mailbox {
+        Mailbox::Bounded(n) => {
+            let (tx, rx) = mpsc::sync_channel(n);
+            (Tx::StdBounded(tx), Rx::Std(rx))
+        }
+        Mailbox::Unbounded => {
+            let (tx, rx) = mpsc::channel();
+            (Tx::StdUnbounded(tx), Rx::Std(rx))
+        }
+        Mailbox::PriorityBounded(n) => {
+            assert!(n > 0, "`Mailbox::PriorityBounded` needs capacity of at least 1");
+            let (tx, rx) = queue::queue::<M>(Some(n));
+            (Tx::Queue(tx), Rx::Queue(rx))
+        }
+        Mailbox::PriorityUnbounded => {
+            let (tx, rx) = queue::queue::<M>(None);
+            (Tx::Queue(tx), Rx::Queue(rx))
+        }
+    }
+}
+
+/// Run `obj` on a new thread, applying incoming messages to it until all [`ActorHandle`]s are dropped.
+/// Thread returns the object in its final state.
+///
+/// # Panics
+///
+/// Panics on `Mailbox::PriorityBounded(0)`.
+pub fn spawn_thread<M, I>(mut obj: I, mailbox: Mailbox) -> (ActorHandle<M>, JoinHandle<I>)
+where
+    M: Dispatch<I> + Send + 'static,
+    I: Send + 'static,
+{
+    let (tx, rx) = std_mailbox::<M>(mailbox);
+    let join = std::thread::spawn(move || {
+        for msg in rx {
+            if msg.dispatch(&mut obj) == Control::Shutdown {
+                break;
+            }
+        }
+        obj
+    });
+    (ActorHandle { tx }, join)
+}
+
+/// How a message accesses actor's object. Returned by [`RwDispatch::access`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// `&self` method, may run in parallel with other reads
+    Read,
+    /// `&mut self` method, runs exclusively
+    Write,
+    /// `self` method, consumes the object and stops the actor
+    Consume,
+    /// Variant added by `control_variants`. Handled exclusively after all earlier messages.
+    Control,
+}
+
+/// Enum value that can be applied to a reader/writer actor's object. Implemented by `call_fn(..., once, rw_dispatch)`.
+pub trait RwDispatch<I>: Message {
+    /// Receiver style of the method specified by this enum value
+    fn access(&self) -> Access;
+    /// Call `&self` method. Panics for other methods.
+    fn dispatch_ref(self, o: &I);
+    /// Call `&self` or `&mut self` method, or handle control variant. Panics for `self` methods.
+    fn dispatch_mut(self, o: &mut I) -> Control;
+    /// Call any method
+    fn dispatch_once(self, o: I);
+}
+
+/// Ordering guarantees between writes and reads of [`spawn_rw_thread`] actor.
+///
+/// In both modes reads see all writes sent before them and writes are applied in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RwOrdering {
+    /// Each write waits for all earlier reads to finish, so reads never see writes sent after them.
+    Strict,
+    /// Writes do not wait for earlier reads that are still queued, so those may see newer state.
+    Relaxed,
+}
+
+/// Number of reads queued to or running on worker threads
+#[derive(Default)]
+struct InFlight {
+    count: Mutex<usize>,
+    cond: Condvar,
+}
+
+impl InFlight {
+    fn wait_idle(&self) {
+        let mut count = self.count.lock().unwrap_or_else(PoisonError::into_inner);
+        while *count > 0 {
+            count = self.cond.wait(count).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// Decrements [`InFlight`] counter even if the method panics
+struct ReadDone<'a>(&'a InFlight);
+
+impl Drop for ReadDone<'_> {
+    fn drop(&mut self) {
+        *self.0.count.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        self.0.cond.notify_all();
+    }
+}
+
+/// Run `obj` as a reader/writer actor: `&self` methods are executed in parallel on `workers` threads,
+/// `&mut self` methods are executed exclusively on the dispatcher thread in mailbox order.
+///
+/// Actor stops when all [`ActorHandle`]s are dropped or `Shutdown` control variant is received (returning `Some(obj)`), or after handling a `self` method (returning `None`).
+/// Messages still queued after a `self` method are dropped.
+///
+/// A panicking `&self` method does not stop the actor, only its caller sees the return channel closed.
+/// A panicking `&mut self` method stops the actor, like with [`spawn_thread`].
+///
+/// # Panics
+///
+/// Panics if `workers` is 0 or on `Mailbox::PriorityBounded(0)`.
+pub fn spawn_rw_thread<M, I>(
+    obj: I,
+    mailbox: Mailbox,
+    workers: usize,
+    ordering: RwOrdering,
+) -> (ActorHandle<M>, JoinHandle<Option<I>>)
+where
+    M: RwDispatch<I> + Send + 'static,
+    I: Send + Sync + 'static,
+{
+    assert!(workers > 0, "reader/writer actor needs at least one worker thread");
+    let (tx, rx) = std_mailbox::<M>(mailbox);
+    let join = std::thread::spawn(move || {
+        let obj = Arc::new(RwLock::new(obj));
+        let in_flight = Arc::new(InFlight::default());
+        let (jobs_tx, jobs_rx) = mpsc::channel::<M>();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let pool: Vec<JoinHandle<()>> = (0..workers)
+            .map(|_| {
+                let obj = obj.clone();
+                let in_flight = in_flight.clone();
+                let jobs_rx = jobs_rx.clone();
+                std::thread::spawn(move || loop {
+                    let Ok(msg) = jobs_rx.lock().unwrap_or_else(PoisonError::into_inner).recv() else {
+                        break;
+                    };
+                    let _done = ReadDone(&in_flight);
+                    // Keep the worker alive; caller of the panicking method sees its return channel closed
+                    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                        msg.dispatch_ref(&obj.read().unwrap_or_else(PoisonError::into_inner))
+                    }));
+                })
+            })
+            .collect();
+        let mut consume = None;
+        for msg in rx {
+            match msg.access() {
+                Access::Read => {
+                    *in_flight.count.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+                    jobs_tx.send(msg).expect("workers run until `jobs_tx` is dropped");
+                }
+                Access::Write => {
+                    if ordering == RwOrdering::Strict {
+                        in_flight.wait_idle();
+                    }
+                    msg.dispatch_mut(&mut obj.write().unwrap_or_else(PoisonError::into_inner));
+                }
+                Access::Control => {
+                    in_flight.wait_idle();
+                    if msg.dispatch_mut(&mut obj.write().unwrap_or_else(PoisonError::into_inner)) == Control::Shutdown {
+                        break;
+                    }
+                }
+                Access::Consume => {
+                    consume = Some(msg);
+                    break;
+                }
+            }
+        }
+        drop(jobs_tx);
+        for worker in pool {
+            let _ = worker.join();
+        }
+        let obj = Arc::try_unwrap(obj)
+            .ok()
+            .expect("workers are stopped")
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        match consume {
+            Some(msg) => {
+                msg.dispatch_once(obj);
+                None
+            }
+            None => Some(obj),
+        }
+    });
+    (ActorHandle { tx }, join)
+}
+
+#[cfg(feature = "tokio")]
+enum TokioRx<M> {
+    Bounded(tokio::sync::mpsc::Receiver<M>),
+    Unbounded(tokio::sync::mpsc::UnboundedReceiver<M>),
+    Queue(QueueReceiver<M>),
+}
+
+#[cfg(feature = "tokio")]
+impl<M> TokioRx<M> {
+    async fn recv(&mut self) -> Option<M> {
+        match self {
+            TokioRx::Bounded(x) => x.recv().await,
+            TokioRx::Unbounded(x) => x.recv().await,
+            TokioRx::Queue(x) => x.recv_async().await,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn tokio_mailbox<M: Message>(mailbox: Mailbox) -> (ActorHandle<M>, TokioRx<M>) {
+    match mailbox {
+        Mailbox::Bounded(n) => {
+            assert!(n > 0, "Tokio actors need `Mailbox::Bounded` capacity of at least 1, as Tokio has no rendezvous channels");
+            let (tx, rx) = tokio::sync::mpsc::channel(n);
+            (ActorHandle { tx: Tx::TokioBounded(tx) }, TokioRx::Bounded(rx))
+        }
+        Mailbox::Unbounded => {
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            (ActorHandle { tx: Tx::TokioUnbounded(tx) }, TokioRx::Unbounded(rx))
+        }
+        Mailbox::PriorityBounded(n) => {
+            assert!(n > 0, "`Mailbox::PriorityBounded` needs capacity of at least 1");
+            let (tx, rx) = queue::queue::<M>(Some(n));
+            (ActorHandle { tx: Tx::Queue(tx) }, TokioRx::Queue(rx))
+        }
+        Mailbox::PriorityUnbounded => {
+            let (tx, rx) = queue::queue::<M>(None);
+            (ActorHandle { tx: Tx::Queue(tx) }, TokioRx::Queue(rx))
+        }
+    }
+}
+
+/// Run `obj` in a new Tokio task, applying incoming messages to it (using non-async call function) until all [`ActorHandle`]s are dropped.
+/// Task returns the object in its final state.
+///
+/// Must be called within Tokio runtime.
+///
+/// # Panics
+///
+/// Panics on `Mailbox::Bounded(0)` and `Mailbox::PriorityBounded(0)`.
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub fn spawn_tokio<M, I>(mut obj: I, mailbox: Mailbox) -> (ActorHandle<M>, tokio::task::JoinHandle<I>)
+where
+    M: Dispatch<I> + Send + 'static,
+    I: Send + 'static,
+{
+    let (handle, mut rx) = tokio_mailbox::<M>(mailbox);
+    let join = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if msg.dispatch(&mut obj) == Control::Shutdown {
+                break;
+            }
+        }
+        obj
+    });
+    (handle, join)
+}
+
+/// Like [`spawn_tokio`], but uses async call function to handle messages.
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub fn spawn_tokio_async<M, I>(mut obj: I, mailbox: Mailbox) -> (ActorHandle<M>, tokio::task::JoinHandle<I>)
+where
+    M: AsyncDispatch<I> + Send + 'static,
+    I: Send + 'static,
+{
+    let (handle, mut rx) = tokio_mailbox::<M>(mailbox);
+    let join = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if msg.dispatch_async(&mut obj).await == Control::Shutdown {
+                break;
+            }
+        }
+        obj
+    });
+    (handle, join)
+}
@@ -0,0 +1,229 @@
+//! Mailbox that receives messages with higher [`Priority`] first, keeping FIFO order within a priority level.
+//! Coalescing messages replace matching queued ones in place.
+
+use core::task::{Poll, Waker};
+use std::collections::VecDeque;
+use std::vec::Vec;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use super::Message;
+use crate::Priority;
+
+struct State<M> {
+    levels: [VecDeque<M>; Priority::COUNT],
+    len: usize,
+    senders: usize,
+    receiver_alive: bool,
+    /// Async receiver waiting for a message
+    waker: Option<Waker>,
+    /// Async senders waiting for space in the queue
+    sender_wakers: Vec<Waker>,
+}
+
+impl<M> State<M> {
+    fn pop(&mut self) -> Option<M> {
+        let msg = self.levels.iter_mut().rev().find_map(VecDeque::pop_front)?;
+        self.len -= 1;
+        Some(msg)
+    }
+}
+
+/// Outcome of an attempt to queue a message without waiting
+enum Attempt<M> {
+    Queued,
+    /// Message replaced this queued one
+    Coalesced(M),
+    Full(M),
+    ReceiverGone(M),
+}
+
+struct Shared<M> {
+    state: Mutex<State<M>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: Option<usize>,
+}
+
+impl<M> Shared<M> {
+    fn lock(&self) -> MutexGuard<'_, State<M>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wake_receiver(&self, mut state: MutexGuard<'_, State<M>>) {
+        let waker = state.waker.take();
+        drop(state);
+        self.not_empty.notify_one();
+        if let Some(w) = waker {
+            w.wake();
+        }
+    }
+
+    /// Wakes both blocked and async senders after a message is taken from the queue.
+    /// All async senders are woken, as some of them may have been cancelled since registering.
+    fn wake_senders(&self, mut state: MutexGuard<'_, State<M>>) {
+        let wakers = core::mem::take(&mut state.sender_wakers);
+        drop(state);
+        self.not_full.notify_one();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl<M: Message> Shared<M> {
+    fn try_push(&self, state: &mut State<M>, msg: M) -> Attempt<M> {
+        if !state.receiver_alive {
+            return Attempt::ReceiverGone(msg);
+        }
+        let level = msg.priority().index();
+        if M::COALESCING {
+            if let Some(queued) = state.levels[level].iter_mut().find(|q| msg.coalesces_with(q)) {
+                return Attempt::Coalesced(core::mem::replace(queued, msg));
+            }
+        }
+        if matches!(self.capacity, Some(c) if state.len >= c) {
+            return Attempt::Full(msg);
+        }
+        state.levels[level].push_back(msg);
+        state.len += 1;
+        Attempt::Queued
+    }
+}
+
+pub(super) struct QueueSender<M>(Arc<Shared<M>>);
+pub(super) struct QueueReceiver<M>(Arc<Shared<M>>);
+
+pub(super) fn queue<M>(capacity: Option<usize>) -> (QueueSender<M>, QueueReceiver<M>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            levels: Default::default(),
+            len: 0,
+            senders: 1,
+            receiver_alive: true,
+            waker: None,
+            sender_wakers: Vec::new(),
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        capacity,
+    });
+    (QueueSender(shared.clone()), QueueReceiver(shared))
+}
+
+impl<M: Message> QueueSender<M> {
+    /// Waits while the queue is full (unless message coalesces). Fails if the receiver is gone.
+    pub(super) fn send(&self, mut msg: M) -> Result<(), M> {
+        let mut state = self.0.lock();
+        loop {
+            match self.0.try_push(&mut state, msg) {
+                Attempt::Queued => {
+                    self.0.wake_receiver(state);
+                    return Ok(());
+                }
+                Attempt::Coalesced(old) => {
+                    drop(state);
+                    drop(old);
+                    return Ok(());
+                }
+                Attempt::Full(m) => {
+                    msg = m;
+                    state = self.0.not_full.wait(state).unwrap_or_else(PoisonError::into_inner);
+                }
+                Attempt::ReceiverGone(m) => return Err(m),
+            }
+        }
+    }
+
+    /// Async version of [`send`](Self::send), waiting without blocking the thread
+    pub(super) async fn send_async(&self, msg: M) -> Result<(), M> {
+        let mut msg = Some(msg);
+        core::future::poll_fn(|cx| {
+            let mut state = self.0.lock();
+            match self.0.try_push(&mut state, msg.take().expect("polled after completion")) {
+                Attempt::Queued => {
+                    self.0.wake_receiver(state);
+                    Poll::Ready(Ok(()))
+                }
+                Attempt::Coalesced(old) => {
+                    drop(state);
+                    drop(old);
+                    Poll::Ready(Ok(()))
+                }
+                Attempt::Full(m) => {
+                    msg = Some(m);
+                    if !state.sender_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        state.sender_wakers.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+                Attempt::ReceiverGone(m) => Poll::Ready(Err(m)),
+            }
+        })
+        .await
+    }
+}
+
+impl<M> Clone for QueueSender<M> {
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
+        QueueSender(self.0.clone())
+    }
+}
+
+impl<M> Drop for QueueSender<M> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.0.wake_receiver(state);
+        }
+    }
+}
+
+impl<M> QueueReceiver<M> {
+    /// Waits for a message. Returns `None` when queue is empty and all senders are gone.
+    pub(super) fn recv(&self) -> Option<M> {
+        let mut state = self.0.lock();
+        loop {
+            if let Some(msg) = state.pop() {
+                self.0.wake_senders(state);
+                return Some(msg);
+            }
+            if state.senders == 0 {
+                return None;
+            }
+            state = self.0.not_empty.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Async version of [`recv`](Self::recv)
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(super) async fn recv_async(&self) -> Option<M> {
+        core::future::poll_fn(|cx| {
+            let mut state = self.0.lock();
+            if let Some(msg) = state.pop() {
+                self.0.wake_senders(state);
+                return Poll::Ready(Some(msg));
+            }
+            if state.senders == 0 {
+                return Poll::Ready(None);
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl<M> Drop for QueueReceiver<M> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.receiver_alive = false;
+        // Queued messages (and their return value senders) are dropped outside of the lock
+        let levels = core::mem::take(&mut state.levels);
+        state.len = 0;
+        let sender_wakers = core::mem::take(&mut state.sender_wakers);
+        drop(state);
+        self.0.not_full.notify_all();
+        sender_wakers.into_iter().for_each(Waker::wake);
+        drop(levels);
+    }
+}
@@ -0,0 +1,48 @@
+/// How `fan_out` call functions combine return values of the same method called on several targets.
+///
+/// Implement it for each non-`()` return type of the trait's methods. Results are folded in order of targets.
+pub trait Aggregate<T> {
+    /// Combine results accumulated so far with the result of the next target
+    fn combine(&mut self, acc: T, next: T) -> T;
+}
+
+/// Reply with the result of the first target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct First;
+
+impl<T> Aggregate<T> for First {
+    fn combine(&mut self, acc: T, _next: T) -> T {
+        acc
+    }
+}
+
+/// Reply with the result of the last target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Last;
+
+impl<T> Aggregate<T> for Last {
+    fn combine(&mut self, _acc: T, next: T) -> T {
+        next
+    }
+}
+
+/// Reply with results of all targets concatenated, e.g. for methods returning `Vec`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Concat;
+
+impl<T: Extend<T::Item> + IntoIterator> Aggregate<T> for Concat {
+    fn combine(&mut self, mut acc: T, next: T) -> T {
+        acc.extend(next);
+        acc
+    }
+}
+
+/// Reply with results of all targets combined by the closure
+#[derive(Debug, Clone, Copy)]
+pub struct Reduce<F>(pub F);
+
+impl<T, F: FnMut(T, T) -> T> Aggregate<T> for Reduce<F> {
+    fn combine(&mut self, acc: T, next: T) -> T {
+        (self.0)(acc, next)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Panicked, Reject};
+
+/// Error sent back through the return channel instead of method's return value.
+///
+/// Return values are wrapped in `Result<T, CallError>` if the enum has `call_errors` parameter. It is required by call functions
+/// with `catch_unwind`, `interceptor`, `only(...)` or `except(...)` and by proxies with `interceptor` or `object_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CallError {
+    /// Method panicked in a `call_fn(..., catch_unwind)` call function
+    Panicked(Panicked),
+    /// Interceptor refused the call
+    Rejected(Reject),
+    /// Proxy's `object_id` was not found in `trait_enumizer::registry::Registry`
+    NoSuchObject,
+    /// Method is outside of call function's `only(...)` or `except(...)` list
+    NotAllowed(NotAllowed),
+    /// Method was not called, as the object is [`Poisonable`](crate::Poisonable) and an earlier call panicked
+    Poisoned,
+    /// `call_fn(..., fan_out)` call function got no objects to call the method on
+    NoTargets,
+}
+
+impl core::fmt::Display for CallError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CallError::Panicked(x) => x.fmt(f),
+            CallError::Rejected(x) => x.fmt(f),
+            CallError::NoSuchObject => crate::registry::NoSuchObject.fmt(f),
+            CallError::NotAllowed(x) => x.fmt(f),
+            CallError::Poisoned => f.write_str("trait-enumizer: object is poisoned by an earlier panic"),
+            CallError::NoTargets => f.write_str("trait-enumizer: no objects to call the method on"),
+        }
+    }
+}
+impl std::error::Error for CallError {}
+
+/// Call of a method that call function with `only(...)` or `except(...)` does not allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NotAllowed {
+    method: &'static str,
+}
+
+impl NotAllowed {
+    /// Used by generated call functions
+    pub fn new(method: &'static str) -> Self {
+        NotAllowed { method }
+    }
+
+    /// Name of the method that was not called
+    pub fn method(&self) -> &'static str {
+        self.method
+    }
+}
+
+impl core::fmt::Display for NotAllowed {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "trait-enumizer: method `{}` is not allowed", self.method)
+    }
+}
+impl std::error::Error for NotAllowed {}
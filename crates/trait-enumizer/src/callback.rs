@@ -0,0 +1,36 @@
+use std::boxed::Box;
+use std::sync::Arc;
+
+/// Continuation of `try_*_then` proxy methods, prepared to be run by [`CallbackExecutor`]
+pub type CallbackJob = Box<dyn FnOnce() + Send>;
+
+/// Runs continuations of `try_*_then` proxy methods instead of the callee thread, e.g. by posting them to an event loop.
+/// Use it as `extra_field_type(trait_enumizer::CallbackExecutor)` of a proxy with `returnval=callback_class`.
+///
+/// Blocking `try_*` methods of such proxy do not use it.
+#[derive(Clone)]
+pub struct CallbackExecutor(Arc<dyn Fn(CallbackJob) + Send + Sync>);
+
+impl CallbackExecutor {
+    /// Executor handing each continuation to `f`
+    pub fn new(f: impl Fn(CallbackJob) + Send + Sync + 'static) -> Self {
+        CallbackExecutor(Arc::new(f))
+    }
+
+    /// Hand `job` to the executor
+    pub fn execute(&self, job: CallbackJob) {
+        (self.0)(job)
+    }
+
+    #[doc(hidden)]
+    pub fn wrap<T: Send + 'static>(&self, f: impl FnOnce(T) + Send + 'static) -> Box<dyn FnOnce(T) + Send> {
+        let executor = self.clone();
+        Box::new(move |x| executor.execute(Box::new(move || f(x))))
+    }
+}
+
+impl core::fmt::Debug for CallbackExecutor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CallbackExecutor").finish_non_exhaustive()
+    }
+}
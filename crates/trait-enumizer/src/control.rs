@@ -0,0 +1,8 @@
+/// What the caller of a call function should do next. Returned by call functions of enums with `control_variants`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Control {
+    /// Method was called (or `Ping`/`Barrier` was answered), keep handling messages
+    Continue,
+    /// `Shutdown` was received, stop handling messages
+    Shutdown,
+}
@@ -0,0 +1,61 @@
+use core::time::Duration;
+use std::borrow::Cow;
+
+/// Static description of a method, as seen by interceptors. Returned by generated `method_info` function of the enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MethodInfo {
+    /// Method name. `shutdown`, `ping` or `barrier` for variants added by `control_variants`.
+    pub name: &'static str,
+    /// Name of the generated enum
+    pub enum_name: &'static str,
+    /// Whether the variant has a `ret` field, i.e. the caller expects a reply
+    pub has_return_value: bool,
+}
+
+/// Hooks around calls, specified by `interceptor = Type` subparameter of `call_fn` or `proxy`.
+///
+/// Hooks are associated functions, not methods: use statics if they need state.
+///
+/// For call functions, `after` receives the duration of the method call (including sending its return value).
+/// For proxies, it receives the duration of the proxy's closure call, i.e. sending the message, not waiting for the reply.
+pub trait Interceptor<M> {
+    /// Called before `msg` is handled. Returning an error skips the call.
+    ///
+    /// Rejected calls reply with [`CallError::Rejected`](crate::CallError::Rejected) to methods with return values.
+    /// For methods without return values, proxies return it from `try_*` methods, while call functions skip the call.
+    /// Streamed return values are just ended early.
+    fn before(method: &MethodInfo, msg: &M) -> Result<(), Reject> {
+        let _ = (method, msg);
+        Ok(())
+    }
+
+    /// Called after a message that was not rejected is handled.
+    fn after(method: &MethodInfo, elapsed: Duration) {
+        let _ = (method, elapsed);
+    }
+}
+
+/// Reason for [`Interceptor::before`] to refuse a call
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reject {
+    reason: Cow<'static, str>,
+}
+
+impl Reject {
+    /// Create rejection with human-readable reason
+    pub fn new(reason: impl Into<Cow<'static, str>>) -> Self {
+        Reject { reason: reason.into() }
+    }
+
+    /// Reason specified in [`Reject::new`]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl core::fmt::Display for Reject {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "trait-enumizer: call rejected: {}", self.reason)
+    }
+}
+impl std::error::Error for Reject {}
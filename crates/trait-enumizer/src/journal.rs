@@ -0,0 +1,188 @@
+//! Recording enum values to an append-only file and replaying them against an implementor.
+//!
+//! `journal=<ident>` parameter generates a serializable enum with the given name, holding just arguments
+//! of each method, along with [`Journaled`] impl for the main enum and [`Replay`] impl for the new one.
+//! Arguments need to be `Clone + Serialize + Deserialize`, return values need to be `Serialize`.
+//!
+//! [`Journal`] writes one JSON object per line: `{"ts_us":...,"call":...}`, with optional `"ret"` field.
+
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Re-export for code generated in `journal` mode
+pub use serde;
+/// Re-export for code generated in `journal` mode
+pub use serde_json;
+
+/// Implemented for enums generated with `journal=<ident>` parameter
+pub trait Journaled {
+    /// Generated enum with arguments of each method
+    type Call: Serialize + DeserializeOwned;
+
+    /// Copy arguments of this call. `None` for control variants.
+    fn to_call(&self) -> Option<Self::Call>;
+}
+
+/// Implemented for enums named by `journal=<ident>` parameter, for each implementor of the trait
+pub trait Replay<I: ?Sized> {
+    /// Call the method directly on `o` (bypassing call functions and channels) and serialize its return value.
+    /// Methods without return value produce `null`, iterators are collected.
+    fn replay(self, o: &mut I) -> Result<Value, serde_json::Error>;
+}
+
+/// One line of a journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry<C> {
+    /// Microseconds since Unix epoch when the entry was recorded
+    pub ts_us: u64,
+    /// Method and its arguments
+    pub call: C,
+    /// Serialized return value, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ret: Option<Value>,
+}
+
+/// Append-only writer of journal entries. Can be shared between threads (e.g. captured in proxy's `Fn` closure).
+#[derive(Debug)]
+pub struct Journal<W> {
+    out: Mutex<W>,
+}
+
+impl<W: Write> Journal<W> {
+    /// Write entries to `out`, e.g. a file opened in append mode
+    pub fn new(out: W) -> Self {
+        Journal { out: Mutex::new(out) }
+    }
+
+    /// Record arguments of `msg`, without return value. Control variants are skipped.
+    pub fn record<M: Journaled>(&self, msg: &M) -> std::io::Result<()> {
+        match msg.to_call() {
+            Some(call) => self.record_call(&call, None),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a call with optional serialized return value
+    pub fn record_call<C: Serialize>(&self, call: &C, ret: Option<Value>) -> std::io::Result<()> {
+        let ts_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+        let mut line = serde_json::to_vec(&Entry { ts_us, call, ret })?;
+        line.push(b'\n');
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        out.write_all(&line)?;
+        out.flush()
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Parse journal entries from `journal`, one per line. Empty lines are skipped.
+pub fn read<C: DeserializeOwned>(journal: impl BufRead) -> impl Iterator<Item = Result<Entry<C>, ReplayError>> {
+    journal
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, Ok(l) if l.trim().is_empty()))
+        .map(|(n, l)| {
+            let l = l.map_err(ReplayError::Io)?;
+            serde_json::from_str(&l).map_err(|error| ReplayError::Parse { line: n + 1, error })
+        })
+}
+
+/// Replay all calls from `journal` against `o`, returning their serialized return values.
+///
+/// If an entry has recorded return value and it differs from the replayed one, stops with [`ReplayError::Mismatch`].
+/// Use [`replay_into`] to produce a journal with return values to compare against later.
+pub fn replay<C: DeserializeOwned + Serialize + Clone + Replay<I>, I: ?Sized>(journal: impl BufRead, o: &mut I) -> Result<Vec<Value>, ReplayError> {
+    replay_impl::<C, I, std::io::Sink>(journal, o, None)
+}
+
+/// Like [`replay`], but also record each replayed call with its return value to `out`
+pub fn replay_into<C: DeserializeOwned + Serialize + Clone + Replay<I>, I: ?Sized, W: Write>(
+    journal: impl BufRead,
+    o: &mut I,
+    out: &Journal<W>,
+) -> Result<Vec<Value>, ReplayError> {
+    replay_impl::<C, I, W>(journal, o, Some(out))
+}
+
+fn replay_impl<C: DeserializeOwned + Serialize + Clone + Replay<I>, I: ?Sized, W: Write>(
+    journal: impl BufRead,
+    o: &mut I,
+    out: Option<&Journal<W>>,
+) -> Result<Vec<Value>, ReplayError> {
+    let mut rets = Vec::new();
+    for (index, entry) in read::<C>(journal).enumerate() {
+        let entry = entry?;
+        let call = out.map(|_| entry.call.clone());
+        let actual = entry.call.replay(o).map_err(|error| ReplayError::Serialize { index, error })?;
+        if let Some(expected) = entry.ret {
+            if expected != actual {
+                return Err(ReplayError::Mismatch { index, expected, actual });
+            }
+        }
+        if let (Some(out), Some(call)) = (out, call) {
+            out.record_call(&call, Some(actual.clone())).map_err(ReplayError::Io)?;
+        }
+        rets.push(actual);
+    }
+    Ok(rets)
+}
+
+/// Error of reading or replaying a journal
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReplayError {
+    /// Failed to read or write a journal
+    Io(std::io::Error),
+    /// Journal line (counting from 1) is not a valid entry
+    Parse {
+        /// Line number
+        line: usize,
+        /// Deserialization error
+        error: serde_json::Error,
+    },
+    /// Return value of replayed call (counting from 0) could not be serialized
+    Serialize {
+        /// Entry number
+        index: usize,
+        /// Serialization error
+        error: serde_json::Error,
+    },
+    /// Replayed call (counting from 0) returned something else than recorded
+    Mismatch {
+        /// Entry number
+        index: usize,
+        /// Recorded return value
+        expected: Value,
+        /// Replayed return value
+        actual: Value,
+    },
+}
+
+impl core::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "trait-enumizer: journal I/O error: {}", e),
+            ReplayError::Parse { line, error } => write!(f, "trait-enumizer: invalid journal line {}: {}", line, error),
+            ReplayError::Serialize { index, error } => {
+                write!(f, "trait-enumizer: cannot serialize return value of journal entry {}: {}", index, error)
+            }
+            ReplayError::Mismatch { index, expected, actual } => write!(
+                f,
+                "trait-enumizer: journal entry {} returned {} instead of recorded {}",
+                index, actual, expected
+            ),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
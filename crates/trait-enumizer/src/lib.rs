@@ -12,6 +12,111 @@ extern crate std;
 pub use trait_enumizer_derive::enumizer;
 
 mod returnval;
+mod pending;
+mod control;
+mod priority;
+mod aggregate;
+mod multiplex;
+#[cfg(any(feature="flume", feature="futures"))]
+mod stream;
 
 #[doc(inline)]
 pub use returnval::*;
+
+pub use pending::PendingReply;
+
+pub use control::Control;
+
+pub use priority::Priority;
+
+pub use aggregate::{Aggregate, Concat, First, Last, Reduce};
+
+pub use multiplex::MuxProxy;
+
+#[cfg(any(feature="flume", feature="futures"))]
+#[doc(inline)]
+pub use stream::*;
+
+#[cfg(feature="std")]
+mod pooled;
+
+#[cfg(feature="std")]
+mod callback;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use callback::{CallbackExecutor, CallbackJob};
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use pooled::{pooled_channel, PooledReceiver, PooledSender, ReturnSlotPool};
+
+#[cfg(feature="std")]
+mod unwind;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use unwind::{catch_unwind, catch_unwind_async, Panicked, Poisonable};
+
+#[cfg(feature="std")]
+mod intercept;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use intercept::{Interceptor, MethodInfo, Reject};
+
+#[cfg(feature="std")]
+mod metric;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use metric::{InMemoryMetrics, MethodStats, Metrics, SentAt};
+
+#[cfg(feature="metrics")]
+#[doc(inline)]
+pub use metric::MetricsFacade;
+
+#[cfg(feature="std")]
+mod recorder;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use recorder::Canned;
+
+#[cfg(feature="std")]
+mod mock;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use mock::Expectations;
+
+#[cfg(feature="std")]
+mod call_error;
+
+#[cfg(feature="std")]
+#[doc(inline)]
+pub use call_error::{CallError, NotAllowed};
+
+#[cfg(feature="tracing")]
+mod trace;
+
+#[cfg(feature="tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+pub use trace::TraceContext;
+
+/// Re-export for code generated in `trace` mode
+#[cfg(feature="tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+pub use tracing;
+
+#[cfg(feature="std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod actor;
+
+#[cfg(feature="std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod registry;
+
+#[cfg(feature="journal")]
+#[cfg_attr(docsrs, doc(cfg(feature = "journal")))]
+pub mod journal;
@@ -0,0 +1,205 @@
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::MethodInfo;
+
+/// Reports about sent and handled messages, specified by `metrics = Type` subparameter of `call_fn` or `proxy`.
+///
+/// Like [`Interceptor`](crate::Interceptor), hooks are associated functions keyed by static [`MethodInfo`]:
+/// use statics if they need state. See [`InMemoryMetrics`] and [`MetricsFacade`] for ready implementations.
+pub trait Metrics {
+    /// Proxy passed a message to its closure. `ok` is `false` if the closure returned an error.
+    fn sent(method: &MethodInfo, ok: bool) {
+        let _ = (method, ok);
+    }
+
+    /// Call function started handling a message that was created by a proxy `elapsed` ago.
+    /// Only reported with `sent_at` parameter.
+    fn queued(method: &MethodInfo, elapsed: Duration) {
+        let _ = (method, elapsed);
+    }
+
+    /// Call function handled a message. `elapsed` includes the method call and sending its return value.
+    fn handled(method: &MethodInfo, elapsed: Duration) {
+        let _ = (method, elapsed);
+    }
+
+    /// Handling failed: the call was rejected by an interceptor, the method panicked (with `catch_unwind`),
+    /// the object was poisoned (with `poison_on_panic`) or its return value could not be sent. Reported in addition to `handled`.
+    fn failed(method: &MethodInfo) {
+        let _ = method;
+    }
+}
+
+/// Moment when a proxy created the message, stored in `sent_at` field of each variant in `sent_at` mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SentAt(pub Instant);
+
+impl SentAt {
+    /// Stamp with current time
+    pub fn now() -> Self {
+        SentAt(Instant::now())
+    }
+
+    /// Time passed since the stamp
+    pub fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+impl Default for SentAt {
+    fn default() -> Self {
+        SentAt::now()
+    }
+}
+
+/// Counters collected by [`InMemoryMetrics`] for one method
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MethodStats {
+    /// Messages successfully passed to proxy's closure
+    pub sent: u64,
+    /// Messages that proxy's closure failed to send
+    pub send_errors: u64,
+    /// Messages handled by call functions
+    pub handled: u64,
+    /// Handled messages that failed (see [`Metrics::failed`])
+    pub failed: u64,
+    /// Sum of handling durations
+    pub total_latency: Duration,
+    /// Longest handling duration
+    pub max_latency: Duration,
+    /// Messages with known queue time
+    pub queued: u64,
+    /// Sum of queue times
+    pub total_queue_time: Duration,
+    /// Longest queue time
+    pub max_queue_time: Duration,
+}
+
+impl MethodStats {
+    /// Average handling duration, if anything was handled
+    pub fn mean_latency(&self) -> Option<Duration> {
+        mean(self.total_latency, self.handled)
+    }
+
+    /// Average queue time, if known for any message
+    pub fn mean_queue_time(&self) -> Option<Duration> {
+        mean(self.total_queue_time, self.queued)
+    }
+}
+
+fn mean(total: Duration, count: u64) -> Option<Duration> {
+    if count == 0 {
+        return None;
+    }
+    Some(Duration::from_nanos((total.as_nanos() / count as u128) as u64))
+}
+
+/// Keyed by enum name and method name
+static IN_MEMORY: Mutex<BTreeMap<(&'static str, &'static str), MethodStats>> = Mutex::new(BTreeMap::new());
+
+/// [`Metrics`] implementation accumulating [`MethodStats`] in a process-wide table, mostly for tests.
+///
+/// Statistics of all enums using it are kept apart by enum name.
+pub struct InMemoryMetrics;
+
+impl InMemoryMetrics {
+    fn update(method: &MethodInfo, f: impl FnOnce(&mut MethodStats)) {
+        let mut table = IN_MEMORY.lock().unwrap_or_else(|e| e.into_inner());
+        f(table.entry((method.enum_name, method.name)).or_default());
+    }
+
+    /// Statistics for the method `method` of enum `enum_name`. All zeroes if nothing was reported yet.
+    pub fn get(enum_name: &str, method: &str) -> MethodStats {
+        let table = IN_MEMORY.lock().unwrap_or_else(|e| e.into_inner());
+        table.get(&(enum_name, method)).copied().unwrap_or_default()
+    }
+
+    /// Statistics for all methods of enum `enum_name`, by method name
+    pub fn get_all(enum_name: &str) -> BTreeMap<&'static str, MethodStats> {
+        let table = IN_MEMORY.lock().unwrap_or_else(|e| e.into_inner());
+        table
+            .iter()
+            .filter(|((e, _), _)| *e == enum_name)
+            .map(|((_, m), s)| (*m, *s))
+            .collect()
+    }
+
+    /// Forget statistics of enum `enum_name`
+    pub fn reset(enum_name: &str) {
+        let mut table = IN_MEMORY.lock().unwrap_or_else(|e| e.into_inner());
+        table.retain(|(e, _), _| *e != enum_name);
+    }
+}
+
+impl Metrics for InMemoryMetrics {
+    fn sent(method: &MethodInfo, ok: bool) {
+        InMemoryMetrics::update(method, |s| {
+            if ok {
+                s.sent += 1;
+            } else {
+                s.send_errors += 1;
+            }
+        });
+    }
+
+    fn queued(method: &MethodInfo, elapsed: Duration) {
+        InMemoryMetrics::update(method, |s| {
+            s.queued += 1;
+            s.total_queue_time += elapsed;
+            s.max_queue_time = s.max_queue_time.max(elapsed);
+        });
+    }
+
+    fn handled(method: &MethodInfo, elapsed: Duration) {
+        InMemoryMetrics::update(method, |s| {
+            s.handled += 1;
+            s.total_latency += elapsed;
+            s.max_latency = s.max_latency.max(elapsed);
+        });
+    }
+
+    fn failed(method: &MethodInfo) {
+        InMemoryMetrics::update(method, |s| s.failed += 1);
+    }
+}
+
+/// [`Metrics`] implementation reporting to the [`metrics`](https://crates.io/crates/metrics) crate facade.
+///
+/// Counters `trait_enumizer_sent_total`, `trait_enumizer_send_errors_total`, `trait_enumizer_handled_total`,
+/// `trait_enumizer_failed_total` and histograms (in seconds) `trait_enumizer_latency_seconds`,
+/// `trait_enumizer_queue_seconds` are labelled with `enum` and `method`.
+#[cfg(feature="metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub struct MetricsFacade;
+
+#[cfg(feature="metrics")]
+impl Metrics for MetricsFacade {
+    fn sent(method: &MethodInfo, ok: bool) {
+        let name = if ok {
+            "trait_enumizer_sent_total"
+        } else {
+            "trait_enumizer_send_errors_total"
+        };
+        ::metrics::counter!(name, "enum" => method.enum_name, "method" => method.name).increment(1);
+    }
+
+    fn queued(method: &MethodInfo, elapsed: Duration) {
+        ::metrics::histogram!("trait_enumizer_queue_seconds", "enum" => method.enum_name, "method" => method.name)
+            .record(elapsed.as_secs_f64());
+    }
+
+    fn handled(method: &MethodInfo, elapsed: Duration) {
+        ::metrics::counter!("trait_enumizer_handled_total", "enum" => method.enum_name, "method" => method.name)
+            .increment(1);
+        ::metrics::histogram!("trait_enumizer_latency_seconds", "enum" => method.enum_name, "method" => method.name)
+            .record(elapsed.as_secs_f64());
+    }
+
+    fn failed(method: &MethodInfo) {
+        ::metrics::counter!("trait_enumizer_failed_total", "enum" => method.enum_name, "method" => method.name)
+            .increment(1);
+    }
+}
@@ -0,0 +1,133 @@
+use core::any::Any;
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::string::String;
+use std::sync::Mutex;
+use std::vec::Vec;
+
+type Predicate<M> = Box<dyn Fn(&M) -> bool + Send>;
+
+struct Expectation<M> {
+    description: String,
+    predicate: Predicate<M>,
+    reply: Option<Box<dyn Any + Send>>,
+}
+
+struct State<M> {
+    queue: VecDeque<Expectation<M>>,
+    calls: usize,
+    failures: Vec<String>,
+    verified: bool,
+}
+
+/// Ordered queue of expected calls, used by mocks generated with `mock` parameter.
+///
+/// Each call must match the oldest unmet expectation. Mismatches are collected and reported
+/// by [`Expectations::verify`] or when dropped, unless the call needs a return value, which panics right away.
+pub struct Expectations<M> {
+    mock_name: &'static str,
+    state: Mutex<State<M>>,
+}
+
+impl<M> Expectations<M> {
+    /// Empty queue for a mock named `mock_name`
+    pub fn new(mock_name: &'static str) -> Self {
+        Expectations {
+            mock_name,
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                calls: 0,
+                failures: Vec::new(),
+                verified: false,
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State<M>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Expect a call that satisfies `predicate`, replying with `reply` (which must be of method's return type)
+    pub fn push(
+        &self,
+        description: String,
+        predicate: impl Fn(&M) -> bool + Send + 'static,
+        reply: Option<Box<dyn Any + Send>>,
+    ) {
+        let mut state = self.state();
+        state.verified = false;
+        state.queue.push_back(Expectation {
+            description,
+            predicate: Box::new(predicate),
+            reply,
+        });
+    }
+
+    /// Check `msg` (described as `description`) against the next expectation, returning its reply.
+    /// Returns `None` if the call was not expected.
+    pub fn call(&self, msg: &M, description: String) -> Option<Box<dyn Any + Send>> {
+        let mut state = self.state();
+        state.calls += 1;
+        let n = state.calls;
+        match state.queue.front() {
+            Some(e) if (e.predicate)(msg) => state.queue.pop_front().unwrap().reply.or_else(|| Some(Box::new(()))),
+            Some(e) => {
+                let failure = std::format!("call #{}:\n    expected: {}\n    actual:   {}", n, e.description, description);
+                state.failures.push(failure);
+                None
+            }
+            None => {
+                state.failures.push(std::format!("call #{}: unexpected {}", n, description));
+                None
+            }
+        }
+    }
+
+    /// Like [`Expectations::call`], but panic with the report right away if the call was not expected
+    pub fn call_needing_reply<T: 'static>(&self, msg: &M, description: String) -> T {
+        match self.call(msg, description) {
+            Some(reply) => match reply.downcast::<T>() {
+                Ok(reply) => *reply,
+                Err(_) => panic!("trait-enumizer: {}: canned reply has wrong type", self.mock_name),
+            },
+            None => self.fail(),
+        }
+    }
+
+    /// Panic with the report if any call mismatched or any expectation is left unmet
+    pub fn verify(&self) {
+        let mut state = self.state();
+        state.verified = true;
+        if state.failures.is_empty() && state.queue.is_empty() {
+            return;
+        }
+        drop(state);
+        self.fail()
+    }
+
+    fn fail(&self) -> ! {
+        let mut state = self.state();
+        state.verified = true;
+        let mut report = std::format!("trait-enumizer: {} expectations failed", self.mock_name);
+        for failure in &state.failures {
+            report.push_str("\n  ");
+            report.push_str(failure);
+        }
+        for e in &state.queue {
+            report.push_str("\n  unmet: ");
+            report.push_str(&e.description);
+        }
+        state.failures.clear();
+        state.queue.clear();
+        drop(state);
+        panic!("{}", report)
+    }
+}
+
+impl<M> Drop for Expectations<M> {
+    fn drop(&mut self) {
+        if !self.state().verified && !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
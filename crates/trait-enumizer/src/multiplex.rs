@@ -0,0 +1,122 @@
+use core::marker::PhantomData;
+
+/// Proxy sending values of several enums through one closure, which accepts wrapper enum `M` generated by [`multiplex!`](crate::multiplex).
+///
+/// Implements a trait (and its resultified trait) if the trait's proxy has `multiplexed` subparameter and its enum converts into `M`.
+pub struct MuxProxy<M, F>(pub F, PhantomData<fn(M)>);
+
+impl<M, F> MuxProxy<M, F> {
+    /// Wrap closure accepting `M`
+    pub fn new(f: F) -> Self {
+        MuxProxy(f, PhantomData)
+    }
+}
+
+impl<M, F: Clone> Clone for MuxProxy<M, F> {
+    fn clone(&self) -> Self {
+        MuxProxy::new(self.0.clone())
+    }
+}
+
+/// Generate enum wrapping several enums from `enumizer`, so that one actor can serve all their traits over one channel.
+///
+/// ```rust,ignore
+/// trait_enumizer::multiplex!(pub ServiceMsg = FooEnum, BarEnum, BazEnum);
+/// ```
+///
+/// generates
+///
+/// ```rust,ignore
+/// pub enum ServiceMsg { FooEnum(FooEnum), BarEnum(BarEnum), BazEnum(BazEnum) }
+/// impl From<FooEnum> for ServiceMsg { ... } // and others
+/// impl ServiceMsg {
+///     pub fn call<I: ?Sized>(self, o: &mut I) -> Control where FooEnum: Dispatch<I>, ...;
+///     pub fn proxy<F>(f: F) -> MuxProxy<ServiceMsg, F>;
+/// }
+/// impl Message for ServiceMsg { ... }
+/// impl<I: ?Sized> Dispatch<I> for ServiceMsg where FooEnum: Dispatch<I>, ... { ... }
+/// impl<I: ?Sized + Send> AsyncDispatch<I> for ServiceMsg where FooEnum: AsyncDispatch<I>, ... { ... }
+/// ```
+///
+/// Wrapped enums need `call_fn(..., dispatch)`. Attributes before the name (e.g. `#[derive(Debug)]`) are applied to the enum.
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! multiplex {
+    ($(#[$attr:meta])* $vis:vis $name:ident = $($inner:ident),+ $(,)?) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                #[allow(missing_docs)]
+                $inner($inner),
+            )+
+        }
+
+        $(
+            impl ::core::convert::From<$inner> for $name {
+                fn from(x: $inner) -> Self {
+                    $name::$inner(x)
+                }
+            }
+        )+
+
+        impl $name {
+            /// Call the method specified by the wrapped enum value
+            #[allow(dead_code)]
+            $vis fn call<I: ?::core::marker::Sized>(self, o: &mut I) -> $crate::Control
+            where
+                $($inner: $crate::actor::Dispatch<I>,)+
+            {
+                match self {
+                    $($name::$inner(x) => $crate::actor::Dispatch::dispatch(x, o),)+
+                }
+            }
+
+            /// Proxy implementing traits of all wrapped enums that have `multiplexed` proxies, sending through `f`
+            #[allow(dead_code)]
+            $vis fn proxy<F>(f: F) -> $crate::MuxProxy<$name, F> {
+                $crate::MuxProxy::new(f)
+            }
+        }
+
+        impl $crate::actor::Message for $name {
+            fn priority(&self) -> $crate::Priority {
+                match self {
+                    $($name::$inner(x) => $crate::actor::Message::priority(x),)+
+                }
+            }
+
+            const COALESCING: bool = false $(|| <$inner as $crate::actor::Message>::COALESCING)+;
+
+            fn coalesces_with(&self, queued: &Self) -> bool {
+                match (self, queued) {
+                    $(($name::$inner(x), $name::$inner(y)) => $crate::actor::Message::coalesces_with(x, y),)+
+                    #[allow(unreachable_patterns)]
+                    _ => false,
+                }
+            }
+        }
+
+        impl<I: ?::core::marker::Sized> $crate::actor::Dispatch<I> for $name
+        where
+            $($inner: $crate::actor::Dispatch<I>,)+
+        {
+            fn dispatch(self, o: &mut I) -> $crate::Control {
+                self.call(o)
+            }
+        }
+
+        impl<I: ?::core::marker::Sized + ::core::marker::Send> $crate::actor::AsyncDispatch<I> for $name
+        where
+            $($inner: $crate::actor::AsyncDispatch<I>,)+
+        {
+            fn dispatch_async(self, o: &mut I) -> impl ::core::future::Future<Output = $crate::Control> + ::core::marker::Send {
+                async move {
+                    match self {
+                        $($name::$inner(x) => $crate::actor::AsyncDispatch::dispatch_async(x, o).await,)+
+                    }
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,37 @@
+use core::future::{Future, IntoFuture};
+
+/// Reply to a proxy call that was already sent, but not waited for yet.
+/// Returned by `try_*_deferred` proxy methods, which allows to send several requests before collecting the answers.
+///
+/// Use [`wait`](PendingReply::wait) for sync proxies and `.await` for async proxies.
+#[must_use = "Reply is only received when you `wait()` or `.await` it"]
+pub struct PendingReply<F>(F);
+
+impl<F> PendingReply<F> {
+    /// Wrap a closure (for sync proxies) or a future (for async proxies) that receives the reply
+    pub fn new(f: F) -> Self {
+        PendingReply(f)
+    }
+}
+
+impl<T, F: FnOnce() -> T> PendingReply<F> {
+    /// Block until the reply arrives
+    pub fn wait(self) -> T {
+        (self.0)()
+    }
+}
+
+impl<Fu: Future> IntoFuture for PendingReply<Fu> {
+    type Output = Fu::Output;
+    type IntoFuture = Fu;
+
+    fn into_future(self) -> Fu {
+        self.0
+    }
+}
+
+impl<F> core::fmt::Debug for PendingReply<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PendingReply").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,200 @@
+use std::any::{Any, TypeId};
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::mpsc::RecvError;
+use std::sync::{Arc, Condvar, Mutex};
+use std::vec::Vec;
+
+use crate::FailedToSendReturnValue;
+
+/// How many idle return slots of each type a pool keeps before freeing extra ones.
+const MAX_IDLE_SLOTS_PER_TYPE: usize = 64;
+
+enum SlotState<T> {
+    Waiting,
+    Ready(T),
+    SenderGone,
+    ReceiverGone,
+}
+
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+    cond: Condvar,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Arc<Self> {
+        Arc::new(Slot {
+            state: Mutex::new(SlotState::Waiting),
+            cond: Condvar::new(),
+        })
+    }
+}
+
+/// Idle return slots, one free list per return type.
+#[derive(Default)]
+struct FreeLists(HashMap<TypeId, Box<dyn Any + Send>>);
+
+impl FreeLists {
+    fn take<T: Send + 'static>(&mut self) -> Arc<Slot<T>> {
+        self.0
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|l| l.downcast_mut::<Vec<Arc<Slot<T>>>>().unwrap().pop())
+            .unwrap_or_else(Slot::new)
+    }
+
+    fn put<T: Send + 'static>(&mut self, slot: Arc<Slot<T>>) {
+        let l = self
+            .0
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Arc<Slot<T>>>::new()))
+            .downcast_mut::<Vec<Arc<Slot<T>>>>()
+            .unwrap();
+        if l.len() < MAX_IDLE_SLOTS_PER_TYPE {
+            l.push(slot);
+        }
+    }
+}
+
+std::thread_local! {
+    static LOCAL_POOL: RefCell<FreeLists> = RefCell::new(FreeLists::default());
+}
+
+/// Sending half of a pooled return channel. Used as `ret` field type by [`pooled_class`](crate::pooled_class).
+pub struct PooledSender<T> {
+    slot: Option<Arc<Slot<T>>>,
+}
+
+/// Receiving half of a pooled return channel.
+/// Return slot goes back to the pool after [`recv`](PooledReceiver::recv) or [`recv_into`](PooledReceiver::recv_into).
+pub struct PooledReceiver<T> {
+    slot: Option<Arc<Slot<T>>>,
+}
+
+impl<T> PooledSender<T> {
+    /// Deliver the return value. Fails if the receiver is already gone.
+    pub fn send(mut self, value: T) -> Result<(), FailedToSendReturnValue> {
+        let slot = self.slot.take().unwrap();
+        let mut state = slot.state.lock().unwrap();
+        if let SlotState::ReceiverGone = *state {
+            return Err(FailedToSendReturnValue);
+        }
+        *state = SlotState::Ready(value);
+        drop(state);
+        slot.cond.notify_one();
+        Ok(())
+    }
+}
+
+impl<T> Drop for PooledSender<T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            let mut state = slot.state.lock().unwrap();
+            if let SlotState::Waiting = *state {
+                *state = SlotState::SenderGone;
+            }
+            drop(state);
+            slot.cond.notify_one();
+        }
+    }
+}
+
+impl<T: Send + 'static> PooledReceiver<T> {
+    /// Wait for the return value, then recycle the slot into the current thread's pool.
+    pub fn recv(mut self) -> Result<T, RecvError> {
+        let ret = self.wait();
+        let slot = self.slot.take().unwrap();
+        LOCAL_POOL.with(|p| p.borrow_mut().put(slot));
+        ret
+    }
+
+    /// Wait for the return value, then recycle the slot into the specified pool.
+    pub fn recv_into(mut self, pool: &ReturnSlotPool) -> Result<T, RecvError> {
+        let ret = self.wait();
+        let slot = self.slot.take().unwrap();
+        pool.0.lock().unwrap().put(slot);
+        ret
+    }
+
+    /// Leaves the slot in `Waiting` state, ready for reuse (sender is known to be gone by then).
+    fn wait(&mut self) -> Result<T, RecvError> {
+        let slot = self.slot.as_ref().unwrap();
+        let mut state = slot.state.lock().unwrap();
+        loop {
+            match core::mem::replace(&mut *state, SlotState::Waiting) {
+                SlotState::Ready(x) => return Ok(x),
+                SlotState::SenderGone => return Err(RecvError),
+                SlotState::Waiting => state = slot.cond.wait(state).unwrap(),
+                SlotState::ReceiverGone => unreachable!(),
+            }
+        }
+    }
+}
+
+impl<T> Drop for PooledReceiver<T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            let mut state = slot.state.lock().unwrap();
+            *state = SlotState::ReceiverGone;
+        }
+    }
+}
+
+/// Create return channel, reusing an idle slot from the current thread's pool if there is one.
+pub fn pooled_channel<T: Send + 'static>() -> (PooledSender<T>, PooledReceiver<T>) {
+    let slot = LOCAL_POOL.with(|p| p.borrow_mut().take::<T>());
+    pair(slot)
+}
+
+fn pair<T>(slot: Arc<Slot<T>>) -> (PooledSender<T>, PooledReceiver<T>) {
+    (
+        PooledSender {
+            slot: Some(slot.clone()),
+        },
+        PooledReceiver { slot: Some(slot) },
+    )
+}
+
+/// Explicit shareable pool of return slots. Cheap to clone: clones refer to the same pool.
+///
+/// Use it as `extra_field_type(trait_enumizer::ReturnSlotPool)` of a proxy with `returnval=pooled_class`
+/// to share recycled slots between threads instead of using per-thread pools.
+#[derive(Clone, Default)]
+pub struct ReturnSlotPool(Arc<Mutex<FreeLists>>);
+
+impl ReturnSlotPool {
+    /// Create new empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create return channel, reusing an idle slot from this pool if there is one.
+    pub fn channel<T: Send + 'static>(&self) -> (PooledSender<T>, PooledReceiver<T>) {
+        let slot = self.0.lock().unwrap().take::<T>();
+        pair(slot)
+    }
+}
+
+impl core::fmt::Debug for ReturnSlotPool {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ReturnSlotPool").finish_non_exhaustive()
+    }
+}
+
+/// Channel class that recycles return slots instead of allocating a new channel for each call. Sync-only.
+///
+/// By default slots are taken from (and returned to) a thread-local pool of the thread calling the proxy.
+/// Add `extra_field_type(trait_enumizer::ReturnSlotPool)` to the proxy to use explicit shared pool instead.
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+macro_rules! pooled_class {
+    (Sender<$T:ty>) => { $crate::PooledSender<$T> };
+    (SendError) => { $crate::FailedToSendReturnValue };
+    (RecvError) => { ::std::sync::mpsc::RecvError };
+    (create::<$T:ty>()) => { $crate::pooled_channel::<$T>() };
+    (create::<$T:ty>($pool:expr)) => { ($pool).channel::<$T>() };
+    (send::<$T:ty>($channel:expr, $msg:expr $(, $extraarg:expr)?)) => { ($channel).send($msg) };
+    (recv::<$T:ty>($channel:expr)) => { ($channel).recv() };
+    (recv::<$T:ty>($channel:expr, $pool:expr)) => { ($channel).recv_into(&$pool) };
+}
@@ -0,0 +1,23 @@
+/// Message priority, as specified by `#[enumizer_priority(...)]`. Higher priorities compare greater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// `#[enumizer_priority(low)]`
+    Low,
+    /// Methods without `enumizer_priority` attribute
+    #[default]
+    Normal,
+    /// `#[enumizer_priority(high)]`
+    High,
+}
+
+impl Priority {
+    /// Number of priority levels
+    #[cfg(feature = "std")]
+    pub(crate) const COUNT: usize = 3;
+
+    /// Index from `0` (`Low`) to `COUNT - 1` (`High`)
+    #[cfg(feature = "std")]
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
@@ -0,0 +1,56 @@
+use std::boxed::Box;
+use std::collections::VecDeque;
+
+type Responder<M, T> = Box<dyn FnMut(&M) -> T + Send>;
+
+/// Return values for one method of a generated recorder (see `recorder` parameter).
+///
+/// Queued values are used first, then the responder closure, which sees the recorded enum value.
+pub struct Canned<M, T> {
+    queue: VecDeque<T>,
+    responder: Option<Responder<M, T>>,
+}
+
+impl<M, T> Default for Canned<M, T> {
+    fn default() -> Self {
+        Canned {
+            queue: VecDeque::new(),
+            responder: None,
+        }
+    }
+}
+
+impl<M, T> Canned<M, T> {
+    /// Queue a return value for one call
+    pub fn push(&mut self, value: T) -> &mut Self {
+        self.queue.push_back(value);
+        self
+    }
+
+    /// Produce return values with `f` once the queue is exhausted
+    pub fn respond_with(&mut self, f: impl FnMut(&M) -> T + Send + 'static) -> &mut Self {
+        self.responder = Some(Box::new(f));
+        self
+    }
+
+    /// Number of queued values left
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether no queued values left
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Return value for call `msg` of `method`. Panics if there is nothing to return.
+    pub fn next(&mut self, msg: &M, method: &str) -> T {
+        if let Some(value) = self.queue.pop_front() {
+            return value;
+        }
+        match &mut self.responder {
+            Some(f) => f(msg),
+            None => panic!("trait-enumizer: no canned return value for `{}`", method),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Many objects implementing the same trait, addressed by id.
+//!
+//! Proxies with `object_id(<type>)` subparameter pass `(id, enum_value)` tuples to their closures.
+//! Such tuples implement [`Dispatch`] for [`Registry`], so a registry can be run as a single actor
+//! serving all its objects over one channel:
+//!
+//! ```rust,ignore
+//! #[trait_enumizer::enumizer(name=QqqEnum, returnval=stdmpsc_class, call_errors, call_fn(name=try_call_mut,ref_mut,dispatch), proxy(Fn,name=QqqProxy,resultified_trait=QqqResultified,object_id(u32)))]
+//! trait Qqq { ... }
+//!
+//! let mut registry = Registry::new();
+//! registry.insert(1, obj);
+//! let (handle, join) = trait_enumizer::actor::spawn_thread::<(u32, QqqEnum), _>(registry, Mailbox::Unbounded);
+//! let p = QqqProxy(handle.sink(), 1);
+//! ```
+//!
+//! Methods marked `#[enumizer_handle(SubEnum, SubProxy)]` keep objects they return in callee's [`Handles`],
+//! replying [`HandleId`] instead. Trait impls of proxies turn it into `SubProxy` bound to that id,
+//! whose calls travel in `<Method>Handle` variants of the same enum. Dropping the sub-proxy releases the object.
+
+use core::borrow::Borrow;
+use core::hash::Hash;
+use core::ops::DerefMut;
+use std::collections::HashMap;
+
+use crate::actor::{Dispatch, Message};
+use crate::{CallError, Control, Priority};
+
+/// Enum value that can be answered with an error instead of being handled. Implemented for enums that have proxies with `object_id`.
+pub trait Refuse {
+    /// Reply `error` to the return channel (if any), without calling the method
+    fn refuse(self, error: CallError);
+}
+
+/// Objects implementing the same trait, keyed by id
+#[derive(Debug)]
+pub struct Registry<Id, I> {
+    objects: HashMap<Id, I>,
+}
+
+impl<Id, I> Default for Registry<Id, I> {
+    fn default() -> Self {
+        Registry { objects: HashMap::new() }
+    }
+}
+
+impl<Id: Eq + Hash, I> Registry<Id, I> {
+    /// Empty registry
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add an object, returning the previous one with the same id
+    pub fn insert(&mut self, id: Id, o: I) -> Option<I> {
+        self.objects.insert(id, o)
+    }
+
+    /// Remove an object. Later messages addressed to it get [`CallError::NoSuchObject`].
+    pub fn remove<Q: Eq + Hash + ?Sized>(&mut self, id: &Q) -> Option<I>
+    where
+        Id: Borrow<Q>,
+    {
+        self.objects.remove(id)
+    }
+
+    /// Object with the given id
+    pub fn get<Q: Eq + Hash + ?Sized>(&self, id: &Q) -> Option<&I>
+    where
+        Id: Borrow<Q>,
+    {
+        self.objects.get(id)
+    }
+
+    /// Object with the given id
+    pub fn get_mut<Q: Eq + Hash + ?Sized>(&mut self, id: &Q) -> Option<&mut I>
+    where
+        Id: Borrow<Q>,
+    {
+        self.objects.get_mut(id)
+    }
+
+    /// Number of objects
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Whether there are no objects
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Iterate over ids and objects in arbitrary order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Id, &mut I)> {
+        self.objects.iter_mut()
+    }
+
+    /// Call the method specified by `msg` on the object with the given id.
+    ///
+    /// If there is no such object, replies [`CallError::NoSuchObject`] to `msg`'s return channel and returns `Err`.
+    pub fn dispatch<Q: Eq + Hash + ?Sized, M: Dispatch<I> + Refuse>(&mut self, id: &Q, msg: M) -> Result<Control, NoSuchObject>
+    where
+        Id: Borrow<Q>,
+    {
+        match self.objects.get_mut(id) {
+            Some(o) => Ok(msg.dispatch(o)),
+            None => {
+                msg.refuse(CallError::NoSuchObject);
+                Err(NoSuchObject)
+            }
+        }
+    }
+}
+
+impl<Id: PartialEq, M: Message> Message for (Id, M) {
+    fn priority(&self) -> Priority {
+        self.1.priority()
+    }
+
+    const COALESCING: bool = M::COALESCING;
+
+    /// Only values addressed to the same object coalesce
+    fn coalesces_with(&self, queued: &Self) -> bool {
+        self.0 == queued.0 && self.1.coalesces_with(&queued.1)
+    }
+}
+
+impl<Id: Eq + Hash, I, M: Dispatch<I> + Refuse> Dispatch<Registry<Id, I>> for (Id, M) {
+    fn dispatch(self, registry: &mut Registry<Id, I>) -> Control {
+        registry.dispatch(&self.0, self.1).unwrap_or(Control::Continue)
+    }
+}
+
+/// Error of [`Registry::dispatch`] when there is no object with the given id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NoSuchObject;
+impl core::fmt::Display for NoSuchObject {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "trait-enumizer: no such object")
+    }
+}
+impl std::error::Error for NoSuchObject {}
+
+/// Id of an object in [`Handles`]
+pub type HandleId = u64;
+
+/// Objects returned by `#[enumizer_handle]` methods, kept by the callee until callers release them
+#[derive(Debug)]
+pub struct Handles<I> {
+    objects: Registry<HandleId, I>,
+    next_id: HandleId,
+}
+
+impl<I> Default for Handles<I> {
+    fn default() -> Self {
+        Handles { objects: Registry::new(), next_id: 0 }
+    }
+}
+
+impl<I> Handles<I> {
+    /// Empty set of handles
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Keep an object, returning a new id for it. Ids are not reused.
+    pub fn register(&mut self, o: I) -> HandleId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.objects.insert(id, o);
+        id
+    }
+
+    /// Remove an object. Later messages addressed to it get [`CallError::NoSuchObject`].
+    pub fn release(&mut self, id: HandleId) -> Option<I> {
+        self.objects.remove(&id)
+    }
+
+    /// Object with the given id
+    pub fn get(&self, id: HandleId) -> Option<&I> {
+        self.objects.get(&id)
+    }
+
+    /// Object with the given id
+    pub fn get_mut(&mut self, id: HandleId) -> Option<&mut I> {
+        self.objects.get_mut(&id)
+    }
+
+    /// Number of objects not released yet
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Whether all objects are released
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl<I: DerefMut> Handles<I> {
+    /// Call the method specified by `msg` on the object (like `dyn Trait` in a `Box`) with the given id.
+    ///
+    /// If there is no such object, replies [`CallError::NoSuchObject`] to `msg`'s return channel and returns `Err`.
+    pub fn dispatch<M: Dispatch<I::Target> + Refuse>(&mut self, id: HandleId, msg: M) -> Result<Control, NoSuchObject> {
+        match self.objects.get_mut(&id) {
+            Some(o) => Ok(msg.dispatch(&mut **o)),
+            None => {
+                msg.refuse(CallError::NoSuchObject);
+                Err(NoSuchObject)
+            }
+        }
+    }
+}
+
+/// Implemented by objects whose trait has `#[enumizer_handle]` methods returning `I`, so that call functions can keep returned objects
+pub trait HoldsHandles<I> {
+    /// Where returned objects are kept
+    fn handles(&mut self) -> &mut Handles<I>;
+}
+
+/// Message for an object in [`Handles`], carried by `<Method>Handle` variants
+#[derive(Debug)]
+pub enum HandleMsg<M> {
+    /// Call a method of the object
+    Call(M),
+    /// Drop the object. Sent when the sub-proxy is dropped.
+    Release,
+}
+
+/// Calls the closure when dropped. Sub-proxies hold it to release their objects.
+pub struct ReleaseGuard<R: FnOnce()>(Option<R>);
+
+impl<R: FnOnce()> ReleaseGuard<R> {
+    /// Guard calling `release` on drop
+    pub fn new(release: R) -> Self {
+        ReleaseGuard(Some(release))
+    }
+}
+
+impl<R: FnOnce()> Drop for ReleaseGuard<R> {
+    fn drop(&mut self) {
+        if let Some(release) = self.0.take() {
+            release();
+        }
+    }
+}
@@ -17,6 +17,7 @@ impl core::fmt::Display for FailedToSendReturnValue {
 impl std::error::Error for FailedToSendReturnValue {}
 
 /// Channel class for using `flume::bounded(1)` to deliver return values. Supports both sync and async.
+/// Also supports streamed return values, using `flume::bounded(16)` and [`FlumeItems`](crate::FlumeItems).
 #[cfg(feature = "flume")]
 #[cfg_attr(docsrs, doc(cfg(feature = "flume")))]
 #[macro_export]
@@ -29,6 +30,14 @@ macro_rules! flume_class {
     (recv::<$T:ty>($channel:expr)) => { ($channel).recv() };
     (send_async::<$T:ty>($channel:expr, $msg:expr)) => { ($channel).send_async($msg).await.map_err(|_| $crate::FailedToSendReturnValue) };
     (recv_async::<$T:ty>($channel:expr)) => { ($channel).recv_async().await };
+    (StreamSender<$T:ty>) => { ::flume::Sender<::core::option::Option<$T>> };
+    (StreamReceiver<$T:ty>) => { $crate::FlumeItems<$T> };
+    (create_stream::<$T:ty>()) => { ::flume::bounded(16) };
+    (send_item::<$T:ty>($channel:expr, $msg:expr)) => { ($channel).send(::core::option::Option::Some($msg)).map_err(|_| $crate::FailedToSendReturnValue) };
+    (send_end::<$T:ty>($channel:expr)) => { ($channel).send(::core::option::Option::None).map_err(|_| $crate::FailedToSendReturnValue) };
+    (send_item_async::<$T:ty>($channel:expr, $msg:expr)) => { ($channel).send_async(::core::option::Option::Some($msg)).await.map_err(|_| $crate::FailedToSendReturnValue) };
+    (send_end_async::<$T:ty>($channel:expr)) => { ($channel).send_async(::core::option::Option::None).await.map_err(|_| $crate::FailedToSendReturnValue) };
+    (recv_stream::<$T:ty>($channel:expr)) => { $crate::FlumeItems::new($channel) };
 }
 #[cfg(feature = "crossbeam-channel")]
 #[cfg_attr(docsrs, doc(cfg(feature = "crossbeam-channel")))]
@@ -101,3 +110,26 @@ macro_rules! stdmpsc_class {
     (send::<$T:ty>($channel:expr, $msg:expr)) => { ($channel).send($msg).map_err(|_| $crate::FailedToSendReturnValue) };
     (recv::<$T:ty>($channel:expr)) => { ($channel).recv() };
 }
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+/// Channel class where return value is delivered by calling a boxed `FnOnce(T)` continuation instead of sending to a channel.
+/// Use `callbacks` proxy subparameter to get non-blocking `try_*_then` methods accepting the continuation.
+/// Usual blocking `try_*` methods are also supported, using `std::sync::mpsc::sync_channel(1)` behind the callback. Sync-only.
+///
+/// With `extra_field_type(trait_enumizer::CallbackExecutor)` continuations are handed to the executor instead of being called by the callee.
+macro_rules! callback_class {
+    (Sender<$T:ty>) => { ::std::boxed::Box<dyn ::core::ops::FnOnce($T) + ::core::marker::Send> };
+    (SendError) => { $crate::FailedToSendReturnValue };
+    (RecvError) => { ::std::sync::mpsc::RecvError };
+    (callback::<$T:ty>($f:expr)) => { ::std::boxed::Box::new($f) };
+    (callback::<$T:ty>($f:expr, $executor:expr)) => { $crate::CallbackExecutor::wrap::<$T>(&$executor, $f) };
+    (create::<$T:ty>($($executor:expr)?)) => { {
+        let (tx, rx) = ::std::sync::mpsc::sync_channel::<$T>(1);
+        let tx: $crate::callback_class!(Sender<$T>) = ::std::boxed::Box::new(move |x| { let _ = tx.send(x); });
+        (tx, rx)
+    } };
+    (send::<$T:ty>($channel:expr, $msg:expr $(, $extraarg:expr)?)) => { { ($channel)($msg); ::core::result::Result::<(), $crate::FailedToSendReturnValue>::Ok(()) } };
+    (recv::<$T:ty>($channel:expr $(, $executor:expr)?)) => { ($channel).recv() };
+}
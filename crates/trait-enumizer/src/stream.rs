@@ -0,0 +1,78 @@
+/// Receiving side of a streamed return value for [`flume_class`](crate::flume_class).
+///
+/// Yields `Ok(item)` for each item, then ends when callee signals end of stream.
+/// If callee side disappears before that, yields single `Err(RecvError)` and ends.
+#[cfg(feature = "flume")]
+#[cfg_attr(docsrs, doc(cfg(feature = "flume")))]
+pub struct FlumeItems<T> {
+    rx: flume::Receiver<Option<T>>,
+    done: bool,
+}
+
+#[cfg(feature = "flume")]
+impl<T> FlumeItems<T> {
+    /// Wrap receiver of a channel where `None` means end of stream
+    pub fn new(rx: flume::Receiver<Option<T>>) -> Self {
+        FlumeItems { rx, done: false }
+    }
+
+    fn handle(&mut self, x: Result<Option<T>, flume::RecvError>) -> Option<Result<T, flume::RecvError>> {
+        match x {
+            Ok(Some(x)) => Some(Ok(x)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// Async version of [`Iterator::next`]
+    pub async fn next_async(&mut self) -> Option<Result<T, flume::RecvError>> {
+        if self.done {
+            return None;
+        }
+        let x = self.rx.recv_async().await;
+        self.handle(x)
+    }
+
+    /// Convert to [`futures::Stream`]
+    #[cfg(feature = "futures")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "futures")))]
+    pub fn into_stream(self) -> impl futures::Stream<Item = Result<T, flume::RecvError>> {
+        futures::stream::unfold(self, |mut items| async move {
+            let x = items.next_async().await?;
+            Some((x, items))
+        })
+    }
+}
+
+#[cfg(feature = "flume")]
+impl<T> Iterator for FlumeItems<T> {
+    type Item = Result<T, flume::RecvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let x = self.rx.recv();
+        self.handle(x)
+    }
+}
+
+#[cfg(feature = "flume")]
+impl<T> core::fmt::Debug for FlumeItems<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FlumeItems").field("done", &self.done).finish_non_exhaustive()
+    }
+}
+
+/// Get next item of a pinned stream. Used by async call functions for methods returning `impl Stream`.
+#[cfg(feature = "futures")]
+#[cfg_attr(docsrs, doc(cfg(feature = "futures")))]
+pub async fn next_item<S: futures::Stream + ?Sized>(mut s: core::pin::Pin<&mut S>) -> Option<S::Item> {
+    core::future::poll_fn(|cx| s.as_mut().poll_next(cx)).await
+}
@@ -0,0 +1,61 @@
+use tracing::Span;
+
+/// Span of the caller, carried inside enum values in `trace` mode.
+///
+/// Proxies capture the span of the proxy method; call functions open method's span as its child,
+/// so that traces continue across threads (e.g. into actors).
+///
+/// All contexts compare equal. With `serde` crate feature they are serialized as unit and deserialized
+/// as [`TraceContext::current`], as spans do not travel across processes. So enums can still derive
+/// `PartialEq`, `Serialize` or `Deserialize` with `enum_attr`.
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    span: Span,
+}
+
+impl TraceContext {
+    /// Capture [`Span::current`]
+    pub fn current() -> Self {
+        TraceContext {
+            span: Span::current(),
+        }
+    }
+
+    /// Use specified span as the parent of method's span in call function
+    pub fn from_span(span: Span) -> Self {
+        TraceContext { span }
+    }
+
+    /// Captured span
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        TraceContext::current()
+    }
+}
+
+impl PartialEq for TraceContext {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl Eq for TraceContext {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TraceContext {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TraceContext {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <()>::deserialize(deserializer)?;
+        Ok(TraceContext::default())
+    }
+}
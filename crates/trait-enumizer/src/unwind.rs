@@ -0,0 +1,106 @@
+use core::future::Future;
+use core::panic::AssertUnwindSafe;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+use std::any::Any;
+use std::boxed::Box;
+use std::string::{String, ToString};
+
+/// Panic caught by a `call_fn(..., catch_unwind)` call function. Sent back to the caller as [`CallError::Panicked`](crate::CallError::Panicked).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Panicked {
+    message: String,
+}
+
+impl Panicked {
+    /// Extract message from panic payload, as returned by [`std::panic::catch_unwind`]
+    pub fn new(payload: &(dyn Any + Send)) -> Self {
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        Panicked { message }
+    }
+
+    /// Panic message, if it was a string
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl core::fmt::Display for Panicked {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "trait-enumizer: method panicked: {}", self.message)
+    }
+}
+impl std::error::Error for Panicked {}
+
+/// Object for `call_fn(..., catch_unwind, poison_on_panic)` call functions, which mark it poisoned when one of its methods panics.
+/// Later calls are not passed to a poisoned object: methods with return values reply [`CallError::Poisoned`](crate::CallError::Poisoned),
+/// calls without return values are dropped.
+#[derive(Debug, Default)]
+pub struct Poisonable<I: ?Sized> {
+    poisoned: AtomicBool,
+    inner: I,
+}
+
+impl<I> Poisonable<I> {
+    /// Wrap healthy object
+    pub fn new(inner: I) -> Self {
+        Poisonable { poisoned: AtomicBool::new(false), inner }
+    }
+
+    /// Get the object back, poisoned or not
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: ?Sized> Poisonable<I> {
+    /// Whether one of object's methods has panicked
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Object, poisoned or not
+    pub fn get_ref(&self) -> &I {
+        &self.inner
+    }
+
+    /// Object, poisoned or not
+    pub fn get_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Used by generated call functions
+    #[doc(hidden)]
+    pub fn split(&self) -> (&I, &AtomicBool) {
+        (&self.inner, &self.poisoned)
+    }
+
+    /// Used by generated call functions
+    #[doc(hidden)]
+    pub fn split_mut(&mut self) -> (&mut I, &AtomicBool) {
+        (&mut self.inner, &self.poisoned)
+    }
+}
+
+/// [`std::panic::catch_unwind`] for generated code, which does not care about unwind safety:
+/// it is up to `poison_on_panic` whether the object is used after a panic.
+pub fn catch_unwind<T>(f: impl FnOnce() -> T) -> Result<T, Box<dyn Any + Send>> {
+    std::panic::catch_unwind(AssertUnwindSafe(f))
+}
+
+/// Async version of [`catch_unwind`]: catch panics when polling `f`.
+pub async fn catch_unwind_async<F: Future>(f: F) -> Result<F::Output, Box<dyn Any + Send>> {
+    let mut f = core::pin::pin!(f);
+    core::future::poll_fn(|cx| match catch_unwind(|| f.as_mut().poll(cx)) {
+        Ok(Poll::Ready(x)) => Poll::Ready(Ok(x)),
+        Ok(Poll::Pending) => Poll::Pending,
+        Err(e) => Poll::Ready(Err(e)),
+    })
+    .await
+}
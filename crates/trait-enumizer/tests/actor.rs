@@ -0,0 +1,124 @@
+#![cfg(feature="std")]
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=CounterEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=CounterProxy,unwrapping_impl),
+)]
+trait Counter {
+    fn add(&mut self, x: i32);
+    fn get(&self) -> i32;
+}
+
+#[derive(Default)]
+struct Implementor {
+    total: i32,
+}
+
+impl Counter for Implementor {
+    fn add(&mut self, x: i32) {
+        self.total = self.total.checked_add(x).unwrap();
+    }
+
+    fn get(&self) -> i32 {
+        self.total
+    }
+}
+
+#[test]
+fn thread_actor() {
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<CounterEnum, _>(Implementor::default(), Mailbox::Bounded(1));
+    let mut p = CounterProxy::<ActorStopped, _>(handle.sink());
+    let p2 = CounterProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    for i in 1..=10 {
+        p.add(i);
+    }
+    assert_eq!(p2.get(), 55);
+    drop(p);
+    drop(p2);
+    let o = join.join().unwrap();
+    assert_eq!(o.total, 55);
+}
+
+#[test]
+fn stopped() {
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<CounterEnum, _>(Implementor::default(), Mailbox::Unbounded);
+    let p = CounterProxy::<ActorStopped, _>(handle.sink());
+    p.try_add(i32::MAX).unwrap();
+    p.try_add(1).unwrap();
+    // Overflow panic kills the actor thread
+    assert!(join.join().is_err());
+    assert_eq!(p.try_add(1), Err(ActorStopped));
+    assert!(p.try_get().is_err());
+}
+
+#[cfg(feature = "tokio")]
+mod with_tokio {
+    use super::*;
+    use trait_enumizer::tokio_oneshot_class;
+
+    #[trait_enumizer::enumizer(
+        name=AsyncCounterEnum,
+        returnval=tokio_oneshot_class,
+        call_fn(name=call_mut,ref_mut,async,dispatch),
+        proxy(Fn,name=AsyncCounterProxy,async),
+    )]
+    trait Counter2 {
+        fn add(&mut self, x: i32);
+        fn get(&self) -> i32;
+    }
+
+    impl Counter2 for Implementor {
+        fn add(&mut self, x: i32) {
+            self.total += x;
+        }
+
+        fn get(&self) -> i32 {
+            self.total
+        }
+    }
+
+    #[tokio::test]
+    async fn tokio_actor() {
+        let (handle, join) = trait_enumizer::actor::spawn_tokio_async::<AsyncCounterEnum, _>(
+            Implementor::default(),
+            Mailbox::Bounded(2),
+        );
+        let p = AsyncCounterProxy::<ActorStopped, _, _>(handle.async_sink());
+        drop(handle);
+        for i in 1..=10 {
+            p.try_add(i).await.unwrap();
+        }
+        assert_eq!(p.try_get().await.unwrap().unwrap(), 55);
+        drop(p);
+        let o = join.await.unwrap();
+        assert_eq!(o.total, 55);
+    }
+
+    #[tokio::test]
+    async fn tokio_actor_sync_dispatch() {
+        let (handle, join) =
+            trait_enumizer::actor::spawn_tokio::<CounterEnum, _>(Implementor::default(), Mailbox::Bounded(1));
+        for x in 1..=10 {
+            handle.send_async(CounterEnum::Add { x }).await.unwrap();
+        }
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        handle.send_async(CounterEnum::Get { ret: tx }).await.unwrap();
+        drop(handle);
+        let o = join.await.unwrap();
+        assert_eq!(rx.recv().unwrap(), 55);
+        assert_eq!(o.total, 55);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "capacity of at least 1")]
+    async fn tokio_rendezvous_rejected() {
+        let _ = trait_enumizer::actor::spawn_tokio::<CounterEnum, _>(Implementor::default(), Mailbox::Bounded(0));
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(feature="flume")]
+
+use trait_enumizer::flume_class;
+
+#[trait_enumizer::enumizer(
+    name=QqqEnum,
+    returnval=flume_class,
+    call_fn(name=try_call, ref, async),
+    proxy(Fn, name=QqqProxy, async, unwrapping_impl, resultified_trait=QqqResultified),
+)]
+trait Qqq {
+    async fn foo(&self) -> String;
+    async fn bar(&self, x: i32) -> i32;
+    async fn baz(&self, y: String);
+}
+
+struct Implementor;
+
+impl Qqq for Implementor {
+    async fn foo(&self) -> String {
+        "qqq".to_owned()
+    }
+
+    async fn bar(&self, x: i32) -> i32 {
+        x + 1
+    }
+
+    async fn baz(&self, y: String) {
+        dbg!("baz", y);
+    }
+}
+
+async fn use_qqq(q: &impl Qqq) -> (String, i32) {
+    q.baz("w".to_owned()).await;
+    (q.foo().await, q.bar(1).await)
+}
+
+#[test]
+fn proxy_implements_async_trait() {
+    let (tx, rx) = flume::bounded::<QqqEnum>(1);
+    std::thread::spawn(move || {
+        futures::executor::block_on(async {
+            let o = Implementor;
+            while let Ok(msg) = rx.recv_async().await {
+                msg.try_call(&o).await.unwrap();
+            }
+        })
+    });
+    let p = QqqProxy::<_, _, _>(|c| tx.send_async(c));
+    futures::executor::block_on(async {
+        assert_eq!(use_qqq(&Implementor).await, use_qqq(&p).await);
+        assert_eq!(p.try_bar(4).await.unwrap().unwrap(), 5);
+    });
+}
+
+#[trait_enumizer::enumizer(
+    name=LogEnum,
+    call_fn(name=call, ref_mut, async),
+    proxy(FnMut, name=LogProxy, async, infallible_impl),
+)]
+trait Log {
+    async fn log(&mut self, s: String);
+}
+
+impl Log for Vec<String> {
+    async fn log(&mut self, s: String) {
+        self.push(s);
+    }
+}
+
+#[test]
+fn infallible() {
+    let mut buffer = vec![];
+    let mut p = LogProxy(|c| {
+        buffer.push(c);
+        async { Ok(()) }
+    });
+    futures::executor::block_on(async {
+        p.log("hello".to_owned()).await;
+        p.log("world".to_owned()).await;
+    });
+
+    let mut o = vec![];
+    futures::executor::block_on(async {
+        for c in buffer {
+            c.call(&mut o).await;
+        }
+    });
+    assert_eq!(o, vec!["hello".to_owned(), "world".to_owned()]);
+}
@@ -0,0 +1,126 @@
+#![cfg(feature="std")]
+use std::sync::{Arc, Mutex};
+
+use trait_enumizer::callback_class;
+
+#[trait_enumizer::enumizer(
+    name=MyIfaceEnum,
+    returnval=callback_class,
+    call_fn(name=try_call, ref),
+    proxy(Fn,name=MyIfaceProxy,callbacks),
+)]
+trait MyIface {
+    fn foo(&self) -> String;
+    fn bar(&self, x: i32) -> i32;
+    fn baz(&self, y: String);
+}
+
+struct Implementor {}
+
+impl MyIface for Implementor {
+    fn foo(&self) -> String {
+        "qqq".to_owned()
+    }
+
+    fn bar(&self, x: i32) -> i32 {
+        x * x + 1
+    }
+
+    fn baz(&self, y: String) {
+        dbg!("baz", y);
+    }
+}
+
+#[test]
+fn continuations() {
+    let queue = std::cell::RefCell::new(Vec::new());
+    let p = MyIfaceProxy::<std::convert::Infallible, _>(|c| {
+        queue.borrow_mut().push(c);
+        Ok(())
+    });
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let r1 = results.clone();
+    let r2 = results.clone();
+    p.try_bar_then(3, move |x| r1.lock().unwrap().push(x.to_string())).unwrap();
+    p.try_foo_then(move |x| r2.lock().unwrap().push(x)).unwrap();
+    p.try_baz("w".to_owned()).unwrap();
+
+    // Nothing is called yet, proxy methods returned without blocking
+    assert!(results.lock().unwrap().is_empty());
+
+    let o = Implementor {};
+    for c in queue.borrow_mut().drain(..) {
+        c.try_call(&o).unwrap();
+    }
+    assert_eq!(*results.lock().unwrap(), vec!["10".to_owned(), "qqq".to_owned()]);
+}
+
+#[test]
+fn blocking() {
+    let (tx, rx) = flume::bounded::<MyIfaceEnum>(1);
+    std::thread::spawn(move || {
+        let o = Implementor {};
+        for msg in rx {
+            msg.try_call(&o).unwrap();
+        }
+    });
+    let p = MyIfaceProxy::<_, _>(|c| tx.send(c));
+    assert_eq!(p.try_bar(4).unwrap().unwrap(), 17);
+    assert_eq!(p.try_foo().unwrap().unwrap(), "qqq");
+}
+
+#[trait_enumizer::enumizer(
+    name=PostedEnum,
+    returnval=callback_class,
+    call_fn(name=try_call, ref, extra_arg_type(&'static str)),
+    proxy(Fn,name=PostedProxy,callbacks,extra_field_type(trait_enumizer::CallbackExecutor)),
+)]
+trait Posted {
+    fn bar(&self, x: i32) -> i32;
+}
+
+impl Posted for Implementor {
+    fn bar(&self, x: i32) -> i32 {
+        x * x + 1
+    }
+}
+
+#[test]
+fn continuations_via_executor() {
+    let (job_tx, job_rx) = std::sync::mpsc::channel::<trait_enumizer::CallbackJob>();
+    let executor = trait_enumizer::CallbackExecutor::new(move |job| job_tx.send(job).unwrap());
+    let queue = std::cell::RefCell::new(Vec::new());
+    let p = PostedProxy::<std::convert::Infallible, _>(
+        |c| {
+            queue.borrow_mut().push(c);
+            Ok(())
+        },
+        executor,
+    );
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let r = results.clone();
+    p.try_bar_then(3, move |x| r.lock().unwrap().push(x)).unwrap();
+
+    let o = Implementor {};
+    for c in queue.borrow_mut().drain(..) {
+        c.try_call(&o, "callee").unwrap();
+    }
+    // Callee only handed the continuation to the executor
+    assert!(results.lock().unwrap().is_empty());
+    for job in job_rx.try_iter() {
+        job();
+    }
+    assert_eq!(*results.lock().unwrap(), vec![10]);
+
+    // Blocking calls bypass the executor
+    let p = PostedProxy::<std::convert::Infallible, _>(
+        |c| {
+            c.try_call(&o, "callee").unwrap();
+            Ok(())
+        },
+        p.1.clone(),
+    );
+    assert_eq!(p.try_bar(4).unwrap().unwrap(), 17);
+}
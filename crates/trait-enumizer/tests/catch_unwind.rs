@@ -0,0 +1,174 @@
+#![cfg(feature="std")]
+use std::sync::Mutex;
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::{stdmpsc_class, CallError, Poisonable};
+
+static PANICKED_UNIT_METHODS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn record_panic(method: &'static str, p: &trait_enumizer::Panicked) {
+    PANICKED_UNIT_METHODS
+        .lock()
+        .unwrap()
+        .push(format!("{}: {}", method, p.message()));
+}
+
+#[trait_enumizer::enumizer(
+    name=ServiceEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=try_call_mut,ref_mut,catch_unwind,panic_hook(record_panic),dispatch),
+    proxy(Fn,name=ServiceProxy,unwrapping_impl),
+)]
+#[trait_enumizer::enumizer(
+    name=PoisoningServiceEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=try_call_mut,ref_mut,catch_unwind,poison_on_panic,dispatch),
+    proxy(Fn,name=PoisoningServiceProxy),
+)]
+trait Service {
+    fn div(&self, a: i32, b: i32) -> i32;
+    fn crash(&mut self);
+    fn calls(&self) -> usize;
+}
+
+#[derive(Default)]
+struct Implementor {
+    calls: usize,
+}
+
+impl Service for Implementor {
+    fn div(&self, a: i32, b: i32) -> i32 {
+        a / b
+    }
+
+    fn crash(&mut self) {
+        self.calls += 1;
+        panic!("crashed after {} calls", self.calls);
+    }
+
+    fn calls(&self) -> usize {
+        self.calls
+    }
+}
+
+#[test]
+fn keeps_alive() {
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<ServiceEnum, _>(Implementor::default(), Mailbox::Unbounded);
+    let mut p = ServiceProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    let e = p.try_div(1, 0).unwrap().unwrap().unwrap_err();
+    assert!(matches!(e, CallError::Panicked(e) if e.message() == "attempt to divide by zero"));
+    assert_eq!(p.div(6, 3), 2);
+    p.crash();
+    p.crash();
+    assert_eq!(p.calls(), 2);
+    assert_eq!(
+        *PANICKED_UNIT_METHODS.lock().unwrap(),
+        ["crash: crashed after 1 calls", "crash: crashed after 2 calls"]
+    );
+    // Unwrapping impl propagates the panic to the caller
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| p.div(1, 0))).is_err());
+    drop(p);
+    assert_eq!(join.join().unwrap().calls, 2);
+}
+
+#[test]
+fn poison() {
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<PoisoningServiceEnum, _>(
+        Poisonable::new(Implementor::default()),
+        Mailbox::Unbounded,
+    );
+    let p = PoisoningServiceProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    assert_eq!(p.try_div(4, 2).unwrap().unwrap(), Ok(2));
+    let e = p.try_div(1, 0).unwrap().unwrap().unwrap_err();
+    assert!(matches!(e, CallError::Panicked(e) if e.message() == "attempt to divide by zero"));
+    // Actor keeps running, but the object is not used anymore
+    assert_eq!(p.try_div(4, 2).unwrap().unwrap(), Err(CallError::Poisoned));
+    p.try_crash().unwrap();
+    assert_eq!(p.try_calls().unwrap().unwrap(), Err(CallError::Poisoned));
+    drop(p);
+    let o = join.join().unwrap();
+    assert!(o.is_poisoned());
+    assert_eq!(o.into_inner().calls, 0);
+}
+
+#[cfg(feature = "tokio")]
+mod with_tokio {
+    use trait_enumizer::tokio_oneshot_class;
+
+    #[trait_enumizer::enumizer(
+        name=AsyncServiceEnum,
+        returnval=tokio_oneshot_class,
+        call_errors,
+        call_fn(name=call_mut,ref_mut,async,catch_unwind,dispatch),
+        proxy(Fn,name=AsyncServiceProxy,async),
+    )]
+    trait AsyncService {
+        fn div(&self, a: i32, b: i32) -> i32;
+    }
+
+    impl AsyncService for super::Implementor {
+        fn div(&self, a: i32, b: i32) -> i32 {
+            a / b
+        }
+    }
+
+    #[tokio::test]
+    async fn async_call_fn() {
+        let (handle, join) = trait_enumizer::actor::spawn_tokio_async::<AsyncServiceEnum, _>(
+            super::Implementor::default(),
+            trait_enumizer::actor::Mailbox::Unbounded,
+        );
+        let p = AsyncServiceProxy::<trait_enumizer::actor::ActorStopped, _, _>(handle.async_sink());
+        drop(handle);
+        assert!(p.try_div(1, 0).await.unwrap().unwrap().is_err());
+        assert_eq!(p.try_div(9, 3).await.unwrap().unwrap(), Ok(3));
+        drop(p);
+        join.await.unwrap();
+    }
+}
+
+#[cfg(feature = "flume")]
+mod streamed {
+    use trait_enumizer::{flume_class, CallError, Poisonable};
+
+    #[trait_enumizer::enumizer(
+        name=CounterEnum,
+        returnval=flume_class,
+        call_errors,
+        call_fn(name=try_call,ref,catch_unwind,poison_on_panic,panic_hook(super::record_panic)),
+        proxy(Fn,name=CounterProxy,unwrapping_impl),
+    )]
+    trait Counter {
+        fn count_to(&self, n: u32) -> impl Iterator<Item = u32>;
+    }
+
+    struct Implementor;
+
+    impl Counter for Implementor {
+        fn count_to(&self, n: u32) -> impl Iterator<Item = u32> {
+            (1..=n).map(|x| if x == 3 { panic!("three") } else { x })
+        }
+    }
+
+    #[test]
+    fn panic_in_stream() {
+        let o = Poisonable::new(Implementor);
+        let p = CounterProxy::<std::convert::Infallible, _>(|c| {
+            c.try_call(&o).unwrap();
+            Ok(())
+        });
+        assert_eq!(p.count_to(2).collect::<Vec<_>>(), [1, 2]);
+        let items: Vec<_> = p.try_count_to(5).unwrap().map(Result::unwrap).collect();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[..2], [Ok(1), Ok(2)]);
+        assert!(matches!(&items[2], Err(CallError::Panicked(p)) if p.message() == "three"));
+        let items: Vec<_> = p.try_count_to(1).unwrap().map(Result::unwrap).collect();
+        assert_eq!(items, [Err(CallError::Poisoned)]);
+        assert!(o.is_poisoned());
+        assert!(super::PANICKED_UNIT_METHODS.lock().unwrap().contains(&"count_to: three".to_owned()));
+    }
+}
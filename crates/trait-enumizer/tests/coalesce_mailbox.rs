@@ -0,0 +1,107 @@
+#![cfg(feature="std")]
+use std::sync::mpsc::{channel, Receiver};
+use trait_enumizer::actor::{ActorStopped, Dispatch, Mailbox, Message};
+use trait_enumizer::Control;
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=AudioEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=AudioProxy,unwrapping_impl),
+)]
+trait Audio {
+    fn pause(&mut self, gate: Receiver<()>);
+    #[enumizer_coalesce]
+    fn set_volume(&mut self, v: u32);
+    #[enumizer_coalesce(key = channel)]
+    fn set_gain(&mut self, channel: u32, g: u32);
+    fn log(&self) -> Vec<String>;
+}
+
+#[derive(Default)]
+struct Implementor {
+    log: Vec<String>,
+}
+
+impl Audio for Implementor {
+    fn pause(&mut self, gate: Receiver<()>) {
+        let _ = gate.recv();
+    }
+
+    fn set_volume(&mut self, v: u32) {
+        self.log.push(format!("volume {}", v));
+    }
+
+    fn set_gain(&mut self, channel: u32, g: u32) {
+        self.log.push(format!("gain {} {}", channel, g));
+    }
+
+    fn log(&self) -> Vec<String> {
+        self.log.clone()
+    }
+}
+
+#[test]
+fn coalescing() {
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<AudioEnum, _>(Implementor::default(), Mailbox::PriorityBounded(4));
+    let mut p = AudioProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    let (gate_tx, gate_rx) = channel();
+    p.pause(gate_rx);
+    for v in 1..=200 {
+        p.set_volume(v);
+    }
+    p.set_gain(1, 10);
+    p.set_gain(2, 20);
+    p.set_gain(1, 11);
+    gate_tx.send(()).unwrap();
+    // Actor is blocked in `pause`, so everything else stays queued and coalesces
+    assert_eq!(p.log(), ["volume 200", "gain 1 11", "gain 2 20"]);
+    drop(p);
+    join.join().unwrap();
+}
+
+#[trait_enumizer::enumizer(
+    name=MixerEnum,
+    call_fn(name=call_mut,ref_mut),
+)]
+trait Mixer {
+    #[enumizer_coalesce(key = channel)]
+    fn set_gain(&mut self, channel: u32, g: u32);
+    fn mute(&mut self);
+}
+
+#[derive(Default)]
+struct Gains(Vec<(u32, u32)>);
+
+impl Mixer for Gains {
+    fn set_gain(&mut self, channel: u32, g: u32) {
+        self.0.push((channel, g));
+    }
+
+    fn mute(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl Dispatch<Gains> for MixerEnum {
+    fn dispatch(self, o: &mut Gains) -> Control {
+        self.call_mut(o);
+        Control::Continue
+    }
+}
+
+#[test]
+fn coalescing_with_manual_dispatch() {
+    let queued = MixerEnum::SetGain { channel: 1, g: 10 };
+    assert!(MixerEnum::SetGain { channel: 1, g: 11 }.coalesces_with(&queued));
+    assert!(!MixerEnum::SetGain { channel: 2, g: 11 }.coalesces_with(&queued));
+    assert!(!MixerEnum::Mute {}.coalesces_with(&MixerEnum::Mute {}));
+
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<MixerEnum, _>(Gains::default(), Mailbox::PriorityBounded(4));
+    handle.send(MixerEnum::SetGain { channel: 1, g: 10 }).unwrap();
+    drop(handle);
+    assert_eq!(join.join().unwrap().0, [(1, 10)]);
+}
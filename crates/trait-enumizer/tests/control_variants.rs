@@ -0,0 +1,70 @@
+#![cfg(feature="std")]
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::{stdmpsc_class, Control};
+
+#[trait_enumizer::enumizer(
+    name=CounterEnum,
+    returnval=stdmpsc_class,
+    control_variants,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=CounterProxy),
+)]
+trait Counter {
+    fn add(&mut self, x: i32);
+    fn get(&self) -> i32;
+}
+
+#[derive(Default)]
+struct Implementor {
+    total: i32,
+}
+
+impl Counter for Implementor {
+    fn add(&mut self, x: i32) {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        self.total += x;
+    }
+
+    fn get(&self) -> i32 {
+        self.total
+    }
+}
+
+#[test]
+fn manual_loop() {
+    let (tx, rx) = std::sync::mpsc::channel::<CounterEnum>();
+    let t = std::thread::spawn(move || {
+        let mut o = Implementor::default();
+        for msg in rx {
+            if msg.try_call_mut(&mut o).unwrap() == Control::Shutdown {
+                break;
+            }
+        }
+        o
+    });
+    let p = CounterProxy::<_, _>(|c| tx.send(c));
+    p.try_ping().unwrap().unwrap();
+    for i in 1..=10 {
+        p.try_add(i).unwrap();
+    }
+    p.try_barrier().unwrap().unwrap();
+    assert_eq!(p.try_get().unwrap().unwrap(), 55);
+    p.try_add(1).unwrap();
+    p.try_shutdown().unwrap();
+    let o = t.join().unwrap();
+    assert_eq!(o.total, 56);
+    assert!(p.try_ping().is_err());
+}
+
+#[test]
+fn actor_shutdown() {
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<CounterEnum, _>(Implementor::default(), Mailbox::Unbounded);
+    let p = CounterProxy::<ActorStopped, _>(handle.sink());
+    p.try_add(3).unwrap();
+    p.try_shutdown().unwrap();
+    // Handles are still alive, but the actor stops anyway
+    let o = join.join().unwrap();
+    assert_eq!(o.total, 3);
+    assert_eq!(p.try_ping(), Err(ActorStopped));
+}
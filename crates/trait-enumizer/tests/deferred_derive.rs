@@ -0,0 +1,86 @@
+#![cfg(feature="flume")]
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use trait_enumizer::flume_class;
+
+#[trait_enumizer::enumizer(
+    name=MyIfaceEnum,
+    returnval=flume_class,
+    call_fn(name=try_call, ref),
+    call_fn(name=try_call_async, ref, async),
+    proxy(Fn,name=MyIfaceProxy,deferred,unwrapping_impl),
+    proxy(Fn,name=MyIfaceAsyncProxy,deferred,async),
+)]
+trait MyIface {
+    fn get(&self) -> i32;
+    fn square(&self, x: i32) -> i32;
+    #[enumizer_no_wait]
+    fn add(&self, x: i32) -> ();
+    #[enumizer_wait]
+    fn flush(&self);
+}
+
+#[derive(Default)]
+struct Implementor(AtomicI32);
+
+impl MyIface for Implementor {
+    fn get(&self) -> i32 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn square(&self, x: i32) -> i32 {
+        x * x
+    }
+
+    fn add(&self, x: i32) {
+        self.0.fetch_add(x, Ordering::SeqCst);
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn pipelined() {
+    let (tx, rx) = flume::unbounded::<MyIfaceEnum>();
+    let actor = std::thread::spawn(move || {
+        let o = Implementor::default();
+        for msg in rx {
+            msg.try_call(&o).unwrap();
+        }
+    });
+    let p = MyIfaceProxy::<_, _>(move |c| tx.send(c));
+
+    // `enumizer_no_wait` turns `-> ()` method into a fire-and-forget one
+    let () = p.try_add(5).unwrap();
+    // `enumizer_wait` makes caller wait even though there is no return value
+    p.try_flush().unwrap().unwrap();
+    p.flush();
+
+    let replies: Vec<_> = (1..=3).map(|x| p.try_square_deferred(x).unwrap()).collect();
+    let g = p.try_get_deferred().unwrap();
+    let squares: Vec<i32> = replies.into_iter().map(|r| r.wait().unwrap()).collect();
+    assert_eq!(squares, vec![1, 4, 9]);
+    assert_eq!(g.wait().unwrap(), 5);
+
+    drop(p);
+    actor.join().unwrap();
+}
+
+#[test]
+fn pipelined_async() {
+    let o = Implementor::default();
+    let (tx, rx) = flume::unbounded::<MyIfaceEnum>();
+    let p = MyIfaceAsyncProxy::<_, _, _>(|c| tx.send_async(c));
+
+    futures::executor::block_on(async {
+        p.try_add(2).await.unwrap();
+        let r1 = p.try_square_deferred(7).await.unwrap();
+        let r2 = p.try_get_deferred().await.unwrap();
+
+        while let Ok(msg) = rx.try_recv() {
+            msg.try_call_async(&o).await.unwrap();
+        }
+        assert_eq!(r1.await.unwrap(), 49);
+        assert_eq!(r2.await.unwrap(), 2);
+    });
+}
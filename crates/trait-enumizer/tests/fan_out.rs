@@ -0,0 +1,125 @@
+#![cfg(feature="std")]
+use std::cell::RefCell;
+use trait_enumizer::{stdmpsc_class, Aggregate, CallError, First, Last, Reduce};
+
+#[trait_enumizer::enumizer(
+    name=PluginEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=call_all,ref_mut,fan_out),
+    proxy(Fn,name=PluginProxy,unwrapping_impl),
+)]
+trait Plugin {
+    fn load(&mut self, #[enumizer_to_owned] config: &str, version: u32);
+    fn title(&self) -> String;
+    fn commands(&self) -> Vec<String>;
+}
+
+struct Echo {
+    prefix: &'static str,
+    loaded: Vec<String>,
+}
+
+impl Plugin for Echo {
+    fn load(&mut self, config: &str, version: u32) {
+        self.loaded.push(format!("{} v{}", config, version));
+    }
+
+    fn title(&self) -> String {
+        format!("{} ({})", self.prefix, self.loaded.join("; "))
+    }
+
+    fn commands(&self) -> Vec<String> {
+        vec![format!("{}.run", self.prefix), format!("{}.stop", self.prefix)]
+    }
+}
+
+/// Joins titles and concatenates command lists
+struct Merge;
+
+impl Aggregate<String> for Merge {
+    fn combine(&mut self, acc: String, next: String) -> String {
+        acc + ", " + &next
+    }
+}
+
+impl Aggregate<Vec<String>> for Merge {
+    fn combine(&mut self, acc: Vec<String>, next: Vec<String>) -> Vec<String> {
+        trait_enumizer::Concat.combine(acc, next)
+    }
+}
+
+#[test]
+fn plugin_host() {
+    let plugins: RefCell<Vec<Box<dyn Plugin>>> = RefCell::new(vec![
+        Box::new(Echo { prefix: "a", loaded: vec![] }),
+        Box::new(Echo { prefix: "b", loaded: vec![] }),
+    ]);
+    let mut host = PluginProxy(|msg: PluginEnum| msg.call_all(plugins.borrow_mut().iter_mut().map(|p| &mut **p), Merge));
+    host.load("cfg", 2);
+    assert_eq!(host.title(), "a (cfg v2), b (cfg v2)");
+    assert_eq!(host.commands(), ["a.run", "a.stop", "b.run", "b.stop"]);
+
+    let first = PluginProxy(|msg: PluginEnum| msg.call_all(plugins.borrow_mut().iter_mut().map(|p| &mut **p), First));
+    assert_eq!(first.title(), "a (cfg v2)");
+    let last = PluginProxy(|msg: PluginEnum| msg.call_all(plugins.borrow_mut().iter_mut().map(|p| &mut **p), Last));
+    assert_eq!(last.title(), "b (cfg v2)");
+}
+
+#[trait_enumizer::enumizer(
+    name=SensorEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=call_all,ref,fan_out),
+    proxy(Fn,name=SensorProxy,unwrapping_impl),
+)]
+trait Sensor {
+    fn read(&self, scale: u32) -> u32;
+}
+
+impl Sensor for u32 {
+    fn read(&self, scale: u32) -> u32 {
+        self * scale
+    }
+}
+
+#[test]
+fn reducer() {
+    let sensors = [3u32, 7, 5];
+    let max = SensorProxy(|msg: SensorEnum| msg.call_all(&sensors, Reduce(u32::max)));
+    assert_eq!(max.read(10), 70);
+    let sum = SensorProxy(|msg: SensorEnum| msg.call_all(&sensors, Reduce(|a, b| a + b)));
+    assert_eq!(sum.read(2), 30);
+}
+
+#[test]
+fn no_sensors() {
+    let sensors: [u32; 0] = [];
+    let p = SensorProxy(|msg: SensorEnum| msg.call_all(&sensors, First));
+    assert!(p.try_read(1).unwrap().is_err());
+}
+
+#[trait_enumizer::enumizer(
+    name=GaugeEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=call_all,ref,fan_out),
+    proxy(Fn,name=GaugeProxy),
+)]
+trait Gauge {
+    fn level(&self) -> u32;
+}
+
+impl Gauge for u32 {
+    fn level(&self) -> u32 {
+        *self
+    }
+}
+
+#[test]
+fn no_gauges() {
+    let gauges: [u32; 0] = [];
+    let p = GaugeProxy(|msg: GaugeEnum| msg.call_all(&gauges, First));
+    assert_eq!(p.try_level().unwrap().unwrap(), Err(CallError::NoTargets));
+    let gauges = [4u32, 9];
+    let p = GaugeProxy(|msg: GaugeEnum| msg.call_all(&gauges, Last));
+    assert_eq!(p.try_level().unwrap().unwrap(), Ok(9));
+}
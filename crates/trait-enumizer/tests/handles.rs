@@ -0,0 +1,94 @@
+#![cfg(feature="std")]
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::registry::{HandleId, Handles, HoldsHandles};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=FileHandleEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=FileHandleProxy,unwrapping_impl,object_id(HandleId)),
+)]
+trait FileHandle {
+    fn write(&mut self, #[enumizer_to_owned] data: &str);
+    fn read(&self) -> String;
+}
+
+#[trait_enumizer::enumizer(
+    name=FsEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=FsProxy,unwrapping_impl),
+)]
+trait Fs {
+    #[enumizer_handle(FileHandleEnum, FileHandleProxy)]
+    fn open(&mut self, path: String) -> Box<dyn FileHandle + Send>;
+    fn open_count(&self) -> usize;
+}
+
+struct MemFile {
+    path: String,
+    data: String,
+}
+
+impl FileHandle for MemFile {
+    fn write(&mut self, data: &str) {
+        self.data.push_str(data);
+    }
+
+    fn read(&self) -> String {
+        format!("{}: {}", self.path, self.data)
+    }
+}
+
+#[derive(Default)]
+struct MemFs {
+    handles: Handles<Box<dyn FileHandle + Send>>,
+}
+
+impl Fs for MemFs {
+    fn open(&mut self, path: String) -> Box<dyn FileHandle + Send> {
+        Box::new(MemFile { path, data: String::new() })
+    }
+
+    fn open_count(&self) -> usize {
+        self.handles.len()
+    }
+}
+
+impl HoldsHandles<Box<dyn FileHandle + Send>> for MemFs {
+    fn handles(&mut self) -> &mut Handles<Box<dyn FileHandle + Send>> {
+        &mut self.handles
+    }
+}
+
+#[test]
+fn remote_files() {
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<FsEnum, _>(MemFs::default(), Mailbox::Unbounded);
+    let mut fs = FsProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+
+    let mut a = fs.open("a.txt".to_owned());
+    let mut b = fs.open("b.txt".to_owned());
+    a.write("hello");
+    b.write("world");
+    a.write("!");
+    assert_eq!(a.read(), "a.txt: hello!");
+    assert_eq!(b.read(), "b.txt: world");
+    assert_eq!(fs.open_count(), 2);
+
+    // Dropping a sub-proxy releases the object
+    drop(a);
+    assert_eq!(fs.open_count(), 1);
+    assert_eq!(b.read(), "b.txt: world");
+
+    // Raw ids can be used too. Released ones are missing.
+    let stale = FileHandleProxy::<ActorStopped, _>(|(id, msg)| (fs.0)(FsEnum::OpenHandle { id, msg: trait_enumizer::registry::HandleMsg::Call(msg) }), 0);
+    assert_eq!(stale.try_read().unwrap().unwrap(), Err(trait_enumizer::CallError::NoSuchObject));
+
+    drop(b);
+    drop(fs);
+    let mut mem_fs = join.join().unwrap();
+    assert!(mem_fs.handles().is_empty());
+}
@@ -0,0 +1,167 @@
+#![cfg(feature="std")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use trait_enumizer::{stdmpsc_class, CallError, Interceptor, MethodInfo, Reject};
+
+#[trait_enumizer::enumizer(
+    name=AccountEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=try_call_mut,ref_mut,interceptor=Limits),
+    proxy(Fn,name=AccountProxy),
+    proxy(Fn,name=ReadOnlyAccountProxy,interceptor=ReadOnly),
+)]
+trait Account {
+    fn deposit(&mut self, x: i64);
+    fn withdraw(&mut self, x: i64) -> i64;
+    fn balance(&self) -> i64;
+}
+
+#[derive(Default)]
+struct Implementor {
+    balance: i64,
+}
+
+impl Account for Implementor {
+    fn deposit(&mut self, x: i64) {
+        self.balance += x;
+    }
+
+    fn withdraw(&mut self, x: i64) -> i64 {
+        self.balance -= x;
+        self.balance
+    }
+
+    fn balance(&self) -> i64 {
+        self.balance
+    }
+}
+
+static HANDLED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+struct Limits;
+impl Interceptor<AccountEnum> for Limits {
+    fn before(_method: &MethodInfo, msg: &AccountEnum) -> Result<(), Reject> {
+        match msg {
+            AccountEnum::Withdraw { x, .. } if *x > 100 => Err(Reject::new("over the limit")),
+            AccountEnum::Deposit { x } if *x < 0 => Err(Reject::new("negative deposit")),
+            _ => Ok(()),
+        }
+    }
+
+    fn after(method: &MethodInfo, _elapsed: Duration) {
+        HANDLED.lock().unwrap().push(method.name);
+    }
+}
+
+static SENT: AtomicUsize = AtomicUsize::new(0);
+
+struct ReadOnly;
+impl Interceptor<AccountEnum> for ReadOnly {
+    fn before(method: &MethodInfo, _msg: &AccountEnum) -> Result<(), Reject> {
+        assert_eq!(method.enum_name, "AccountEnum");
+        if method.name == "balance" {
+            Ok(())
+        } else {
+            Err(Reject::new(format!("{} is not allowed", method.name)))
+        }
+    }
+
+    fn after(_method: &MethodInfo, _elapsed: Duration) {
+        SENT.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn interceptors() {
+    let (tx, rx) = std::sync::mpsc::channel::<AccountEnum>();
+    let t = std::thread::spawn(move || {
+        let mut o = Implementor::default();
+        for msg in rx {
+            msg.try_call_mut(&mut o).unwrap();
+        }
+        o
+    });
+    let delivered = AtomicUsize::new(0);
+    let sink = |c| {
+        delivered.fetch_add(1, Ordering::SeqCst);
+        tx.send(c)
+    };
+    let p = AccountProxy::<_, _>(sink);
+    let rp = ReadOnlyAccountProxy::<_, _>(sink);
+
+    p.try_deposit(500).unwrap();
+    p.try_deposit(-1).unwrap();
+    assert_eq!(p.try_withdraw(50).unwrap().unwrap(), Ok(450));
+    assert_eq!(
+        p.try_withdraw(1000).unwrap().unwrap(),
+        Err(CallError::Rejected(Reject::new("over the limit")))
+    );
+    assert_eq!(p.try_balance().unwrap().unwrap(), Ok(450));
+    assert_eq!(delivered.load(Ordering::SeqCst), 5);
+
+    // Rejected on proxy side, so never reach the channel
+    assert_eq!(
+        rp.try_deposit(1).unwrap(),
+        Err(CallError::Rejected(Reject::new("deposit is not allowed")))
+    );
+    let e = rp.try_withdraw(1).unwrap().unwrap().unwrap_err();
+    assert_eq!(e.to_string(), "trait-enumizer: call rejected: withdraw is not allowed");
+    assert_eq!(rp.try_balance().unwrap().unwrap(), Ok(450));
+    assert_eq!(delivered.load(Ordering::SeqCst), 6);
+    assert_eq!(SENT.load(Ordering::SeqCst), 1);
+
+    drop(tx);
+    assert_eq!(t.join().unwrap().balance, 450);
+    // `after` runs when the reply is already sent, so check it only after the handler is done
+    assert_eq!(*HANDLED.lock().unwrap(), ["deposit", "withdraw", "balance", "balance"]);
+}
+
+#[cfg(feature = "tokio")]
+mod with_tokio {
+    use trait_enumizer::{tokio_oneshot_class, CallError, Interceptor, MethodInfo, Reject};
+
+    #[trait_enumizer::enumizer(
+        name=AsyncAccountEnum,
+        returnval=tokio_oneshot_class,
+        call_errors,
+        call_fn(name=call_mut,ref_mut,async,interceptor=Closed),
+        proxy(Fn,name=AsyncAccountProxy,async),
+    )]
+    trait AsyncAccount {
+        fn balance(&self) -> i64;
+    }
+
+    impl AsyncAccount for super::Implementor {
+        fn balance(&self) -> i64 {
+            self.balance
+        }
+    }
+
+    struct Closed;
+    impl Interceptor<AsyncAccountEnum> for Closed {
+        fn before(_method: &MethodInfo, _msg: &AsyncAccountEnum) -> Result<(), Reject> {
+            Err(Reject::new("closed"))
+        }
+    }
+
+    #[tokio::test]
+    async fn async_call_fn() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<AsyncAccountEnum>();
+        let p = AsyncAccountProxy::<_, _, _>(move |c| {
+            let r = tx.send(c).map_err(|_| ());
+            async move { r }
+        });
+        let t = tokio::spawn(async move {
+            let mut o = super::Implementor::default();
+            while let Some(msg) = rx.recv().await {
+                msg.call_mut(&mut o).await.unwrap();
+            }
+        });
+        let e = p.try_balance().await.unwrap().unwrap().unwrap_err();
+        assert!(matches!(e, CallError::Rejected(r) if r.reason() == "closed"));
+        drop(p);
+        t.await.unwrap();
+    }
+}
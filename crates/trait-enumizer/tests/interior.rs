@@ -0,0 +1,58 @@
+#![cfg(feature="std")]
+use std::cell::RefCell;
+use std::sync::{mpsc, Mutex};
+
+#[trait_enumizer::enumizer(
+    name=EventEnum,
+    proxy(FnMut,name=LocalEvents,infallible_impl,interior=RefCell),
+    proxy(FnMut,name=SharedEvents,unwrapping_impl,interior=Mutex),
+    enum_attr[derive(Debug, PartialEq)],
+)]
+trait Events {
+    fn emit(&self, name: String);
+    fn emit_all(&self, names: Vec<String>);
+}
+
+/// Code that only knows about `&self` methods
+fn startup(events: &impl Events) {
+    events.emit("start".to_owned());
+    events.emit_all(vec!["a".to_owned(), "b".to_owned()]);
+}
+
+#[test]
+fn refcell_buffer() {
+    let mut buffer = vec![];
+    {
+        let events = LocalEvents(RefCell::new(|msg| {
+            buffer.push(msg);
+            Ok(())
+        }));
+        startup(&events);
+        events.try_emit("done".to_owned()).unwrap();
+    }
+    assert_eq!(
+        buffer,
+        [
+            EventEnum::Emit { name: "start".to_owned() },
+            EventEnum::EmitAll { names: vec!["a".to_owned(), "b".to_owned()] },
+            EventEnum::Emit { name: "done".to_owned() },
+        ]
+    );
+}
+
+#[test]
+fn mutex_across_threads() {
+    let (tx, rx) = mpsc::channel();
+    let mut seq = 0;
+    let events = SharedEvents(Mutex::new(move |msg| {
+        seq += 1;
+        tx.send((seq, msg))
+    }));
+    std::thread::scope(|s| {
+        s.spawn(|| startup(&events));
+        s.spawn(|| startup(&events));
+    });
+    drop(events);
+    let seqs: Vec<u32> = rx.iter().map(|(seq, _)| seq).collect();
+    assert_eq!(seqs, [1, 2, 3, 4]);
+}
@@ -0,0 +1,78 @@
+#![cfg(feature="journal")]
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::journal::{self, Journal, ReplayError};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=CounterEnum,
+    returnval=stdmpsc_class,
+    journal=CounterCall,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=CounterProxy,unwrapping_impl),
+)]
+trait Counter {
+    fn add(&mut self, x: i32) -> i32;
+    fn label(&self, #[enumizer_to_owned] prefix: &str) -> String;
+    fn reset(&mut self);
+}
+
+#[derive(Default)]
+struct Implementor {
+    value: i32,
+    buggy: bool,
+}
+
+impl Counter for Implementor {
+    fn add(&mut self, x: i32) -> i32 {
+        self.value += if self.buggy { x * 2 } else { x };
+        self.value
+    }
+
+    fn label(&self, prefix: &str) -> String {
+        format!("{}{}", prefix, self.value)
+    }
+
+    fn reset(&mut self) {
+        self.value = 0;
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    let journal = Journal::new(Vec::new());
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<CounterEnum, _>(Implementor::default(), Mailbox::Unbounded);
+    let sink = handle.sink();
+    drop(handle);
+    let mut p = CounterProxy::<ActorStopped, _>(|msg| {
+        journal.record(&msg).unwrap();
+        sink(msg)
+    });
+    p.add(5);
+    p.reset();
+    p.add(2);
+    assert_eq!(p.label("v="), "v=2");
+    drop(sink);
+    join.join().unwrap();
+
+    let recorded = journal.into_inner();
+    let entries: Vec<journal::Entry<CounterCall>> = journal::read(&recorded[..]).collect::<Result<_, _>>().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().all(|e| e.ret.is_none() && e.ts_us > 0));
+    assert!(matches!(&entries[3].call, CounterCall::Label { prefix } if prefix == "v="));
+
+    // Replaying the journal produces a baseline with return values
+    let baseline = Journal::new(Vec::new());
+    let rets = journal::replay_into::<CounterCall, _, _>(&recorded[..], &mut Implementor::default(), &baseline).unwrap();
+    assert_eq!(rets, [serde_json::json!(5), serde_json::Value::Null, serde_json::json!(2), serde_json::json!("v=2")]);
+    let baseline = baseline.into_inner();
+    journal::replay::<CounterCall, _>(&baseline[..], &mut Implementor::default()).unwrap();
+
+    let mut buggy = Implementor { buggy: true, ..Default::default() };
+    match journal::replay::<CounterCall, _>(&baseline[..], &mut buggy) {
+        Err(ReplayError::Mismatch { index: 0, expected, actual }) => {
+            assert_eq!((expected, actual), (serde_json::json!(5), serde_json::json!(10)));
+        }
+        x => panic!("unexpected {:?}", x),
+    }
+}
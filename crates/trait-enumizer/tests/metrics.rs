@@ -0,0 +1,58 @@
+#![cfg(feature="std")]
+use std::time::Duration;
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::{stdmpsc_class, InMemoryMetrics};
+
+#[trait_enumizer::enumizer(
+    name=WorkerEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    sent_at,
+    call_fn(name=try_call_mut,ref_mut,catch_unwind,dispatch,metrics=InMemoryMetrics),
+    proxy(Fn,name=WorkerProxy,metrics=InMemoryMetrics),
+)]
+trait Worker {
+    fn sleep(&mut self, ms: u64);
+    fn div(&self, a: i32, b: i32) -> i32;
+}
+
+struct Implementor;
+
+impl Worker for Implementor {
+    fn sleep(&mut self, ms: u64) {
+        std::thread::sleep(Duration::from_millis(ms));
+    }
+
+    fn div(&self, a: i32, b: i32) -> i32 {
+        a / b
+    }
+}
+
+#[test]
+fn in_memory() {
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<WorkerEnum, _>(Implementor, Mailbox::Unbounded);
+    let p = WorkerProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    p.try_sleep(30).unwrap();
+    // Waits in the mailbox while the actor sleeps
+    assert_eq!(p.try_div(6, 3).unwrap().unwrap(), Ok(2));
+    assert!(p.try_div(1, 0).unwrap().unwrap().is_err());
+    drop(p);
+    join.join().unwrap();
+    let broken = WorkerProxy::<(), _>(|_| Err(()));
+    assert!(broken.try_sleep(1).is_err());
+
+    let sleep = InMemoryMetrics::get("WorkerEnum", "sleep");
+    assert_eq!((sleep.sent, sleep.send_errors, sleep.handled, sleep.failed), (1, 1, 1, 0));
+    assert!(sleep.max_latency >= Duration::from_millis(30));
+
+    let div = InMemoryMetrics::get("WorkerEnum", "div");
+    assert_eq!((div.sent, div.send_errors, div.handled, div.failed), (2, 0, 2, 1));
+    assert_eq!(div.queued, 2);
+    assert!(div.max_queue_time >= Duration::from_millis(20));
+    assert!(div.mean_latency().unwrap() < div.max_queue_time);
+
+    assert_eq!(InMemoryMetrics::get_all("WorkerEnum").len(), 2);
+    InMemoryMetrics::reset("WorkerEnum");
+    assert_eq!(InMemoryMetrics::get("WorkerEnum", "div"), Default::default());
+}
@@ -0,0 +1,116 @@
+#![cfg(feature="std")]
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=CanvasEnum,
+    returnval=stdmpsc_class,
+    mock=CanvasMock,
+)]
+trait Canvas {
+    #[enumizer_mock_eq]
+    fn move_to(&mut self, x: i32, y: i32);
+    #[enumizer_mock_eq]
+    fn text(&mut self, #[enumizer_to_owned] s: &str);
+    #[enumizer_mock_eq]
+    fn measure(&self, s: String) -> u32;
+    fn flush(&mut self) -> ();
+}
+
+/// Code under test
+fn caption(c: &mut impl Canvas, s: &str) {
+    let width = c.measure(s.to_owned());
+    c.move_to(-(width as i32) / 2, 0);
+    c.text(s);
+    c.flush();
+}
+
+fn panic_message(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+    let payload = std::panic::catch_unwind(f).unwrap_err();
+    payload.downcast::<String>().map(|s| *s).unwrap_or_default()
+}
+
+#[test]
+fn expectations_met() {
+    let mut mock = CanvasMock::new();
+    mock.expect_measure("hello".to_owned(), 10)
+        .expect_move_to(-5, 0)
+        .expect_text("hello".to_owned())
+        .expect_flush();
+    caption(&mut mock, "hello");
+    mock.verify();
+
+    mock.expect_measure_with(|msg| matches!(msg, CanvasEnum::Measure { s, .. } if s.starts_with('h')), 4)
+        .expect_move_to_with(|msg| matches!(msg, CanvasEnum::MoveTo { y: 0, .. }))
+        .expect_text_with(|_| true)
+        .expect_flush();
+    caption(&mut mock, "hi");
+}
+
+#[test]
+fn report_on_drop() {
+    let message = panic_message(|| {
+        let mut mock = CanvasMock::new();
+        mock.expect_measure("hello".to_owned(), 10)
+            .expect_text("hello".to_owned())
+            .expect_move_to(-5, 0)
+            .expect_flush();
+        caption(&mut mock, "hello");
+    });
+    assert_eq!(
+        message,
+        "trait-enumizer: CanvasMock expectations failed
+  call #2:
+    expected: text(s: \"hello\")
+    actual:   move_to(x: -5, y: 0)
+  call #4:
+    expected: move_to(x: -5, y: 0)
+    actual:   flush()
+  unmet: move_to(x: -5, y: 0)
+  unmet: flush()"
+    );
+}
+
+#[test]
+#[should_panic(expected = "unexpected measure(s: \"hello\")")]
+fn unexpected_call_needing_reply() {
+    caption(&mut CanvasMock::new(), "hello");
+}
+
+/// Neither `PartialEq` nor `Debug`
+struct Filter(Box<dyn Fn(u32) -> bool + Send>);
+
+#[trait_enumizer::enumizer(
+    name=PipelineEnum,
+    returnval=stdmpsc_class,
+    mock=PipelineMock,
+)]
+trait Pipeline {
+    fn add_filter(&mut self, name: String, filter: Filter) -> usize;
+    fn clear(&mut self);
+}
+
+#[test]
+fn arguments_without_eq_and_debug() {
+    let mut mock = PipelineMock::new();
+    mock.expect_add_filter_with(
+        |msg| matches!(msg, PipelineEnum::AddFilter { name, filter, .. } if name == "even" && (filter.0)(4) && !(filter.0)(5)),
+        1,
+    )
+    .expect_clear();
+    assert_eq!(mock.add_filter("even".to_owned(), Filter(Box::new(|x| x % 2 == 0))), 1);
+    mock.clear();
+    mock.verify();
+
+    let message = panic_message(move || {
+        mock.expect_clear();
+        let _ = mock.add_filter("odd".to_owned(), Filter(Box::new(|x| x % 2 == 1)));
+    });
+    assert_eq!(
+        message,
+        "trait-enumizer: PipelineMock expectations failed
+  call #3:
+    expected: clear()
+    actual:   add_filter(name: <alloc::string::String>, filter: <mock::Filter>)
+  unmet: clear()"
+    );
+}
@@ -0,0 +1,82 @@
+#![cfg(feature="std")]
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=AccountEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=AccountProxy,unwrapping_impl,multiplexed),
+)]
+trait Account {
+    fn deposit(&mut self, amount: u32);
+    fn balance(&self) -> u32;
+}
+
+#[trait_enumizer::enumizer(
+    name=AuditEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=AuditProxy,resultified_trait=AuditResultified,multiplexed),
+)]
+trait Audit {
+    fn note(&mut self, #[enumizer_to_owned] text: &str);
+    fn log(&self) -> Vec<String>;
+}
+
+trait_enumizer::multiplex!(BankMsg = AccountEnum, AuditEnum);
+
+#[derive(Default)]
+struct Bank {
+    balance: u32,
+    log: Vec<String>,
+}
+
+impl Account for Bank {
+    fn deposit(&mut self, amount: u32) {
+        self.balance += amount;
+        self.log.push(format!("deposit {}", amount));
+    }
+
+    fn balance(&self) -> u32 {
+        self.balance
+    }
+}
+
+impl Audit for Bank {
+    fn note(&mut self, text: &str) {
+        self.log.push(text.to_owned());
+    }
+
+    fn log(&self) -> Vec<String> {
+        self.log.clone()
+    }
+}
+
+#[test]
+fn one_actor_two_traits() {
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<BankMsg, _>(Bank::default(), Mailbox::Unbounded);
+    let mut p = BankMsg::proxy(handle.sink());
+    drop(handle);
+
+    p.deposit(10);
+    p.try_note("checked").unwrap();
+    p.deposit(5);
+    assert_eq!(p.balance(), 15);
+    assert_eq!(p.try_log().unwrap().unwrap(), ["deposit 10", "checked", "deposit 5"]);
+
+    // Single-trait proxies can feed the same actor too
+    let account = AccountProxy::<ActorStopped, _>(|msg| (p.0)(BankMsg::from(msg)));
+    assert_eq!(account.balance(), 15);
+    drop(p);
+    let bank = join.join().unwrap();
+    assert_eq!(bank.balance, 15);
+}
+
+#[test]
+fn call_directly() {
+    let mut bank = Bank::default();
+    BankMsg::from(AccountEnum::Deposit { amount: 3 }).call(&mut bank);
+    BankMsg::AuditEnum(AuditEnum::Note { text: "x".to_owned() }).call(&mut bank);
+    assert_eq!(bank.log, ["deposit 3", "x"]);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature="std")]
+use trait_enumizer::pooled_class;
+
+#[trait_enumizer::enumizer(
+    name=MyIfaceEnum,
+    returnval=pooled_class,
+    call_fn(name=try_call, ref),
+    proxy(Fn,name=MyIfaceProxy,unwrapping_impl),
+    proxy(Fn,name=MyIfaceSharedPoolProxy,extra_field_type(trait_enumizer::ReturnSlotPool)),
+)]
+trait MyIface {
+    fn foo(&self) -> String;
+    fn bar(&self, x: i32) -> i32;
+    fn baz(&self, y: String, z: Vec<u8>);
+}
+
+struct Implementor {}
+
+impl MyIface for Implementor {
+    fn foo(&self) -> String {
+        "qqq".to_owned()
+    }
+
+    fn bar(&self, x: i32) -> i32 {
+        x * x + 1
+    }
+
+    fn baz(&self, y: String, z: Vec<u8>) {
+        dbg!("baz", y, z);
+    }
+}
+
+#[test]
+fn simple() {
+    let o = Implementor {};
+    let p = MyIfaceProxy::<_, _>(|c| c.try_call(&o));
+    assert_eq!(p.foo(), "qqq");
+    for i in 0..100 {
+        assert_eq!(p.bar(i), i * i + 1);
+    }
+    p.baz("w".to_owned(), vec![1]);
+}
+
+#[test]
+fn threaded() {
+    let (tx, rx) = flume::bounded::<MyIfaceEnum>(1);
+    std::thread::spawn(move || {
+        let o = Implementor {};
+        for msg in rx {
+            msg.try_call(&o).unwrap();
+        }
+    });
+    let pool = trait_enumizer::ReturnSlotPool::new();
+    let p = MyIfaceProxy::<_, _>(|c| tx.send(c));
+    let p2 = MyIfaceSharedPoolProxy::<_, _>(|c| tx.send(c), pool.clone());
+    for i in 0..100 {
+        assert_eq!(p.bar(i), i * i + 1);
+        assert_eq!(p2.try_bar(i).unwrap().unwrap(), i * i + 1);
+        assert_eq!(p2.try_foo().unwrap().unwrap(), "qqq");
+    }
+}
+
+#[test]
+fn dropped_sender() {
+    let p = MyIfaceProxy::<std::convert::Infallible, _>(|c| {
+        drop(c);
+        Ok(())
+    });
+    assert!(p.try_bar(4).unwrap().is_err());
+    assert!(p.try_foo().unwrap().is_err());
+}
@@ -0,0 +1,101 @@
+#![cfg(feature="std")]
+use std::sync::mpsc::{channel, Receiver};
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::{stdmpsc_class, Priority};
+
+#[trait_enumizer::enumizer(
+    name=SchedulerEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=SchedulerProxy,unwrapping_impl),
+)]
+trait Scheduler {
+    fn pause(&mut self, gate: Receiver<()>);
+    fn submit_job(&mut self, id: u32);
+    #[enumizer_priority(high)]
+    fn cancel_job(&mut self, id: u32);
+    #[enumizer_priority(low)]
+    fn log_len(&self) -> usize;
+}
+
+#[derive(Default)]
+struct Implementor {
+    log: Vec<String>,
+}
+
+impl Scheduler for Implementor {
+    fn pause(&mut self, gate: Receiver<()>) {
+        let _ = gate.recv();
+    }
+
+    fn submit_job(&mut self, id: u32) {
+        self.log.push(format!("submit {}", id));
+    }
+
+    fn cancel_job(&mut self, id: u32) {
+        self.log.push(format!("cancel {}", id));
+    }
+
+    fn log_len(&self) -> usize {
+        self.log.len()
+    }
+}
+
+#[test]
+fn priority_fn() {
+    assert_eq!(SchedulerEnum::SubmitJob { id: 1 }.priority(), Priority::Normal);
+    assert_eq!(SchedulerEnum::CancelJob { id: 1 }.priority(), Priority::High);
+    let (tx, _rx) = std::sync::mpsc::sync_channel(1);
+    assert_eq!(SchedulerEnum::LogLen { ret: tx }.priority(), Priority::Low);
+}
+
+#[test]
+fn high_priority_first() {
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<SchedulerEnum, _>(Implementor::default(), Mailbox::PriorityUnbounded);
+    let mut p = SchedulerProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    let (gate_tx, gate_rx) = channel();
+    p.pause(gate_rx);
+    for i in 1..=3 {
+        p.submit_job(i);
+    }
+    p.cancel_job(2);
+    p.cancel_job(3);
+    gate_tx.send(()).unwrap();
+    assert_eq!(p.log_len(), 5);
+    drop(p);
+    let o = join.join().unwrap();
+    assert_eq!(
+        o.log,
+        ["cancel 2", "cancel 3", "submit 1", "submit 2", "submit 3"]
+    );
+}
+
+#[test]
+#[should_panic(expected = "capacity of at least 1")]
+fn priority_rendezvous_rejected() {
+    let _ = trait_enumizer::actor::spawn_thread::<SchedulerEnum, _>(Implementor::default(), Mailbox::PriorityBounded(0));
+}
+
+#[cfg(feature = "tokio")]
+mod with_tokio {
+    use super::*;
+
+    // Single-threaded runtime: a sender blocking the thread would never let the actor drain the queue
+    #[tokio::test(flavor = "current_thread")]
+    async fn bounded_send_async() {
+        let (handle, join) =
+            trait_enumizer::actor::spawn_tokio::<SchedulerEnum, _>(Implementor::default(), Mailbox::PriorityBounded(1));
+        for id in 1..=10 {
+            handle.send_async(SchedulerEnum::SubmitJob { id }).await.unwrap();
+        }
+        handle.send_async(SchedulerEnum::CancelJob { id: 10 }).await.unwrap();
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        handle.send_async(SchedulerEnum::LogLen { ret: tx }).await.unwrap();
+        drop(handle);
+        let o = join.await.unwrap();
+        assert_eq!(rx.recv().unwrap(), 11);
+        assert_eq!(o.log.last().unwrap(), "cancel 10");
+    }
+}
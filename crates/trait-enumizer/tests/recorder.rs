@@ -0,0 +1,94 @@
+#![cfg(feature="std")]
+use std::convert::Infallible;
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=CanvasEnum,
+    returnval=stdmpsc_class,
+    recorder=CanvasRecorder,
+    proxy(Fn,name=CanvasProxy,resultified_trait=CanvasResultified),
+)]
+trait Canvas {
+    fn move_to(&mut self, x: i32, y: i32);
+    fn text(&mut self, #[enumizer_to_owned] s: &str);
+    fn measure(&self, s: String) -> u32;
+    fn flush(&mut self) -> ();
+}
+
+/// Code under test
+fn caption(c: &mut impl Canvas, s: &str) {
+    let width = c.measure(s.to_owned());
+    c.move_to(-(width as i32) / 2, 0);
+    c.text(s);
+    c.flush();
+}
+
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl Canvas for Log {
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.0.push(format!("move {} {}", x, y));
+    }
+
+    fn text(&mut self, s: &str) {
+        self.0.push(format!("text {}", s));
+    }
+
+    fn measure(&self, s: String) -> u32 {
+        s.len() as u32
+    }
+
+    fn flush(&mut self) {
+        self.0.push("flush".to_owned());
+    }
+}
+
+#[test]
+fn command_buffer() {
+    let mut rec = CanvasRecorder::new();
+    rec.canned_measure().push(10).respond_with(|msg| match msg {
+        CanvasEnum::Measure { s, .. } => s.len() as u32 * 2,
+        _ => unreachable!(),
+    });
+    caption(&mut rec, "hello");
+    caption(&mut rec, "abc");
+    assert_eq!(rec.len(), 8);
+
+    let calls = rec.take();
+    assert!(matches!(calls[1], CanvasEnum::MoveTo { x: -5, y: 0 }));
+    assert!(matches!(calls[5], CanvasEnum::MoveTo { x: -3, y: 0 }));
+    assert!(matches!(&calls[6], CanvasEnum::Text { s } if s == "abc"));
+    assert!(rec.is_empty());
+
+    caption(&mut rec, "hi");
+    let mut log = Log::default();
+    rec.replay_into(&mut log);
+    assert_eq!(log.0, ["move -2 0", "text hi", "flush"]);
+
+    rec.canned_measure().push(7);
+    assert_eq!(CanvasResultified::<Infallible>::try_measure(&rec, "x".to_owned()), Ok(Ok(7)));
+    assert_eq!(rec.drain().count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "no canned return value for `measure`")]
+fn no_canned_value() {
+    caption(&mut CanvasRecorder::new(), "hello");
+}
+
+#[trait_enumizer::enumizer(name=SessionEnum, recorder=SessionRecorder)]
+trait Session {
+    fn log(&self, s: String);
+    fn close(self: std::rc::Rc<Self>);
+}
+
+#[test]
+fn smart_pointer_receiver() {
+    let rec = std::rc::Rc::new(SessionRecorder::new());
+    rec.log("bye".to_owned());
+    rec.clone().close();
+    let calls = std::rc::Rc::try_unwrap(rec).ok().unwrap().take();
+    assert!(matches!(&calls[0], SessionEnum::Log { s } if s == "bye"));
+    assert!(matches!(calls[1], SessionEnum::Close {}));
+}
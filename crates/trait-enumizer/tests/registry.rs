@@ -0,0 +1,58 @@
+#![cfg(feature="std")]
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::registry::{NoSuchObject, Registry};
+use trait_enumizer::{stdmpsc_class, CallError};
+
+#[trait_enumizer::enumizer(
+    name=EntityEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=EntityProxy,resultified_trait=EntityResultified,object_id(u32)),
+)]
+trait Entity {
+    fn move_by(&mut self, dx: i32);
+    fn position(&self) -> i32;
+}
+
+struct Npc {
+    x: i32,
+}
+
+impl Entity for Npc {
+    fn move_by(&mut self, dx: i32) {
+        self.x += dx;
+    }
+
+    fn position(&self) -> i32 {
+        self.x
+    }
+}
+
+#[test]
+fn addressed_actor() {
+    let mut registry = Registry::new();
+    for id in 0..1000u32 {
+        registry.insert(id, Npc { x: id as i32 });
+    }
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<(u32, EntityEnum), _>(registry, Mailbox::Unbounded);
+    let sink = handle.sink();
+    drop(handle);
+
+    let npc = EntityProxy::<ActorStopped, _>(&sink, 7);
+    npc.try_move_by(3).unwrap();
+    assert_eq!(npc.try_position().unwrap().unwrap(), Ok(10));
+    let other = EntityProxy::<ActorStopped, _>(&sink, 999);
+    assert_eq!(other.try_position().unwrap().unwrap(), Ok(999));
+
+    let missing = EntityProxy::<ActorStopped, _>(&sink, 1000);
+    assert_eq!(missing.try_position().unwrap().unwrap(), Err(CallError::NoSuchObject));
+    // Without return value, the message is dropped
+    missing.try_move_by(1).unwrap();
+
+    drop(sink);
+    let mut registry = join.join().unwrap();
+    assert_eq!(registry.get(&7).unwrap().x, 10);
+    assert_eq!(registry.dispatch(&1000, EntityEnum::MoveBy { dx: 1 }), Err(NoSuchObject));
+    assert_eq!(registry.len(), 1000);
+}
@@ -0,0 +1,76 @@
+#![cfg(feature="std")]
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use trait_enumizer::{stdmpsc_class, CallError};
+
+#[trait_enumizer::enumizer(
+    name=StoreEnum,
+    returnval=stdmpsc_class,
+    call_errors,
+    call_fn(name=try_call_mut,ref_mut),
+    call_fn(name=try_call_read,ref,only(get, list)),
+    proxy(Fn,name=StoreProxy,unwrapping_impl),
+    proxy(Fn,name=ReadOnlyStoreProxy,only(get, list)),
+    proxy(Fn,name=NoDeleteStoreProxy,except(delete)),
+)]
+trait Store {
+    fn get(&self, key: String) -> Option<String>;
+    fn list(&self) -> Vec<String>;
+    fn put(&mut self, key: String, value: String);
+    fn delete(&mut self, key: String) -> bool;
+}
+
+#[derive(Default)]
+struct MemStore(BTreeMap<String, String>);
+
+impl Store for MemStore {
+    fn get(&self, key: String) -> Option<String> {
+        self.0.get(&key).cloned()
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+
+    fn put(&mut self, key: String, value: String) {
+        self.0.insert(key, value);
+    }
+
+    fn delete(&mut self, key: String) -> bool {
+        self.0.remove(&key).is_some()
+    }
+}
+
+#[test]
+fn read_only_plugin() {
+    let store = RefCell::new(MemStore::default());
+    let mut admin = StoreProxy(|msg: StoreEnum| msg.try_call_mut(&mut *store.borrow_mut()));
+    admin.put("a".to_owned(), "1".to_owned());
+    admin.put("b".to_owned(), "2".to_owned());
+
+    // Plugin code only sees `try_get` and `try_list`
+    let plugin = ReadOnlyStoreProxy(|msg: StoreEnum| msg.try_call_read(&*store.borrow()));
+    assert_eq!(plugin.try_get("a".to_owned()).unwrap().unwrap(), Ok(Some("1".to_owned())));
+    assert_eq!(plugin.try_list().unwrap().unwrap(), Ok(vec!["a".to_owned(), "b".to_owned()]));
+
+    let editor = NoDeleteStoreProxy(|msg: StoreEnum| msg.try_call_mut(&mut *store.borrow_mut()));
+    editor.try_put("c".to_owned(), "3".to_owned()).unwrap();
+    assert!(admin.delete("a".to_owned()));
+    assert_eq!(admin.list(), ["b", "c"]);
+}
+
+#[test]
+fn call_fn_rejects() {
+    let store = RefCell::new(MemStore::default());
+    store.borrow_mut().put("a".to_owned(), "1".to_owned());
+
+    // Even a full proxy cannot mutate through restricted call function
+    let sneaky = StoreProxy(|msg: StoreEnum| msg.try_call_read(&*store.borrow()));
+    match sneaky.try_delete("a".to_owned()).unwrap().unwrap() {
+        Err(CallError::NotAllowed(e)) => assert_eq!(e.method(), "delete"),
+        x => panic!("unexpected {:?}", x),
+    }
+    // No return value to report the error to, so the call is just dropped
+    sneaky.try_put("a".to_owned(), "2".to_owned()).unwrap();
+    assert_eq!(sneaky.get("a".to_owned()), Some("1".to_owned()));
+}
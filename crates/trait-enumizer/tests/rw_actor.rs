@@ -0,0 +1,127 @@
+#![cfg(feature="std")]
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+use trait_enumizer::actor::{ActorStopped, Mailbox, RwOrdering};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=CacheEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_once,once,rw_dispatch),
+    proxy(Fn,name=CacheProxy),
+)]
+trait Cache {
+    fn get(&self, k: u32) -> Option<String>;
+    fn put(&mut self, k: u32, v: String);
+    fn slow_get(&self, k: u32) -> Option<String>;
+    fn failing_get(&self, k: u32) -> Option<String>;
+    fn into_len(self) -> usize;
+}
+
+#[derive(Default)]
+struct Implementor {
+    map: HashMap<u32, String>,
+    readers: AtomicUsize,
+    max_readers: AtomicUsize,
+    /// Makes `slow_get` calls wait for each other, so they are certainly running in parallel
+    barrier: Option<Barrier>,
+}
+
+impl Cache for Implementor {
+    fn get(&self, k: u32) -> Option<String> {
+        self.map.get(&k).cloned()
+    }
+
+    fn put(&mut self, k: u32, v: String) {
+        self.map.insert(k, v);
+    }
+
+    fn slow_get(&self, k: u32) -> Option<String> {
+        let n = self.readers.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_readers.fetch_max(n, Ordering::SeqCst);
+        if let Some(barrier) = &self.barrier {
+            barrier.wait();
+        }
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+        self.get(k)
+    }
+
+    fn failing_get(&self, k: u32) -> Option<String> {
+        panic!("failing_get {}", k)
+    }
+
+    fn into_len(self) -> usize {
+        self.map.len()
+    }
+}
+
+#[test]
+fn parallel_reads() {
+    let (handle, join) = trait_enumizer::actor::spawn_rw_thread::<CacheEnum, _>(
+        Implementor {
+            barrier: Some(Barrier::new(4)),
+            ..Default::default()
+        },
+        Mailbox::Unbounded,
+        4,
+        RwOrdering::Strict,
+    );
+    let p = CacheProxy::<ActorStopped, _>(handle.sink());
+    p.try_put(1, "one".to_owned()).unwrap();
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let p = CacheProxy::<ActorStopped, _>(handle.sink());
+            std::thread::spawn(move || p.try_slow_get(1).unwrap().unwrap())
+        })
+        .collect();
+    for r in readers {
+        assert_eq!(r.join().unwrap().as_deref(), Some("one"));
+    }
+    drop(p);
+    drop(handle);
+    let o = join.join().unwrap().unwrap();
+    assert_eq!(o.max_readers.load(Ordering::SeqCst), 4);
+    assert_eq!(o.map.len(), 1);
+}
+
+#[test]
+fn writes_and_consume() {
+    for ordering in [RwOrdering::Strict, RwOrdering::Relaxed] {
+        let (handle, join) = trait_enumizer::actor::spawn_rw_thread::<CacheEnum, _>(
+            Implementor::default(),
+            Mailbox::Bounded(2),
+            2,
+            ordering,
+        );
+        let p = CacheProxy::<ActorStopped, _>(handle.sink());
+        for i in 0..10 {
+            p.try_put(i, i.to_string()).unwrap();
+            assert_eq!(p.try_get(i).unwrap().unwrap(), Some(i.to_string()));
+        }
+        assert_eq!(p.try_into_len().unwrap().unwrap(), 10);
+        assert!(join.join().unwrap().is_none());
+        assert!(p.try_get(1).is_err());
+    }
+}
+
+#[test]
+fn panicking_reads() {
+    let (handle, join) = trait_enumizer::actor::spawn_rw_thread::<CacheEnum, _>(
+        Implementor::default(),
+        Mailbox::Unbounded,
+        2,
+        RwOrdering::Strict,
+    );
+    let p = CacheProxy::<ActorStopped, _>(handle.sink());
+    p.try_put(1, "one".to_owned()).unwrap();
+    assert!(p.try_failing_get(1).unwrap().is_err());
+    assert_eq!(p.try_get(1).unwrap().unwrap().as_deref(), Some("one"));
+    assert!(p.try_failing_get(2).unwrap().is_err());
+    assert!(p.try_failing_get(3).unwrap().is_err());
+    p.try_put(2, "two".to_owned()).unwrap();
+    assert_eq!(p.try_get(2).unwrap().unwrap().as_deref(), Some("two"));
+    drop(p);
+    drop(handle);
+    assert_eq!(join.join().unwrap().unwrap().map.len(), 2);
+}
@@ -0,0 +1,78 @@
+#![cfg(feature="std")]
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=InventoryEnum,
+    returnval=stdmpsc_class,
+    split_enums,
+    proxy(Fn,name=InventoryProxy,unwrapping_impl),
+    enum_attr[derive(Debug)],
+)]
+trait Inventory {
+    fn count(&self, item: String) -> u32;
+    fn items(&self) -> Vec<String>;
+    fn add(&mut self, item: String, n: u32);
+    fn take(&mut self, item: String) -> bool;
+}
+
+#[derive(Default, Clone)]
+struct Warehouse(BTreeMap<String, u32>);
+
+impl Inventory for Warehouse {
+    fn count(&self, item: String) -> u32 {
+        self.0.get(&item).copied().unwrap_or(0)
+    }
+
+    fn items(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+
+    fn add(&mut self, item: String, n: u32) {
+        *self.0.entry(item).or_default() += n;
+    }
+
+    fn take(&mut self, item: String) -> bool {
+        match self.0.get_mut(&item) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn primary_and_replica() {
+    let primary = RefCell::new(Warehouse::default());
+    let replica = RefCell::new(Warehouse::default());
+    let mut inventory = InventoryProxy(|msg: InventoryEnum| match InventoryQuery::try_from(msg) {
+        Ok(query) => query.try_call(&*replica.borrow()),
+        Err(msg) => {
+            let command = InventoryCommand::try_from(msg).unwrap();
+            let result = command.try_call_mut(&mut *primary.borrow_mut());
+            replica.replace(primary.borrow().clone());
+            result
+        }
+    });
+
+    inventory.add("bolt".to_owned(), 2);
+    assert!(inventory.take("bolt".to_owned()));
+    assert!(!inventory.take("nut".to_owned()));
+    assert_eq!(inventory.count("bolt".to_owned()), 1);
+    assert_eq!(inventory.items(), ["bolt"]);
+    assert_eq!(replica.borrow().0, primary.borrow().0);
+}
+
+#[test]
+fn conversions() {
+    let msg = InventoryEnum::from(InventoryCommand::Add { item: "nut".to_owned(), n: 5 });
+    assert!(matches!(msg, InventoryEnum::Add { n: 5, .. }));
+    let msg = InventoryQuery::try_from(msg).unwrap_err();
+    let InventoryCommand::Add { item, .. } = InventoryCommand::try_from(msg).unwrap() else {
+        panic!("expected `Add`");
+    };
+    assert_eq!(item, "nut");
+}
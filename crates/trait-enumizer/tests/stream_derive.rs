@@ -0,0 +1,102 @@
+#![cfg(feature="flume")]
+#![cfg(feature="futures")]
+
+use trait_enumizer::flume_class;
+
+#[trait_enumizer::enumizer(
+    name=LogEnum,
+    returnval=flume_class,
+    call_fn(name=try_call, ref),
+    proxy(Fn,name=LogProxy,unwrapping_impl),
+)]
+trait Log {
+    fn tail(&self, n: usize) -> impl Iterator<Item = String>;
+    #[enumizer_stream]
+    fn sizes(&self) -> Vec<usize>;
+    fn len(&self) -> usize;
+}
+
+struct Implementor(Vec<String>);
+
+impl Log for Implementor {
+    fn tail(&self, n: usize) -> impl Iterator<Item = String> {
+        self.0[self.0.len() - n..].iter().cloned()
+    }
+
+    fn sizes(&self) -> Vec<usize> {
+        self.0.iter().map(|x| x.len()).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+fn sample() -> Implementor {
+    Implementor(vec!["a".to_owned(), "bb".to_owned(), "ccc".to_owned()])
+}
+
+#[test]
+fn iterator() {
+    let (tx, rx) = flume::bounded::<LogEnum>(1);
+    std::thread::spawn(move || {
+        let o = sample();
+        for msg in rx {
+            msg.try_call(&o).unwrap();
+        }
+    });
+    let p = LogProxy::<_, _>(|c| tx.send(c));
+    let items: Vec<_> = p.try_tail(2).unwrap().collect();
+    assert_eq!(items, vec![Ok("bb".to_owned()), Ok("ccc".to_owned())]);
+    assert_eq!(p.tail(1).collect::<Vec<_>>(), vec!["ccc".to_owned()]);
+    assert_eq!(p.sizes(), vec![1, 2, 3]);
+    assert_eq!(p.len(), 3);
+}
+
+#[test]
+fn callee_gone() {
+    let p = LogProxy::<std::convert::Infallible, _>(|c| {
+        if let LogEnum::Sizes { ret } = c {
+            ret.send(Some(42)).unwrap();
+        }
+        Ok(())
+    });
+    let items: Vec<_> = p.try_sizes().unwrap().collect();
+    assert_eq!(items, vec![Ok(42), Err(flume::RecvError::Disconnected)]);
+}
+
+struct Ticker;
+
+#[trait_enumizer::enumizer(
+    name=TickerEnum,
+    inherent_impl,
+    returnval=flume_class,
+    call_fn(name=try_call, ref, async),
+    proxy(Fn,name=TickerProxy,async),
+)]
+impl Ticker {
+    fn ticks(&self, n: u32) -> impl futures::Stream<Item = u32> {
+        futures::stream::iter(0..n)
+    }
+}
+
+#[test]
+fn stream() {
+    use futures::StreamExt;
+
+    let (tx, rx) = flume::bounded::<TickerEnum>(1);
+    std::thread::spawn(move || {
+        futures::executor::block_on(async {
+            let o = Ticker;
+            while let Ok(msg) = rx.recv_async().await {
+                msg.try_call(&o).await.unwrap();
+            }
+        })
+    });
+    let p = TickerProxy::<_, _, _>(|c| tx.send_async(c));
+    futures::executor::block_on(async {
+        let mut items = p.try_ticks(3).await.unwrap();
+        assert_eq!(items.next_async().await, Some(Ok(0)));
+        assert_eq!(items.into_stream().collect::<Vec<_>>().await, vec![Ok(1), Ok(2)]);
+    });
+}
@@ -0,0 +1,181 @@
+#![cfg(feature="tracing")]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_core::span::Current;
+use tracing::{Event, Metadata};
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=KvEnum,
+    returnval=stdmpsc_class,
+    trace,
+    call_fn(name=try_call_mut,ref_mut,dispatch),
+    proxy(Fn,name=KvProxy,unwrapping_impl),
+)]
+trait Kv {
+    fn put(&mut self, #[enumizer_trace] key: String, value: Vec<u8>);
+    fn len(&self) -> usize;
+}
+
+#[derive(Default)]
+struct Implementor {
+    map: HashMap<String, Vec<u8>>,
+}
+
+impl Kv for Implementor {
+    fn put(&mut self, key: String, value: Vec<u8>) {
+        self.map.insert(key, value);
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+#[derive(Debug)]
+struct SpanRecord {
+    name: &'static str,
+    metadata: &'static Metadata<'static>,
+    parent: Option<u64>,
+    fields: HashMap<&'static str, String>,
+}
+
+impl Visit for SpanRecord {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.fields.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+thread_local! {
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Just remembers all spans with their parents and fields
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<HashMap<u64, SpanRecord>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl tracing::Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let parent = if let Some(p) = span.parent() {
+            Some(p.into_u64())
+        } else if span.is_contextual() {
+            ENTERED.with(|e| e.borrow().last().copied())
+        } else {
+            None
+        };
+        let mut record = SpanRecord {
+            name: span.metadata().name(),
+            metadata: span.metadata(),
+            parent,
+            fields: HashMap::new(),
+        };
+        span.record(&mut record);
+        self.spans.lock().unwrap().insert(id, record);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        values.record(self.spans.lock().unwrap().get_mut(&span.into_u64()).unwrap());
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|e| e.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, _span: &Id) {
+        ENTERED.with(|e| e.borrow_mut().pop());
+    }
+
+    fn current_span(&self) -> Current {
+        let spans = self.spans.lock().unwrap();
+        match ENTERED.with(|e| e.borrow().last().copied()) {
+            Some(id) => Current::new(Id::from_u64(id), spans[&id].metadata),
+            None => Current::none(),
+        }
+    }
+}
+
+#[test]
+fn spans_cross_actor_thread() {
+    let recorder = Recorder::default();
+    tracing::subscriber::set_global_default(recorder.clone()).unwrap();
+
+    let (handle, join) =
+        trait_enumizer::actor::spawn_thread::<KvEnum, _>(Implementor::default(), Mailbox::Unbounded);
+    let mut p = KvProxy::<ActorStopped, _>(handle.sink());
+    drop(handle);
+    tracing::info_span!("request").in_scope(|| {
+        p.put("a".to_owned(), vec![1, 2, 3]);
+        assert_eq!(p.len(), 1);
+    });
+    drop(p);
+    join.join().unwrap();
+
+    let spans = recorder.spans.lock().unwrap();
+    let parent_name = |s: &SpanRecord| s.parent.map(|p| spans[&p].name);
+    let (_, callee) = spans
+        .iter()
+        .find(|(_, s)| s.name == "put" && parent_name(s) == Some("put"))
+        .unwrap();
+    assert_eq!(callee.fields["key"], "\"a\"");
+    assert_eq!(callee.fields["returned"], "true");
+    assert!(callee.fields.contains_key("elapsed_us"));
+    assert!(!callee.fields.contains_key("value"));
+    let caller = &spans[&callee.parent.unwrap()];
+    assert_eq!(caller.fields["key"], "\"a\"");
+    assert_eq!(caller.fields["returned"], "true");
+    assert!(caller.fields.contains_key("elapsed_us"));
+    assert_eq!(parent_name(caller), Some("request"));
+
+    assert_eq!(spans.values().filter(|s| s.name == "len").count(), 2);
+}
+
+#[cfg(feature = "serde")]
+mod serialized {
+    use trait_enumizer::TraceContext;
+
+    #[trait_enumizer::enumizer(
+        name=CounterEnum,
+        trace,
+        enum_attr[derive(Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)],
+        call_fn(name=call_mut,ref_mut),
+    )]
+    trait Counter {
+        fn add(&mut self, #[enumizer_trace] x: u32);
+    }
+
+    impl Counter for u32 {
+        fn add(&mut self, x: u32) {
+            *self += x;
+        }
+    }
+
+    #[test]
+    fn serde_derives() {
+        let msg = CounterEnum::Add { x: 2, trace: TraceContext::current() };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(json, r#"{"Add":{"x":2,"trace":null}}"#);
+        let received: CounterEnum = serde_json::from_str(&json).unwrap();
+        assert_eq!(received, msg);
+        let mut n = 1;
+        received.call_mut(&mut n);
+        assert_eq!(n, 3);
+    }
+}
@@ -0,0 +1,97 @@
+#![cfg(feature="std")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use trait_enumizer::actor::{ActorStopped, Mailbox};
+use trait_enumizer::stdmpsc_class;
+
+#[trait_enumizer::enumizer(
+    name=CounterEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_arc,arc,dispatch),
+    proxy(Fn,name=CounterProxy,unwrapping_impl),
+)]
+trait Counter {
+    fn total(&self) -> u32;
+    fn add_in_background(self: Arc<Self>, n: u32) -> u32;
+}
+
+#[derive(Default)]
+struct SharedCounter(AtomicU32);
+
+impl Counter for SharedCounter {
+    fn total(&self) -> u32 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn add_in_background(self: Arc<Self>, n: u32) -> u32 {
+        std::thread::spawn(move || self.0.fetch_add(n, Ordering::SeqCst) + n).join().unwrap()
+    }
+}
+
+#[trait_enumizer::enumizer(
+    name=ParserEnum,
+    returnval=stdmpsc_class,
+    call_fn(name=try_call_pin,pin_mut,allow_panic),
+    call_fn(name=try_call_boxed,boxed),
+    proxy(FnMut,name=ParserProxy,unwrapping_impl),
+)]
+trait Parser {
+    fn feed(self: Pin<&mut Self>, byte: u8) -> bool;
+    fn finish(self: Box<Self>) -> Vec<String>;
+}
+
+#[derive(Default)]
+struct LineParser {
+    current: String,
+    lines: Vec<String>,
+}
+
+impl Parser for LineParser {
+    fn feed(self: Pin<&mut Self>, byte: u8) -> bool {
+        let this = self.get_mut();
+        if byte == b'\n' {
+            this.lines.push(std::mem::take(&mut this.current));
+            true
+        } else {
+            this.current.push(byte as char);
+            false
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<String> {
+        self.lines
+    }
+}
+
+#[test]
+fn shared_service() {
+    let (handle, join) = trait_enumizer::actor::spawn_thread::<CounterEnum, _>(Arc::new(SharedCounter::default()), Mailbox::Unbounded);
+    let counter = Arc::new(CounterProxy::<ActorStopped, _>(handle.sink()));
+    drop(handle);
+
+    assert_eq!(counter.clone().add_in_background(2), 2);
+    assert_eq!(counter.clone().add_in_background(3), 5);
+    assert_eq!(counter.total(), 5);
+
+    drop(counter);
+    let service = join.join().unwrap();
+    assert_eq!(Arc::strong_count(&service), 1);
+}
+
+#[test]
+fn pinned_and_boxed() {
+    let mut parser = Box::pin(LineParser::default());
+    let mut complete = vec![];
+    {
+        let mut proxy = ParserProxy(|msg: ParserEnum| msg.try_call_pin(parser.as_mut()));
+        for byte in b"ab\ncd\ne" {
+            complete.push(Pin::new(&mut proxy).feed(*byte));
+        }
+    }
+    assert_eq!(complete, [false, false, true, false, false, true, false]);
+
+    let mut parser = Some(Pin::into_inner(parser));
+    let proxy = ParserProxy(|msg: ParserEnum| msg.try_call_boxed(parser.take().unwrap()));
+    assert_eq!(Box::new(proxy).finish(), ["ab", "cd"]);
+}